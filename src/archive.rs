@@ -0,0 +1,230 @@
+//! A minimal reader for `ar` archives, to check that a static library found
+//! for a `-l` flag is usable before linking it statically, see
+//! `Config::verify_archives`, and which symbols it defines.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+/// The size of a member header.
+const HEADER_LEN: usize = 60;
+
+/// The longest BSD long name read, well beyond those of the symbol indexes,
+/// so that a corrupt length doesn't make us allocate it.
+const MAX_LONG_NAME: usize = 256;
+
+/// Whether the file at `path` is an `ar` archive whose symbol index lists at
+/// least one symbol.
+///
+/// The index is the first member of the archives made by `ar` and `ranlib`:
+/// `/` or `/SYM64/` in the GNU and COFF variants, `__.SYMDEF` (possibly
+/// `SORTED` or `_64`) in the BSD one. Archives without one can't be linked
+/// anyway.
+pub fn has_symbols(path: &Path) -> bool {
+    read_symbol_count(path).is_ok_and(|count| count > 0)
+}
+
+/// The names of the symbols listed in the index of the `ar` archive at
+/// `path`, empty if it has none.
+pub fn symbols(path: &Path) -> io::Result<Vec<String>> {
+    let (name, file) = match read_index(path)? {
+        Some(index) => index,
+        None => return Ok(Vec::new()),
+    };
+    let mut file = BufReader::new(file);
+    // The GNU variants list the offsets of the members defining each symbol,
+    // then their names, the BSD ones pairs of name and member offsets, then
+    // the names.
+    let names = match &name[..] {
+        "/" => {
+            let count = read_u32_be(&mut file)? as u64;
+            skip(&mut file, count * 4)?;
+            read_names(&mut file, count)?
+        }
+        "/SYM64/" => {
+            let count = read_u64_be(&mut file)?;
+            skip(&mut file, count.checked_mul(8).ok_or_else(invalid)?)?;
+            read_names(&mut file, count)?
+        }
+        _ => {
+            let wide = name.starts_with("__.SYMDEF_64");
+            let (ranlib_len, entry_len) = if wide {
+                (read_u64_le(&mut file)?, 16)
+            } else {
+                (read_u32_le(&mut file)? as u64, 8)
+            };
+            let mut offsets = Vec::new();
+            for _ in 0..ranlib_len / entry_len {
+                offsets.push(if wide { read_u64_le(&mut file)? } else {
+                    read_u32_le(&mut file)? as u64
+                });
+                skip(&mut file, entry_len / 2)?;
+            }
+            let strtab_len = if wide { read_u64_le(&mut file)? } else {
+                read_u32_le(&mut file)? as u64
+            };
+            let mut strtab = Vec::new();
+            file.by_ref().take(strtab_len).read_to_end(&mut strtab)?;
+            offsets.iter().filter_map(|&offset| {
+                let name = strtab.get(offset as usize..)?;
+                let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                Some(String::from_utf8_lossy(&name[..end]).into_owned())
+            }).collect()
+        }
+    };
+    Ok(names)
+}
+
+fn read_symbol_count(path: &Path) -> io::Result<u64> {
+    let (name, mut file) = match read_index(path)? {
+        Some(index) => index,
+        None => return Ok(0),
+    };
+    match &name[..] {
+        "/" => Ok(read_u32_be(&mut file)? as u64),
+        "/SYM64/" => read_u64_be(&mut file),
+        "__.SYMDEF" | "__.SYMDEF SORTED" => Ok(read_u32_le(&mut file)? as u64 / 8),
+        _ => Ok(read_u64_le(&mut file)? / 16),
+    }
+}
+
+/// The name of the symbol index of the archive at `path`, and the archive
+/// positioned at its contents, if it has one.
+fn read_index(path: &Path) -> io::Result<Option<(String, File)>> {
+    let mut file = File::open(path)?;
+    let mut magic = [0; 8];
+    file.read_exact(&mut magic)?;
+    if &magic != b"!<arch>\n" && &magic != b"!<thin>\n" {
+        return Ok(None);
+    }
+    let mut header = [0; HEADER_LEN];
+    file.read_exact(&mut header)?;
+    if &header[58..] != b"`\n" {
+        return Ok(None);
+    }
+    let name = String::from_utf8_lossy(&header[..16]).trim_end().to_string();
+
+    // BSD archives store long names, including `__.SYMDEF SORTED`, right
+    // after the header, e.g. `#1/20`.
+    let name = match name.strip_prefix("#1/") {
+        Some(len) => {
+            let len = len.parse::<usize>().map_err(|_| invalid())?;
+            if len > MAX_LONG_NAME {
+                return Ok(None);
+            }
+            let mut long_name = vec![0; len];
+            file.read_exact(&mut long_name)?;
+            String::from_utf8_lossy(&long_name).trim_end_matches('\0').to_string()
+        }
+        None => name,
+    };
+
+    match &name[..] {
+        "/" | "/SYM64/" | "__.SYMDEF" | "__.SYMDEF SORTED" | "__.SYMDEF_64" |
+        "__.SYMDEF_64 SORTED" => Ok(Some((name, file))),
+        _ => Ok(None),
+    }
+}
+
+/// Read `count` NUL-terminated names.
+fn read_names<R: Read>(file: &mut R, count: u64) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    let mut name = Vec::new();
+    let mut byte = [0];
+    while (names.len() as u64) < count {
+        file.read_exact(&mut byte)?;
+        if byte[0] == 0 {
+            names.push(String::from_utf8_lossy(&name).into_owned());
+            name.clear();
+        } else {
+            name.push(byte[0]);
+        }
+    }
+    Ok(names)
+}
+
+fn skip<R: Read>(file: &mut R, len: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut file.by_ref().take(len), &mut io::sink())?;
+    if skipped < len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated symbol index"));
+    }
+    Ok(())
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid archive member header")
+}
+
+fn read_u32_be<R: Read>(file: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    file.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u32_le<R: Read>(file: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    file.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64_be<R: Read>(file: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    file.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn read_u64_le<R: Read>(file: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    file.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[test]
+fn has_symbols_test() {
+    use std::fs;
+    use TempDir;
+
+    let dir = TempDir::new("archive");
+    let header = |name: &str, size: usize| format!("{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                                                    name, 0, 0, 0, 644, size);
+    let write = |file: &str, contents: &[u8]| {
+        let path = dir.join(file);
+        fs::write(&path, contents).unwrap();
+        path
+    };
+
+    let mut gnu = b"!<arch>\n".to_vec();
+    gnu.extend(header("/", 12).as_bytes());
+    gnu.extend(&[0, 0, 0, 1, 0, 0, 0, 0x44, b'f', b'o', b'o', 0]);
+    assert!(has_symbols(&write("libgnu.a", &gnu)));
+    assert_eq!(symbols(&dir.join("libgnu.a")).unwrap(), vec!["foo"]);
+
+    let mut bsd = b"!<arch>\n".to_vec();
+    bsd.extend(header("#1/20", 32).as_bytes());
+    bsd.extend(b"__.SYMDEF SORTED\0\0\0\0");
+    bsd.extend(&[8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert!(has_symbols(&write("libbsd.a", &bsd)));
+
+    let mut bsd_names = b"!<arch>\n".to_vec();
+    bsd_names.extend(header("#1/20", 40).as_bytes());
+    bsd_names.extend(b"__.SYMDEF SORTED\0\0\0\0");
+    bsd_names.extend(&[8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, b'b', b'a', b'r', 0]);
+    assert_eq!(symbols(&write("libbsd-names.a", &bsd_names)).unwrap(), vec!["bar"]);
+
+    let mut empty_index = b"!<arch>\n".to_vec();
+    empty_index.extend(header("/", 4).as_bytes());
+    empty_index.extend(&[0, 0, 0, 0]);
+    assert!(!has_symbols(&write("libempty-index.a", &empty_index)));
+
+    let mut huge_name = b"!<arch>\n".to_vec();
+    huge_name.extend(header("#1/9999999999999", 0).as_bytes());
+    assert!(!has_symbols(&write("libhuge-name.a", &huge_name)));
+
+    let mut no_index = b"!<arch>\n".to_vec();
+    no_index.extend(header("foo.o/", 4).as_bytes());
+    no_index.extend(b"\x7fELF");
+    assert!(!has_symbols(&write("libno-index.a", &no_index)));
+
+    assert!(!has_symbols(&write("libempty.a", b"")));
+    assert!(!has_symbols(&write("libscript.a", b"INPUT(-lfoo)\n")));
+}
@@ -0,0 +1,197 @@
+//! Resolution of library names to the files a linker would pick up.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use archive;
+#[cfg(feature = "elf")]
+use elf;
+
+/// The kind of file a library resolved to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArtifactKind {
+    /// A static archive, e.g. `libfoo.a` or `foo.lib`.
+    Static,
+    /// A shared library, e.g. `libfoo.so`, `libfoo.so.1.2`, `libfoo.dylib` or
+    /// `foo.dll`.
+    Shared,
+    /// A MinGW import library (`libfoo.dll.a`) standing in for a DLL.
+    ImportLibrary,
+}
+
+impl ArtifactKind {
+    /// Classify a library file by its name.
+    ///
+    /// Note that MSVC import libraries share the `.lib` extension with static
+    /// archives and are reported as `Static`.
+    pub fn from_path(path: &Path) -> Option<ArtifactKind> {
+        let name = path.file_name().and_then(|n| n.to_str())?;
+        if name.ends_with(".dll.a") {
+            Some(ArtifactKind::ImportLibrary)
+        } else if name.ends_with(".a") || name.ends_with(".lib") {
+            Some(ArtifactKind::Static)
+        } else if name.ends_with(".so") || name.ends_with(".dylib") ||
+                  name.ends_with(".dll") || is_versioned_so(name) {
+            Some(ArtifactKind::Shared)
+        } else {
+            None
+        }
+    }
+}
+
+/// A library file found in the link paths of a `Library`.
+#[derive(Clone, Debug)]
+#[allow(clippy::manual_non_exhaustive)]
+pub struct Artifact {
+    /// The library name, as passed to `-l`.
+    pub name: String,
+    /// The file found in the link paths.
+    pub path: PathBuf,
+    /// `path` with all symlinks resolved.
+    pub real_path: PathBuf,
+    pub kind: ArtifactKind,
+    /// The soname of a shared library, if the symlink chain from `path` to
+    /// `real_path` goes through one (e.g. `libfoo.so.1` in
+    /// `libfoo.so -> libfoo.so.1 -> libfoo.so.1.2.3`).
+    ///
+    /// With the `elf` feature, the `DT_SONAME` of ELF libraries is used
+    /// instead.
+    pub soname: Option<String>,
+    _priv: (),
+}
+
+impl Artifact {
+    /// Find the file the linker would use for `-l<name>` with the `-L`
+    /// directories `dirs`, preferring shared libraries the way linkers do by
+    /// default.
+    pub fn resolve(name: &str, dirs: &[PathBuf]) -> Option<Artifact> {
+        let candidates = [
+            format!("lib{}.so", name),
+            format!("lib{}.dylib", name),
+            format!("lib{}.dll.a", name),
+            format!("{}.dll.a", name),
+            format!("lib{}.a", name),
+            format!("{}.lib", name),
+        ];
+        for dir in dirs {
+            for candidate in &candidates {
+                let path = dir.join(candidate);
+                if path.exists() {
+                    return Artifact::from_path(name, path);
+                }
+            }
+        }
+        None
+    }
+
+    /// Describe the library file at `path`, following its symlink chain.
+    pub fn from_path(name: &str, path: PathBuf) -> Option<Artifact> {
+        let kind = ArtifactKind::from_path(&path)?;
+        let real_path = fs::canonicalize(&path).ok()?;
+
+        let mut soname = None;
+        if kind == ArtifactKind::Shared {
+            let mut link = path.clone();
+            for _ in 0..32 {
+                if let Some(file_name) = link.file_name().and_then(|n| n.to_str()) {
+                    if is_soname(file_name) {
+                        soname = Some(file_name.to_string());
+                        break;
+                    }
+                }
+                match fs::read_link(&link) {
+                    Ok(target) => {
+                        link = link.parent().map(|p| p.join(&target)).unwrap_or(target)
+                    }
+                    Err(_) => break,
+                }
+            }
+        }
+        #[cfg(feature = "elf")]
+        {
+            if kind == ArtifactKind::Shared {
+                if let Some(elf_soname) = elf::read_dynamic(&real_path).and_then(|d| d.soname) {
+                    soname = Some(elf_soname);
+                }
+            }
+        }
+
+        Some(Artifact {
+            name: name.to_string(),
+            path,
+            real_path,
+            kind,
+            soname,
+            _priv: (),
+        })
+    }
+
+    /// The names of the shared libraries this artifact depends on at runtime,
+    /// i.e. its `DT_NEEDED` entries.
+    ///
+    /// Empty for anything but ELF shared libraries.
+    #[cfg(feature = "elf")]
+    pub fn needed(&self) -> Vec<String> {
+        if self.kind != ArtifactKind::Shared {
+            return Vec::new();
+        }
+        elf::read_dynamic(&self.real_path).map(|d| d.needed).unwrap_or_default()
+    }
+
+    /// The symbols the library defines, from the index of a static archive
+    /// or import library or, with the `elf` feature, the dynamic symbol
+    /// table of an ELF shared library. `None` if they couldn't be read, e.g.
+    /// for other shared libraries.
+    pub fn symbols(&self) -> Option<Vec<String>> {
+        match self.kind {
+            #[cfg(feature = "elf")]
+            ArtifactKind::Shared => elf::read_defined_symbols(&self.real_path),
+            #[cfg(not(feature = "elf"))]
+            ArtifactKind::Shared => None,
+            ArtifactKind::Static | ArtifactKind::ImportLibrary => {
+                archive::symbols(&self.real_path).ok()
+            }
+        }
+    }
+}
+
+/// Whether `name` looks like `libfoo.so.1` or `libfoo.so.1.2.3`.
+fn is_versioned_so(name: &str) -> bool {
+    match name.rfind(".so.") {
+        Some(i) => name[i + 4..].split('.').all(|v| !v.is_empty() &&
+                                                  v.chars().all(|c| c.is_ascii_digit())),
+        None => false,
+    }
+}
+
+/// Whether `name` has the conventional soname shape, `libfoo.so.<major>` or
+/// `libfoo.<major>.dylib`.
+fn is_soname(name: &str) -> bool {
+    if is_versioned_so(name) {
+        let i = name.rfind(".so.").unwrap();
+        return !name[i + 4..].contains('.');
+    }
+    if let Some(stem) = name.strip_suffix(".dylib") {
+        if let Some(i) = stem.rfind('.') {
+            let major = &stem[i + 1..];
+            return !major.is_empty() && major.chars().all(|c| c.is_ascii_digit());
+        }
+    }
+    false
+}
+
+#[test]
+fn artifact_kind_test() {
+    let kind = |name: &str| ArtifactKind::from_path(Path::new(name));
+    assert_eq!(kind("libfoo.dll.a"), Some(ArtifactKind::ImportLibrary));
+    assert_eq!(kind("libfoo.a"), Some(ArtifactKind::Static));
+    assert_eq!(kind("foo.lib"), Some(ArtifactKind::Static));
+    assert_eq!(kind("libfoo.so"), Some(ArtifactKind::Shared));
+    assert_eq!(kind("libfoo.so.1.2.3"), Some(ArtifactKind::Shared));
+    assert_eq!(kind("libfoo.1.dylib"), Some(ArtifactKind::Shared));
+    assert_eq!(kind("libfoo.so.debug"), None);
+    assert!(is_soname("libfoo.so.1"));
+    assert!(is_soname("libfoo.1.dylib"));
+    assert!(!is_soname("libfoo.so.1.2"));
+    assert!(!is_soname("libfoo.dylib"));
+}
@@ -0,0 +1,65 @@
+//! Locating the `.pc` files of Homebrew and MacPorts on macOS, see
+//! `Config::macos_package_managers`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str;
+use std::sync::Mutex;
+
+/// The output of `brew --prefix`, once it has been run.
+static BREW_PREFIX: Mutex<Option<Option<PathBuf>>> = Mutex::new(None);
+
+/// The Homebrew prefix: `homebrew_prefix` (the `HOMEBREW_PREFIX` set by
+/// `brew shellenv`) if given, else the one `brew --prefix` reports, else the
+/// default prefix on Apple Silicon or Intel Macs, whichever exists.
+pub fn homebrew_prefix(homebrew_prefix: Option<PathBuf>) -> Option<PathBuf> {
+    if homebrew_prefix.is_some() {
+        return homebrew_prefix;
+    }
+    let prefix = BREW_PREFIX.lock().unwrap().get_or_insert_with(|| {
+        let output = Command::new("brew").arg("--prefix").output().ok()?;
+        let prefix = str::from_utf8(&output.stdout).ok()?.trim();
+        if !output.status.success() || prefix.is_empty() {
+            return None;
+        }
+        Some(PathBuf::from(prefix))
+    }).clone();
+    prefix.or_else(|| {
+        ["/opt/homebrew", "/usr/local"].iter()
+                                       .map(PathBuf::from)
+                                       .find(|p| p.join("bin/brew").is_file())
+    })
+}
+
+/// The `pkgconfig` directories of the Homebrew prefix `prefix`, followed by
+/// those of its keg-only formulas, which aren't linked into the prefix.
+///
+/// Formulas are listed in reverse order of their names, so that e.g.
+/// `openssl@3` comes before `openssl@1.1`.
+pub fn homebrew_dirs(prefix: &Path) -> Vec<PathBuf> {
+    let mut dirs = pkgconfig_dirs(prefix);
+    let mut formulas = match fs::read_dir(prefix.join("opt")) {
+        Ok(entries) => entries.flatten().map(|e| e.path()).collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+    formulas.sort();
+    for formula in formulas.iter().rev() {
+        dirs.extend(pkgconfig_dirs(formula));
+    }
+    dirs
+}
+
+/// The `pkgconfig` directories of MacPorts, if it's installed.
+pub fn macports_dirs() -> Vec<PathBuf> {
+    pkgconfig_dirs(Path::new("/opt/local"))
+}
+
+/// The `lib/pkgconfig` and `share/pkgconfig` directories of `prefix` that
+/// exist.
+fn pkgconfig_dirs(prefix: &Path) -> Vec<PathBuf> {
+    ["lib/pkgconfig", "share/pkgconfig"].iter()
+                                        .map(|dir| prefix.join(dir))
+                                        .filter(|dir| dir.is_dir())
+                                        .collect()
+}
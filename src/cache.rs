@@ -0,0 +1,84 @@
+//! Caching of "package not found" results, see `Config::negative_cache`.
+//!
+//! Results are keyed by the package name and `Config::fingerprint`, so any
+//! change to the configuration or environment misses the cache. The cache
+//! file has one `<fingerprint> <name> <seconds since the epoch>` line per
+//! missing package.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use Error;
+
+/// The packages found missing by this process.
+static MISSING: Mutex<Vec<(String, Error)>> = Mutex::new(Vec::new());
+
+/// A copy of `err`, for the errors kept by the cache.
+fn duplicate(err: &Error) -> Option<Error> {
+    match *err {
+        Error::Failure { ref command, ref output, ref suggestions } => {
+            Some(Error::Failure {
+                command: command.clone(),
+                output: output.clone(),
+                suggestions: suggestions.clone(),
+            })
+        }
+        Error::InProcess { ref package, ref message, ref suggestions } => {
+            Some(Error::InProcess {
+                package: package.clone(),
+                message: message.clone(),
+                suggestions: suggestions.clone(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The error `name` was found missing with before, if any.
+pub fn lookup(fingerprint: &str, name: &str) -> Option<Error> {
+    let key = format!("{} {}", fingerprint, name);
+    let missing = MISSING.lock().unwrap();
+    missing.iter().find(|e| e.0 == key).and_then(|e| duplicate(&e.1))
+}
+
+/// Remember that `name` is missing, if `err` says so.
+pub fn insert(fingerprint: &str, name: &str, err: &Error) {
+    if !err.is_not_found() {
+        return;
+    }
+    if let Some(err) = duplicate(err) {
+        MISSING.lock().unwrap().push((format!("{} {}", fingerprint, name), err));
+    }
+}
+
+/// Whether the cache file at `path` records `name` as missing less than
+/// `ttl` ago.
+pub fn lookup_file(path: &Path, fingerprint: &str, name: &str, ttl: Duration) -> bool {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return false,
+    };
+    let now = now();
+    contents.lines().any(|line| {
+        let parts = line.split(' ').collect::<Vec<_>>();
+        match parts[..] {
+            [f, n, time] if f == fingerprint && n == name => {
+                time.parse::<u64>().is_ok_and(|t| now.saturating_sub(t) < ttl.as_secs())
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Record `name` as missing in the cache file at `path`.
+pub fn record_file(path: &Path, fingerprint: &str, name: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(format!("{} {} {}\n", fingerprint, name, now()).as_bytes())
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
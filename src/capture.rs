@@ -0,0 +1,51 @@
+//! Redirection of the `cargo:` lines printed on a thread, used to print the
+//! metadata of parallel probes in order and by `testing::capture_metadata`.
+
+use std::cell::RefCell;
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, returning its result and the lines passed to `record` on the
+/// current thread meanwhile, instead of printing them.
+pub fn capture<F, R>(f: F) -> (R, Vec<String>)
+    where F: FnOnce() -> R
+{
+    let previous = CAPTURED.with(|c| c.borrow_mut().replace(Vec::new()));
+    let guard = Restore { previous: Some(previous) };
+    let result = f();
+    let lines = guard.finish();
+    (result, lines)
+}
+
+struct Restore {
+    previous: Option<Option<Vec<String>>>,
+}
+
+impl Restore {
+    fn finish(mut self) -> Vec<String> {
+        let previous = self.previous.take().unwrap();
+        CAPTURED.with(|c| std::mem::replace(&mut *c.borrow_mut(), previous)).unwrap_or_default()
+    }
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            CAPTURED.with(|c| *c.borrow_mut() = previous);
+        }
+    }
+}
+
+/// Record `line` if a capture is active on this thread, otherwise hand it
+/// back to be printed.
+pub fn record(line: String) -> Option<String> {
+    CAPTURED.with(|c| match *c.borrow_mut() {
+        Some(ref mut lines) => {
+            lines.push(line);
+            None
+        }
+        None => Some(line),
+    })
+}
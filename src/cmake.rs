@@ -0,0 +1,232 @@
+//! A fallback for libraries that install CMake package configuration files
+//! (`FooConfig.cmake` and its exported targets) but no `.pc` file.
+//!
+//! CMake isn't run: the `set_target_properties` calls of the generated
+//! targets files are read directly, which covers what `install(EXPORT)`
+//! writes. Generator expressions are skipped.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use {Library, Linkage};
+
+/// Find the CMake package `name` (case-insensitively) under the prefixes of
+/// `prefix_path`, the value of `CMAKE_PREFIX_PATH`, or the usual prefixes of
+/// the host if `native`.
+pub fn find(name: &str, prefix_path: Option<OsString>, native: bool) -> Option<Library> {
+    let dir = prefixes(prefix_path, native).iter().flat_map(|p| config_dirs(p)).find(|dir| {
+        dir.file_name().is_some_and(|d| package_name(&d.to_string_lossy()).eq_ignore_ascii_case(name))
+    })?;
+    let files = fs::read_dir(&dir).ok()?
+                                  .flatten()
+                                  .map(|e| e.path())
+                                  .collect::<Vec<_>>();
+    let has_config = files.iter().any(|f| {
+        let file = f.file_name().unwrap().to_string_lossy().to_lowercase();
+        file == format!("{}config.cmake", name.to_lowercase()) ||
+        file == format!("{}-config.cmake", name.to_lowercase())
+    });
+    if !has_config {
+        return None;
+    }
+
+    let mut targets = Targets::default();
+    let mut targets_files = files.iter()
+                                 .filter(|f| f.to_string_lossy().to_lowercase().contains("targets"))
+                                 .collect::<Vec<_>>();
+    // The main file before the per-configuration ones, which use the prefix
+    // it computes.
+    targets_files.sort_by_key(|f| f.to_string_lossy().contains('-'));
+    for file in targets_files {
+        if let Ok(contents) = fs::read_to_string(file) {
+            targets.parse(file, &contents);
+        }
+    }
+
+    let mut library = Library::new();
+    for file in &files {
+        let file_name = file.file_name().unwrap().to_string_lossy().to_lowercase();
+        if file_name.ends_with("configversion.cmake") || file_name.ends_with("config-version.cmake") {
+            if let Ok(contents) = fs::read_to_string(file) {
+                library.version = set_value(&contents, "PACKAGE_VERSION").unwrap_or_default();
+            }
+        }
+    }
+    targets.fill(&mut library);
+    Some(library)
+}
+
+/// The prefixes of `prefix_path` followed by the default ones, if `native`.
+fn prefixes(prefix_path: Option<OsString>, native: bool) -> Vec<PathBuf> {
+    let mut prefixes = prefix_path.map(|p| env::split_paths(&p).collect::<Vec<_>>())
+                                  .unwrap_or_default();
+    if !native {
+        return prefixes;
+    }
+    prefixes.push(PathBuf::from("/usr/local"));
+    prefixes.push(PathBuf::from("/usr"));
+    if cfg!(target_os = "macos") {
+        prefixes.push(PathBuf::from("/opt/homebrew"));
+    }
+    prefixes
+}
+
+/// The package directories under `prefix`, e.g. `lib/cmake/Foo`.
+fn config_dirs(prefix: &Path) -> Vec<PathBuf> {
+    let mut roots = vec![prefix.join("lib/cmake"), prefix.join("lib64/cmake"),
+                         prefix.join("share/cmake")];
+    // Debian's multiarch directories, e.g. `lib/x86_64-linux-gnu/cmake`.
+    if let Ok(entries) = fs::read_dir(prefix.join("lib")) {
+        roots.extend(entries.flatten().map(|e| e.path().join("cmake")));
+    }
+    roots.iter()
+         .filter_map(|root| fs::read_dir(root).ok())
+         .flat_map(|entries| entries.flatten().map(|e| e.path()))
+         .filter(|p| p.is_dir())
+         .collect()
+}
+
+/// The package name of a directory name, without a version suffix, e.g.
+/// `Foo` for `Foo-1.2`.
+fn package_name(dir: &str) -> &str {
+    match dir.find('-') {
+        Some(i) if dir[i + 1..].starts_with(|c: char| c.is_ascii_digit()) => &dir[..i],
+        _ => dir,
+    }
+}
+
+/// The value of `set(<var> "<value>")` in `contents`.
+fn set_value(contents: &str, var: &str) -> Option<String> {
+    let prefix = format!("set({} ", var);
+    contents.lines()
+            .filter_map(|l| l.trim().strip_prefix(&prefix[..]))
+            .map(|v| v.trim_end_matches(')').trim().trim_matches('"').to_string())
+            .next()
+}
+
+/// The name to link the library file `file` with, e.g. `python3.11` for
+/// `libpython3.11.so.1.0`.
+fn lib_name(file: &str) -> String {
+    let stem = file.strip_prefix("lib").unwrap_or(file);
+    // `.so` may be followed by the version.
+    let so = stem.match_indices(".so").map(|(i, _)| i)
+                 .find(|&i| stem[i + 3..].is_empty() || stem[i + 3..].starts_with('.'));
+    if let Some(i) = so {
+        return stem[..i].to_string();
+    }
+    let stem = [".a", ".dylib", ".lib", ".dll"].iter().find_map(|ext| stem.strip_suffix(ext))
+                                               .unwrap_or(stem);
+    stem.to_string()
+}
+
+#[derive(Default)]
+struct Targets {
+    import_prefix: String,
+    /// The properties of each imported target, in the order they appear.
+    properties: Vec<(String, HashMap<String, String>)>,
+}
+
+impl Targets {
+    fn parse(&mut self, file: &Path, contents: &str) {
+        let mut lines = contents.lines().map(str::trim);
+        while let Some(line) = lines.next() {
+            if line.starts_with("get_filename_component(_IMPORT_PREFIX") {
+                if line.contains("CMAKE_CURRENT_LIST_FILE") {
+                    self.import_prefix = file.to_string_lossy().into_owned();
+                }
+                self.import_prefix = Path::new(&self.import_prefix).parent()
+                                         .map(|p| p.to_string_lossy().into_owned())
+                                         .unwrap_or_default();
+            } else if let Some(rest) = line.strip_prefix("set_target_properties(") {
+                let target = rest.split_whitespace().next().unwrap_or("").to_string();
+                let mut properties = HashMap::new();
+                for line in lines.by_ref() {
+                    if line.starts_with(')') {
+                        break;
+                    }
+                    if let Some(i) = line.find(' ') {
+                        let value = line[i..].trim().trim_matches('"');
+                        properties.insert(line[..i].to_string(),
+                                          value.replace("${_IMPORT_PREFIX}", &self.import_prefix));
+                    }
+                }
+                match self.properties.iter_mut().find(|t| t.0 == target) {
+                    Some(t) => t.1.extend(properties),
+                    None => self.properties.push((target, properties)),
+                }
+            }
+        }
+    }
+
+    fn fill(&self, library: &mut Library) {
+        let list = |props: &HashMap<String, String>, key: &str| -> Vec<String> {
+            props.get(key)
+                 .map(|v| v.split(';')
+                           .filter(|s| !s.is_empty() && !s.starts_with("$<"))
+                           .map(|s| s.to_string())
+                           .collect())
+                 .unwrap_or_default()
+        };
+        for (_, props) in &self.properties {
+            for dir in list(props, "INTERFACE_INCLUDE_DIRECTORIES") {
+                let dir = PathBuf::from(dir);
+                if !library.include_paths.contains(&dir) {
+                    library.include_paths.push(dir);
+                }
+            }
+            for define in list(props, "INTERFACE_COMPILE_DEFINITIONS") {
+                let mut parts = define.splitn(2, '=');
+                let name = parts.next().unwrap().to_string();
+                library.defines.insert(name, parts.next().map(|v| v.to_string()));
+            }
+            let location = ["IMPORTED_LOCATION_RELEASE", "IMPORTED_LOCATION_NOCONFIG",
+                            "IMPORTED_LOCATION"].iter().find_map(|k| props.get(*k));
+            if let Some(location) = location {
+                let location = Path::new(location);
+                if let (Some(dir), Some(file)) = (location.parent(), location.file_name()) {
+                    if !library.link_paths.iter().any(|p| p == dir) {
+                        library.link_paths.push(dir.to_path_buf());
+                    }
+                    let file = file.to_string_lossy();
+                    let lib = lib_name(&file);
+                    let linkage = if file.ends_with(".a") || file.ends_with(".lib") {
+                        library.static_libs.push(lib.clone());
+                        Linkage::Static
+                    } else {
+                        Linkage::Dynamic
+                    };
+                    library.linkage.insert(lib.clone(), linkage);
+                    library.libs.push(lib);
+                }
+            }
+        }
+        // Plain libraries the targets link against, after the targets
+        // themselves. Other targets, e.g. `Threads::Threads`, can't be
+        // resolved without CMake.
+        for (_, props) in &self.properties {
+            for lib in list(props, "INTERFACE_LINK_LIBRARIES") {
+                if lib.contains("::") {
+                    continue;
+                }
+                let lib = lib.trim_start_matches("-l").to_string();
+                if !library.libs.contains(&lib) {
+                    library.linkage.insert(lib.clone(), Linkage::Dynamic);
+                    library.libs.push(lib);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn lib_name_test() {
+    assert_eq!(lib_name("libwidget.so"), "widget");
+    assert_eq!(lib_name("libwidget.so.1.2"), "widget");
+    assert_eq!(lib_name("libpython3.11.so.1.0"), "python3.11");
+    assert_eq!(lib_name("libpython3.11.a"), "python3.11");
+    assert_eq!(lib_name("libssl.3.dylib"), "ssl.3");
+    assert_eq!(lib_name("zlib.lib"), "zlib");
+}
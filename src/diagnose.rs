@@ -0,0 +1,266 @@
+//! Checks of probe results for the mistakes that otherwise only surface as
+//! confusing linker errors, see `Config::warnings`.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use framework;
+use {command_line, Error, Library, LibraryKind};
+
+/// Describe what looks wrong with `library`, found as the package `name`,
+/// when building for `target`.
+pub fn warnings(name: &str, library: &Library, target: Option<&str>) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !has_flags(library) {
+        warnings.push(format!("pkg-config returned no flags for `{}`", name));
+    }
+    for path in missing_paths(library) {
+        warnings.push(missing_path_warning(name, &path));
+    }
+    warnings.extend(arch_mismatches(name, library, target));
+    warnings
+}
+
+/// Describe the shared libraries of `library` built for another
+/// architecture than `target`.
+pub fn arch_mismatches(name: &str, library: &Library, target: Option<&str>) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let expected = match target.and_then(|t| t.split('-').next()).and_then(normalize_arch) {
+        Some(arch) => arch,
+        None => return warnings,
+    };
+    for lib in &library.libs {
+        let found = library.link_paths.iter().find_map(|dir| {
+            ["so", "dylib"].iter()
+                           .map(|ext| dir.join(format!("lib{}.{}", lib, ext)))
+                           .find(|path| path.is_file())
+        });
+        let path = match found {
+            Some(path) => path,
+            None => continue,
+        };
+        if let Some(arch) = file_arch(&path) {
+            if arch != expected {
+                warnings.push(format!("`{}` of `{}` is built for {}, but the target is {}",
+                                      path.display(), name, arch, expected));
+            }
+        }
+    }
+    warnings
+}
+
+/// Explain how to point `pkg-config` at the packages of `target` rather
+/// than the host's, with an example for the target.
+pub fn cross_hint(target: &str) -> Vec<String> {
+    let target_u = target.replace('-', "_");
+    let example = match debian_multiarch(target) {
+        Some(multiarch) => {
+            format!("e.g. for packages installed with Debian's multiarch, \
+                     PKG_CONFIG_LIBDIR_{}=/usr/lib/{}/pkgconfig:/usr/share/pkgconfig",
+                    target_u, multiarch)
+        }
+        None => {
+            format!("e.g. for a sysroot of {} at /path/to/sysroot, \
+                     PKG_CONFIG_SYSROOT_DIR_{1}=/path/to/sysroot and \
+                     PKG_CONFIG_LIBDIR_{1}=/path/to/sysroot/usr/lib/pkgconfig",
+                    target, target_u)
+        }
+    };
+    vec![
+        format!("pkg-config found libraries of the host rather than of {}: \
+                 PKG_CONFIG_PATH only adds directories in front of pkg-config's \
+                 default search path, which still has the host's packages, \
+                 while PKG_CONFIG_LIBDIR replaces it", target),
+        format!("set PKG_CONFIG_LIBDIR_{} to the target's `pkgconfig` directories, {}",
+                target_u, example),
+    ]
+}
+
+/// The Debian multiarch tuple of a Linux GNU target, which names the
+/// directories its libraries are installed in.
+pub fn debian_multiarch(target: &str) -> Option<String> {
+    let arch = target.split('-').next()?;
+    let abi = target.rsplit('-').next()?;
+    if !target.contains("-linux-") || !abi.starts_with("gnu") {
+        return None;
+    }
+    Some(match arch {
+        "i586" | "i686" => "i386-linux-gnu".to_string(),
+        "riscv64gc" => "riscv64-linux-gnu".to_string(),
+        a if a.starts_with("arm") || a.starts_with("thumb") => {
+            if abi.ends_with("hf") { "arm-linux-gnueabihf" } else { "arm-linux-gnueabi" }.to_string()
+        }
+        "x86_64" | "aarch64" | "powerpc64le" | "s390x" => format!("{}-linux-gnu", arch),
+        _ => return None,
+    })
+}
+
+/// Whether `pkg-config` returned any libraries, frameworks or directories
+/// for `library`.
+pub fn has_flags(library: &Library) -> bool {
+    !library.libs.is_empty() || !library.verbatim_libs.is_empty() ||
+    !library.frameworks.is_empty() ||
+    !library.link_paths.is_empty() || !library.include_paths.is_empty()
+}
+
+/// What's wrong with `library` for the kind `expected`, if anything, see
+/// `Config::expect_kind`. Frameworks are also looked for in the SDK
+/// `sdkroot`.
+pub fn check_kind(library: &Library, expected: LibraryKind, sdkroot: Option<&Path>)
+                  -> Option<String> {
+    let found = library.kind();
+    if found != expected {
+        return Some(format!("it has {}", describe_kind(found)));
+    }
+    match expected {
+        LibraryKind::Libs => None,
+        LibraryKind::FrameworksOnly => {
+            let mut dirs = library.framework_paths.clone();
+            dirs.extend(framework::search_dirs(sdkroot));
+            library.frameworks.iter()
+                              .chain(&library.weak_frameworks)
+                              .chain(&library.needed_frameworks)
+                              .find(|name| framework::find(name, &dirs).is_none())
+                              .map(|name| format!("framework `{}` wasn't found", name))
+        }
+        LibraryKind::HeaderOnly => {
+            library.include_paths.iter()
+                                 .find(|path| !path.is_dir())
+                                 .map(|path| format!("include path `{}` doesn't exist",
+                                                     path.display()))
+        }
+    }
+}
+
+/// Compile and link a trivial C program against `library`, found as the
+/// package `name`, with `compiler` (which may include arguments, like `CC`),
+/// working in `dir`. See `Config::try_link`.
+pub fn try_link(name: &str, library: &Library, compiler: &str, dir: &Path)
+                -> Result<(), Error> {
+    let source = dir.join("try_link.c");
+    let output = dir.join("try_link");
+    fs::create_dir_all(dir).and_then(|_| fs::write(&source, "int main(void) { return 0; }\n"))
+                           .map_err(|cause| Error::Command {
+                               command: format!("writing {}", source.display()),
+                               cause,
+                           })?;
+    let mut words = compiler.split_whitespace();
+    let mut cmd = Command::new(words.next().unwrap_or("cc"));
+    cmd.args(words).arg(&source).arg("-o").arg(&output);
+    for path in &library.link_paths {
+        cmd.arg(format!("-L{}", path.display()));
+    }
+    for path in &library.framework_paths {
+        cmd.arg(format!("-F{}", path.display()));
+    }
+    for lib in &library.libs {
+        cmd.arg(format!("-l{}", lib));
+    }
+    for file in &library.verbatim_libs {
+        cmd.arg(format!("-l:{}", file));
+    }
+    for framework in &library.frameworks {
+        cmd.args(["-framework", framework]);
+    }
+    let command = command_line(&cmd);
+    let result = cmd.output();
+    let _ = fs::remove_dir_all(dir);
+    match result {
+        Ok(ref out) if out.status.success() => Ok(()),
+        Ok(out) => Err(Error::LinkFailed {
+            package: name.to_owned(),
+            command,
+            stderr: String::from_utf8_lossy(&out.stderr).trim_end().to_string(),
+        }),
+        Err(cause) => Err(Error::Command { command, cause }),
+    }
+}
+
+/// What a package of `kind` has, e.g. "libraries to link".
+pub fn describe_kind(kind: LibraryKind) -> &'static str {
+    match kind {
+        LibraryKind::Libs => "libraries to link",
+        LibraryKind::FrameworksOnly => "only frameworks",
+        LibraryKind::HeaderOnly => "neither libraries nor frameworks",
+    }
+}
+
+/// The architecture of the first component of a target triple, in the
+/// terms of `file_arch`.
+fn normalize_arch(arch: &str) -> Option<&'static str> {
+    Some(match arch {
+        "x86_64" => "x86_64",
+        "i386" | "i586" | "i686" => "x86",
+        "aarch64" | "arm64" => "aarch64",
+        a if a.starts_with("arm") || a.starts_with("thumb") => "arm",
+        "riscv64gc" | "riscv64" => "riscv64",
+        "powerpc64" | "powerpc64le" => "powerpc64",
+        "powerpc" => "powerpc",
+        "s390x" => "s390x",
+        _ => return None,
+    })
+}
+
+/// The architecture of the ELF or Mach-O file at `path`, from its header.
+///
+/// Linker scripts, fat binaries and unknown machines give `None`.
+fn file_arch(path: &Path) -> Option<&'static str> {
+    let mut header = [0u8; 20];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+    if &header[..4] == b"\x7fELF" {
+        let machine = match header[5] {
+            1 => u16::from_le_bytes([header[18], header[19]]),
+            2 => u16::from_be_bytes([header[18], header[19]]),
+            _ => return None,
+        };
+        return Some(match machine {
+            3 => "x86",
+            20 => "powerpc",
+            21 => "powerpc64",
+            22 => "s390x",
+            40 => "arm",
+            62 => "x86_64",
+            183 => "aarch64",
+            243 => "riscv64",
+            _ => return None,
+        });
+    }
+    let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    if magic == 0xfeedface || magic == 0xfeedfacf {
+        let cputype = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        return Some(match cputype {
+            7 => "x86",
+            0x0100_0007 => "x86_64",
+            12 => "arm",
+            0x0100_000c => "aarch64",
+            _ => return None,
+        });
+    }
+    None
+}
+
+/// The `-L` and `-F` directories of `library` that don't exist.
+pub fn missing_paths(library: &Library) -> Vec<PathBuf> {
+    library.link_paths.iter()
+                      .chain(&library.framework_paths)
+                      .filter(|path| !path.is_dir())
+                      .cloned()
+                      .collect()
+}
+
+/// The warning about the missing directory `path` of `name`.
+pub fn missing_path_warning(name: &str, path: &Path) -> String {
+    format!("search path `{}` of `{}` doesn't exist", path.display(), name)
+}
+
+#[test]
+fn debian_multiarch_test() {
+    assert_eq!(debian_multiarch("aarch64-unknown-linux-gnu").unwrap(), "aarch64-linux-gnu");
+    assert_eq!(debian_multiarch("armv7-unknown-linux-gnueabihf").unwrap(),
+               "arm-linux-gnueabihf");
+    assert_eq!(debian_multiarch("i686-unknown-linux-gnu").unwrap(), "i386-linux-gnu");
+    assert_eq!(debian_multiarch("aarch64-unknown-linux-musl"), None);
+    assert_eq!(debian_multiarch("aarch64-apple-darwin"), None);
+}
@@ -0,0 +1,252 @@
+//! The system packages providing pkg-config modules, to tell users what to
+//! install when a probe fails.
+//!
+//! A few common modules are known out of the box. More can be added, or the
+//! known ones changed, with a TOML file named by `PKG_CONFIG_DISTRO_PACKAGES`:
+//!
+//! ```toml
+//! ["gtk+-3.0"]
+//! debian = "libgtk-3-dev"
+//! fedora = "gtk3-devel"
+//! # An empty name removes the suggestion.
+//! vcpkg = ""
+//! ```
+
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+
+/// A distribution or package manager.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Distro {
+    Debian,
+    Fedora,
+    Arch,
+    Alpine,
+    Homebrew,
+    Vcpkg,
+}
+
+const DISTROS: [Distro; 6] = [Distro::Debian, Distro::Fedora, Distro::Arch, Distro::Alpine,
+                              Distro::Homebrew, Distro::Vcpkg];
+
+impl Distro {
+    /// The key of the distribution in override files, e.g. `debian`.
+    pub fn key(&self) -> &'static str {
+        match *self {
+            Distro::Debian => "debian",
+            Distro::Fedora => "fedora",
+            Distro::Arch => "arch",
+            Distro::Alpine => "alpine",
+            Distro::Homebrew => "homebrew",
+            Distro::Vcpkg => "vcpkg",
+        }
+    }
+
+    /// The command installing a package, without the package name.
+    pub fn install_command(&self) -> &'static str {
+        match *self {
+            Distro::Debian => "apt install",
+            Distro::Fedora => "dnf install",
+            Distro::Arch => "pacman -S",
+            Distro::Alpine => "apk add",
+            Distro::Homebrew => "brew install",
+            Distro::Vcpkg => "vcpkg install",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Distro> {
+        DISTROS.iter().cloned().find(|d| d.key() == key)
+    }
+}
+
+/// A system package providing a pkg-config module, see `distro_packages`.
+#[derive(Clone, Debug, PartialEq)]
+#[allow(clippy::manual_non_exhaustive)]
+pub struct Suggestion {
+    pub distro: Distro,
+    /// The name of the package, e.g. `libgtk-4-dev`.
+    pub package: String,
+    _priv: (),
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} {}", self.distro.install_command(), self.package)
+    }
+}
+
+/// The known modules, with their packages in the order of `DISTROS`.
+static PACKAGES: &[(&str, [&str; 6])] = &[
+    ("alsa", ["libasound2-dev", "alsa-lib-devel", "alsa-lib", "alsa-lib-dev", "", ""]),
+    ("cairo", ["libcairo2-dev", "cairo-devel", "cairo", "cairo-dev", "cairo", "cairo"]),
+    ("dbus-1", ["libdbus-1-dev", "dbus-devel", "dbus", "dbus-dev", "dbus", "dbus"]),
+    ("fontconfig", ["libfontconfig-dev", "fontconfig-devel", "fontconfig", "fontconfig-dev",
+                    "fontconfig", "fontconfig"]),
+    ("freetype2", ["libfreetype-dev", "freetype-devel", "freetype2", "freetype-dev", "freetype",
+                   "freetype"]),
+    ("glib-2.0", ["libglib2.0-dev", "glib2-devel", "glib2", "glib-dev", "glib", "glib"]),
+    ("gstreamer-1.0", ["libgstreamer1.0-dev", "gstreamer1-devel", "gstreamer", "gstreamer-dev",
+                       "gstreamer", "gstreamer"]),
+    ("gtk+-3.0", ["libgtk-3-dev", "gtk3-devel", "gtk3", "gtk+3.0-dev", "gtk+3", "gtk"]),
+    ("gtk4", ["libgtk-4-dev", "gtk4-devel", "gtk4", "gtk4.0-dev", "gtk4", "gtk"]),
+    ("libcurl", ["libcurl4-openssl-dev", "libcurl-devel", "curl", "curl-dev", "curl", "curl"]),
+    ("libpng", ["libpng-dev", "libpng-devel", "libpng", "libpng-dev", "libpng", "libpng"]),
+    ("libpulse", ["libpulse-dev", "pulseaudio-libs-devel", "libpulse", "pulseaudio-dev", "", ""]),
+    ("libudev", ["libudev-dev", "systemd-devel", "systemd-libs", "eudev-dev", "", ""]),
+    ("libusb-1.0", ["libusb-1.0-0-dev", "libusb1-devel", "libusb", "libusb-dev", "libusb",
+                    "libusb"]),
+    ("libxml-2.0", ["libxml2-dev", "libxml2-devel", "libxml2", "libxml2-dev", "libxml2",
+                    "libxml2"]),
+    ("libzstd", ["libzstd-dev", "libzstd-devel", "zstd", "zstd-dev", "zstd", "zstd"]),
+    ("openssl", ["libssl-dev", "openssl-devel", "openssl", "openssl-dev", "openssl@3",
+                 "openssl"]),
+    ("sqlite3", ["libsqlite3-dev", "sqlite-devel", "sqlite", "sqlite-dev", "sqlite3",
+                 "sqlite3"]),
+    ("wayland-client", ["libwayland-dev", "wayland-devel", "wayland", "wayland-dev", "", ""]),
+    ("x11", ["libx11-dev", "libX11-devel", "libx11", "libx11-dev", "", ""]),
+    ("xkbcommon", ["libxkbcommon-dev", "libxkbcommon-devel", "libxkbcommon", "libxkbcommon-dev",
+                   "", ""]),
+    ("zlib", ["zlib1g-dev", "zlib-devel", "zlib", "zlib-dev", "zlib", "zlib"]),
+];
+
+/// The system packages providing the pkg-config module `name`, one per
+/// distribution at most.
+///
+/// The overrides of the file named by `PKG_CONFIG_DISTRO_PACKAGES` take
+/// precedence over the known packages. An unreadable or invalid file is
+/// ignored, as the suggestions are only a hint.
+///
+/// ```
+/// let suggestions = pkg_config::distro_packages("gtk4");
+/// assert_eq!(suggestions[0].to_string(), "apt install libgtk-4-dev");
+/// ```
+pub fn distro_packages(name: &str) -> Vec<Suggestion> {
+    suggestions(name, env::var_os("PKG_CONFIG_DISTRO_PACKAGES"))
+}
+
+/// The system packages providing `name`, with the overrides of the file
+/// `overrides`, if any.
+pub(crate) fn suggestions(name: &str, overrides: Option<OsString>) -> Vec<Suggestion> {
+    let mut packages: Vec<(Distro, String)> = PACKAGES.iter()
+        .filter(|p| p.0 == name)
+        .flat_map(|p| DISTROS.iter().cloned().zip(p.1.iter().map(|s| s.to_string())))
+        .collect();
+    let overrides = overrides
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| parse_overrides(&contents).ok())
+        .unwrap_or_default();
+    for (module, distro, package) in overrides {
+        if module != name {
+            continue;
+        }
+        match packages.iter_mut().find(|p| p.0 == distro) {
+            Some(p) => p.1 = package,
+            None => packages.push((distro, package)),
+        }
+    }
+    packages.sort_by_key(|p| DISTROS.iter().position(|d| *d == p.0));
+    packages.into_iter()
+            .filter(|p| !p.1.is_empty())
+            .map(|(distro, package)| Suggestion { distro, package, _priv: () })
+            .collect()
+}
+
+/// Parse the `(module, distro, package)` entries of an override file, a
+/// small subset of TOML: `[module]` tables of `distro = "package"` strings.
+fn parse_overrides(contents: &str) -> Result<Vec<(String, Distro, String)>, String> {
+    let mut entries = Vec::new();
+    let mut module = None;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |msg: &str| format!("line {}: {}", i + 1, msg);
+        if let Some(table) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            module = Some(unquote(table.trim()).ok_or_else(|| err("invalid table name"))?);
+            continue;
+        }
+        let mut parts = line.splitn(2, '=');
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key.trim(), value.trim()),
+            _ => return Err(err("expected `distro = \"package\"`")),
+        };
+        let module = module.clone().ok_or_else(|| err("entry outside of a table"))?;
+        let distro = Distro::from_key(&unquote(key).ok_or_else(|| err("invalid key"))?)
+            .ok_or_else(|| err("unknown distribution"))?;
+        let package = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            Some(package) => package.to_string(),
+            None => return Err(err("expected a string")),
+        };
+        entries.push((module, distro, package));
+    }
+    Ok(entries)
+}
+
+/// A bare or quoted TOML key.
+pub(crate) fn unquote(key: &str) -> Option<String> {
+    match key.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+        Some(key) => Some(key.to_string()),
+        None if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() ||
+                                                       c == '-' || c == '_') => {
+            Some(key.to_string())
+        }
+        None => None,
+    }
+}
+
+/// The package a `pkg-config` or pkgconf error message reports missing.
+pub(crate) fn missing_package(stderr: &str) -> Option<&str> {
+    for line in stderr.lines() {
+        // pkg-config: "Package foo was not found in the pkg-config search path."
+        // pkgconf: "Package 'foo', required by 'virtual:world', not found"
+        if let Some(rest) = line.strip_prefix("Package ") {
+            if line.contains("not found") {
+                let name = rest.split([' ', ',']).next().unwrap_or("");
+                return Some(name.trim_matches('\''));
+            }
+        }
+    }
+    None
+}
+
+/// The package a `pkg-config` or pkgconf error message reports missing when
+/// it's one of the packages asked for, rather than a require of one.
+pub(crate) fn requested_missing(stderr: &str) -> Option<&str> {
+    for line in stderr.lines() {
+        // pkg-config: "No package 'foo' found"
+        if let Some(name) = line.strip_prefix("No package '")
+                                .and_then(|rest| rest.strip_suffix("' found")) {
+            return Some(name);
+        }
+        // pkgconf: "Package 'foo', required by 'virtual:world', not found"
+        if let Some(name) = line.strip_prefix("Package '").and_then(|rest| {
+            rest.strip_suffix("', required by 'virtual:world', not found")
+        }) {
+            return Some(name);
+        }
+    }
+    None
+}
+
+#[test]
+fn parse_overrides_test() {
+    let entries = parse_overrides("# comment\n[\"gtk+-3.0\"]\ndebian = \"libgtk-3-dev\"\n\n\
+                                   [foo]\nhomebrew = \"\"\n").unwrap();
+    assert_eq!(entries, vec![("gtk+-3.0".to_string(), Distro::Debian, "libgtk-3-dev".to_string()),
+                             ("foo".to_string(), Distro::Homebrew, String::new())]);
+    assert!(parse_overrides("debian = \"x\"").is_err());
+    assert!(parse_overrides("[foo]\nsolaris = \"x\"").is_err());
+    assert_eq!(missing_package("Package foo was not found in the pkg-config search path."),
+               Some("foo"));
+    assert_eq!(missing_package("Package 'foo', required by 'virtual:world', not found"),
+               Some("foo"));
+    assert_eq!(requested_missing("No package 'foo' found"), Some("foo"));
+    assert_eq!(requested_missing("Package 'foo', required by 'virtual:world', not found"),
+               Some("foo"));
+    assert_eq!(requested_missing("Package bar was not found in the pkg-config search path.\n\
+                                  Package 'bar', required by 'foo', not found"), None);
+}
@@ -0,0 +1,184 @@
+//! A minimal ELF reader, just enough to extract the dynamic section entries
+//! and exported symbols of shared libraries.
+
+use std::fs;
+use std::path::Path;
+
+const SHT_DYNAMIC: u32 = 6;
+const SHT_DYNSYM: u32 = 11;
+const SHN_UNDEF: u64 = 0;
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_SONAME: u64 = 14;
+
+/// Entries of the dynamic section of a shared library.
+#[derive(Debug, Default)]
+pub struct Dynamic {
+    pub soname: Option<String>,
+    pub needed: Vec<String>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Option<Reader<'a>> {
+        if data.len() < 0x34 || &data[..4] != b"\x7fELF" {
+            return None;
+        }
+        let is_64 = match data[4] {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+        let big_endian = match data[5] {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+        Some(Reader { data, is_64, big_endian })
+    }
+
+    fn uint(&self, offset: usize, size: usize) -> Option<u64> {
+        let bytes = self.data.get(offset..offset.checked_add(size)?)?;
+        let mut value = 0u64;
+        for i in 0..size {
+            let b = if self.big_endian { bytes[i] } else { bytes[size - 1 - i] };
+            value = (value << 8) | u64::from(b);
+        }
+        Some(value)
+    }
+
+    fn word(&self, offset: usize) -> Option<u64> {
+        self.uint(offset, if self.is_64 { 8 } else { 4 })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u64> {
+        self.uint(offset, 2)
+    }
+
+    fn u32(&self, offset: usize) -> Option<u64> {
+        self.uint(offset, 4)
+    }
+
+    fn string(&self, offset: usize) -> Option<String> {
+        let bytes = self.data.get(offset..)?;
+        let end = bytes.iter().position(|&b| b == 0)?;
+        String::from_utf8(bytes[..end].to_vec()).ok()
+    }
+
+    /// Offset, size, `sh_link` and `sh_entsize` of each section header of
+    /// type `kind`.
+    fn sections(&self, kind: u32) -> Option<Vec<(usize, usize, usize, usize)>> {
+        let shnum = if self.is_64 { self.u16(0x3c)? } else { self.u16(0x30)? };
+        let mut sections = Vec::new();
+        for i in 0..usize(shnum)? {
+            let header = self.section_header(i)?;
+            if self.u32(header.checked_add(4)?)? != u64::from(kind) {
+                continue;
+            }
+            let (offset, size, link, entsize) = if self.is_64 {
+                (self.word(header.checked_add(24)?)?, self.word(header.checked_add(32)?)?,
+                 self.u32(header.checked_add(40)?)?, self.word(header.checked_add(56)?)?)
+            } else {
+                (self.word(header.checked_add(16)?)?, self.word(header.checked_add(20)?)?,
+                 self.u32(header.checked_add(24)?)?, self.word(header.checked_add(36)?)?)
+            };
+            sections.push((usize(offset)?, usize(size)?, usize(link)?, usize(entsize)?));
+        }
+        Some(sections)
+    }
+
+    /// Offset of the header of the section with index `index`.
+    fn section_header(&self, index: usize) -> Option<usize> {
+        let (shoff, shentsize) = if self.is_64 {
+            (self.word(0x28)?, self.u16(0x3a)?)
+        } else {
+            (self.word(0x20)?, self.u16(0x2e)?)
+        };
+        usize(shoff)?.checked_add(index.checked_mul(usize(shentsize)?)?)
+    }
+
+    /// Offset of the section with index `index`.
+    fn section_offset(&self, index: usize) -> Option<usize> {
+        let header = self.section_header(index)?;
+        let offset = self.word(header.checked_add(if self.is_64 { 24 } else { 16 })?)?;
+        usize(offset)
+    }
+
+    fn dynamic(&self) -> Option<Dynamic> {
+        let mut dynamic = Dynamic::default();
+        let entry_size = if self.is_64 { 16 } else { 8 };
+        for (offset, size, link, _) in self.sections(SHT_DYNAMIC)? {
+            let strtab = self.section_offset(link)?;
+            for entry in (offset..offset.checked_add(size)?).step_by(entry_size) {
+                let tag = self.word(entry)?;
+                let value = usize(self.word(entry.checked_add(entry_size / 2)?)?)?;
+                match tag {
+                    DT_NULL => break,
+                    DT_NEEDED => dynamic.needed.extend(self.string(strtab.checked_add(value)?)),
+                    DT_SONAME => dynamic.soname = self.string(strtab.checked_add(value)?),
+                    _ => {}
+                }
+            }
+        }
+        Some(dynamic)
+    }
+
+    /// The names of the symbols of the dynamic symbol table that are
+    /// defined, rather than imported.
+    fn defined_symbols(&self) -> Option<Vec<String>> {
+        let mut symbols = Vec::new();
+        for (offset, size, link, entry_size) in self.sections(SHT_DYNSYM)? {
+            if entry_size == 0 {
+                return None;
+            }
+            let strtab = self.section_offset(link)?;
+            let end = offset.checked_add(size)?;
+            // The first entry is the null symbol.
+            for entry in (offset.checked_add(entry_size)?..end).step_by(entry_size) {
+                let shndx = self.u16(entry.checked_add(if self.is_64 { 6 } else { 14 })?)?;
+                let name = usize(self.u32(entry)?)?;
+                if shndx != SHN_UNDEF && name != 0 {
+                    symbols.extend(self.string(strtab.checked_add(name)?));
+                }
+            }
+        }
+        Some(symbols)
+    }
+}
+
+/// `value` as an offset or size, if it fits.
+fn usize(value: u64) -> Option<usize> {
+    if value > usize::MAX as u64 {
+        None
+    } else {
+        Some(value as usize)
+    }
+}
+
+/// Read the dynamic section of the ELF file at `path`, returning `None` if it
+/// isn't a readable ELF file.
+pub fn read_dynamic(path: &Path) -> Option<Dynamic> {
+    let data = fs::read(path).ok()?;
+    Reader::new(&data)?.dynamic()
+}
+
+/// The symbols the ELF shared library at `path` exports, `None` if it isn't
+/// a readable ELF file.
+pub fn read_defined_symbols(path: &Path) -> Option<Vec<String>> {
+    let data = fs::read(path).ok()?;
+    Reader::new(&data)?.defined_symbols()
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn read_dynamic_test() {
+    let exe = ::std::env::current_exe().unwrap();
+    let dynamic = read_dynamic(&exe).unwrap();
+    assert!(dynamic.needed.iter().any(|n| n.starts_with("libc.so")));
+    assert!(read_dynamic(Path::new(file!())).is_none());
+}
@@ -0,0 +1,52 @@
+//! Locating macOS frameworks, which have no `.pc` files, see
+//! `Config::probe_framework`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The directories searched for frameworks: the SDK's system frameworks,
+/// if an SDK is given, followed by the standard framework directories.
+pub fn search_dirs(sdkroot: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(sdkroot) = sdkroot {
+        dirs.push(sdkroot.join("System/Library/Frameworks"));
+    }
+    dirs.push(PathBuf::from("/Library/Frameworks"));
+    dirs.push(PathBuf::from("/System/Library/Frameworks"));
+    dirs
+}
+
+/// The first of `dirs` containing `<name>.framework`.
+pub fn find<'a>(name: &str, dirs: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    let bundle = format!("{}.framework", name);
+    dirs.iter().find(|dir| dir.join(&bundle).is_dir())
+}
+
+/// The version of the framework bundle at `path`, from the
+/// `CFBundleShortVersionString` (or else `CFBundleVersion`) of its XML
+/// `Info.plist`.
+pub fn version(path: &Path) -> Option<String> {
+    let plists = [path.join("Resources/Info.plist"),
+                  path.join("Versions/Current/Resources/Info.plist"),
+                  path.join("Info.plist")];
+    let contents = plists.iter().find_map(|p| fs::read_to_string(p).ok())?;
+    plist_string(&contents, "CFBundleShortVersionString")
+        .or_else(|| plist_string(&contents, "CFBundleVersion"))
+}
+
+/// The string value of `key` in the top-level dictionary of an XML plist.
+fn plist_string(contents: &str, key: &str) -> Option<String> {
+    let key = format!("<key>{}</key>", key);
+    let rest = contents[contents.find(&key)? + key.len()..].trim_start();
+    let value = rest.strip_prefix("<string>")?;
+    Some(value[..value.find("</string>")?].trim().to_string())
+}
+
+#[test]
+fn plist_string_test() {
+    let plist = "<dict>\n\t<key>CFBundleName</key>\n\t<string>Foo</string>\n\
+                 \t<key>CFBundleShortVersionString</key>\n\t<string>1.2</string>\n</dict>";
+    assert_eq!(plist_string(plist, "CFBundleShortVersionString"), Some("1.2".to_string()));
+    assert_eq!(plist_string(plist, "CFBundleName"), Some("Foo".to_string()));
+    assert_eq!(plist_string(plist, "CFBundleVersion"), None);
+}
@@ -10,9 +10,40 @@
 //! this crate will invoke `pkg-config`:
 //!
 //! * `PKG_CONFIG_ALLOW_CROSS` - if this variable is not set, then `pkg-config`
-//!   will automatically be disabled for all cross compiles.
+//!   will automatically be disabled for all cross compiles, unless
+//!   `PKG_CONFIG_SYSROOT_DIR` is set, or `PKG_CONFIG_PATH` or
+//!   `PKG_CONFIG_LIBDIR` is set for the target (e.g.
+//!   `PKG_CONFIG_PATH_aarch64-unknown-linux-gnu` or `TARGET_PKG_CONFIG_PATH`).
+//!   Setting it to `0` or `false` disables `pkg-config` for all cross
+//!   compiles, any other value enables it.
 //! * `FOO_NO_PKG_CONFIG` - if set, this will disable running `pkg-config` when
 //!   probing for the library named `foo`.
+//! * `PKG_CONFIG_TRACE` - if set, every command run, its output, the parsed
+//!   flags and the printed metadata are logged to stderr.
+//! * `PKG_CONFIG_SYSTEM_LIBRARY_PATH` and `PKG_CONFIG_SYSTEM_INCLUDE_PATH` -
+//!   the default search paths of the linker and compiler, whose `-L` and `-I`
+//!   flags aren't passed on. Target-specific variants are honored.
+//! * `PKG_CONFIG_ALLOW_SYSTEM_LIBS` - set to `1` for `pkg-config` by default,
+//!   whatever the environment says; see `Config::system_libs` to honor it,
+//!   including its target-specific variants, or always unset it instead.
+//! * `MSYSTEM` - if set when building for a `*-windows-gnu` target, the
+//!   output of `pkg-config` is taken to come from MSYS2 and its POSIX paths,
+//!   e.g. `/mingw64/lib`, are translated to Windows paths. The Windows path
+//!   of the MSYS2 root is taken from `PKG_CONFIG_MSYS_ROOT`, or else asked
+//!   from `cygpath`.
+//! * `PKG_CONFIG_RS_METADATA_FILE` - if set, every `cargo:` line printed for
+//!   a probe, including by `emit_metadata` and
+//!   `Library::emit_cargo_metadata`, is also appended to the file it names,
+//!   preceded by the name of the package being built, e.g. to audit the link
+//!   directives of a whole workspace.
+//! * `PKG_CONFIG_RS_SERVER` - with the `probe-server` feature, if set, the
+//!   first build script to probe a package starts a local server that caches
+//!   the answers of `pkg-config` for the other build scripts of the same
+//!   target directory and profile, so that a workspace of many `-sys` crates
+//!   probing the same packages runs `pkg-config` once per package. Only
+//!   build scripts calling `probe_server` use it. Answers are dropped when a
+//!   `.pc` file in the search path changes, and the server exits after ten
+//!   seconds without requests.
 //!
 //! There are also a number of environment variables which can configure how a
 //! library is linked to (dynamically vs statically). These variables control
@@ -25,9 +56,27 @@
 //! * `PKG_CONFIG_ALL_STATIC` - pass `--static` for all libraries
 //! * `PKG_CONFIG_ALL_DYNAMIC` - do not pass `--static` for all libraries
 //!
+//! `FOO_STATIC` and `PKG_CONFIG_ALL_STATIC` may also be set to one of
+//! `force`, `yes`, `no`, `prefer` or `probe`, for the `Statik` mode of that
+//! name. Any other value, e.g. `1`, means `yes`.
+//!
 //! After running `pkg-config` all appropriate Cargo metadata will be printed on
 //! stdout if the search was successful.
 //!
+//! With the `libpkgconf` feature, packages are resolved in-process by linking
+//! against libpkgconf instead of running `pkg-config`. Otherwise, with the
+//! `vendored-resolver` feature, a bundled pure-Rust resolver handles simple
+//! packages when no `pkg-config` executable can be found, unless
+//! `PKG_CONFIG_NO_VENDORED` is set. With the `vcpkg` feature, packages that
+//! can't be probed for `*-pc-windows-msvc` targets are looked up with vcpkg.
+//! With the `cmake-fallback` feature, packages that can't be probed are
+//! looked up among the CMake package configuration files in
+//! `CMAKE_PREFIX_PATH`, `/usr/local` and `/usr`.
+//!
+//! When a package can't be found, errors suggest the system packages that
+//! usually provide it, see `distro_packages`. `PKG_CONFIG_DISTRO_PACKAGES`
+//! can name a TOML file adding to or overriding these suggestions.
+//!
 //! # Example
 //!
 //! Find the system library named `foo`, with minimum version 1.2.3:
@@ -61,34 +110,230 @@
 //! }
 //! ```
 
-#![doc(html_root_url = "https://docs.rs/pkg-config/0.3")]
+#![doc(html_root_url = "https://docs.rs/pkg-config/0.4")]
+
+#[cfg(feature = "cc")]
+extern crate cc;
+#[cfg(feature = "vcpkg")]
+extern crate vcpkg;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
-#[allow(unused_imports)] // Required for Rust <1.23
-use std::ascii::AsciiExt;
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::error;
 use std::ffi::{OsStr, OsString};
 use std::fmt;
-use std::io;
+use std::fs;
+use std::io::{self, Write};
+use std::ops;
 use std::path::{PathBuf, Path};
 use std::process::{Command, Output};
+use std::slice;
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use std::thread;
 
-pub fn target_supported() -> bool {
-    let target = env::var("TARGET").unwrap_or_else(|_| String::new());
-    let host = env::var("HOST").unwrap_or_else(|_| String::new());
+mod archive;
+mod artifact;
+mod brew;
+mod cache;
+mod capture;
+#[cfg(feature = "cmake-fallback")]
+mod cmake;
+mod diagnose;
+mod distro;
+#[cfg(feature = "elf")]
+mod elf;
+mod framework;
+#[cfg(feature = "libpkgconf")]
+mod libpkgconf;
+mod metadata;
+mod msys;
+#[cfg(feature = "vendored-resolver")]
+mod resolver;
+mod package;
+#[cfg(feature = "serde")]
+mod report;
+mod search_path;
+#[cfg(all(feature = "probe-server", unix))]
+mod server;
+pub mod library;
+pub mod lock;
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub use artifact::{Artifact, ArtifactKind};
+pub use distro::{distro_packages, Distro, Suggestion};
+pub use package::PackageConfig;
+#[cfg(feature = "serde")]
+pub use report::Report;
+pub use search_path::{parse_search_path, validate_search_path, SearchPathEntry, SearchPathProblem};
 
-    // Only use pkg-config in host == target situations by default (allowing an
-    // override).
-    (host == target || env::var_os("PKG_CONFIG_ALLOW_CROSS").is_some())
+pub fn target_supported() -> bool {
+    Config::new().check_cross().is_ok()
 }
 
-#[derive(Clone, PartialEq)]
+/// Whether, and how, a package is linked statically.
+///
+/// Every mode but `No` passes `--static` to `pkg-config`, so that the
+/// private dependencies are listed too; they differ in how each `-l` library
+/// is then linked. The names used by `FromStr` and `Display` are those of
+/// the `FOO_STATIC` values, e.g. `prefer`.
+///
+/// More modes may be added, so matches need a wildcard arm.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Statik {
+    /// Link every library dynamically.
     No,
+    /// Link a library statically if its static archive is found in one of
+    /// the `-L` directories outside of the system ones (`/usr` or, on
+    /// macOS, `/Library` and `/System`), dynamically otherwise.
     Yes,
+    /// Link every library statically, without checking that its static
+    /// archive exists.
     Force,
+    /// Like `Yes`, but static archives in system directories such as
+    /// `/usr/lib` are used too, rather than only those in other directories.
+    Prefer,
+    /// Like `Prefer`, but the default library directories of the C toolchain
+    /// are searched for static archives too. Check `Library::linkage` for
+    /// the libraries that were linked dynamically after all.
+    Probe,
+}
+
+impl Statik {
+    /// The mode `config` probes the package `name` with: `No` if it's on the
+    /// static blacklist, otherwise the mode set with `Config::statik` or, by
+    /// default, the one inferred from the environment.
+    pub fn resolve(config: &Config, name: &str) -> Statik {
+        config.is_static(name)
+    }
+
+    /// Whether the library `lib`, found by a probe with this mode, is linked
+    /// statically given the `-L` directories `link_paths`, as done for
+    /// `Library::static_libs`. The whitelist and blacklist of `config` are
+    /// taken into account.
+    pub fn links_statically(&self, config: &Config, lib: &str, link_paths: &[PathBuf]) -> bool {
+        config.links_statically(self, lib, link_paths, &config.static_archive_patterns())
+    }
+}
+
+impl str::FromStr for Statik {
+    type Err = ParseStatikError;
+
+    fn from_str(s: &str) -> Result<Statik, ParseStatikError> {
+        match &s.to_ascii_lowercase()[..] {
+            "no" => Ok(Statik::No),
+            "yes" => Ok(Statik::Yes),
+            "force" => Ok(Statik::Force),
+            "prefer" => Ok(Statik::Prefer),
+            "probe" => Ok(Statik::Probe),
+            _ => Err(ParseStatikError { value: s.to_string() }),
+        }
+    }
+}
+
+impl fmt::Display for Statik {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match *self {
+            Statik::No => "no",
+            Statik::Yes => "yes",
+            Statik::Force => "force",
+            Statik::Prefer => "prefer",
+            Statik::Probe => "probe",
+        })
+    }
+}
+
+/// The error of parsing a `Statik` from a string other than `no`, `yes`,
+/// `force`, `prefer` or `probe`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseStatikError {
+    value: String,
+}
+
+impl error::Error for ParseStatikError {}
+
+impl fmt::Display for ParseStatikError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "invalid static linking mode `{}`, expected one of no, yes, force, prefer \
+                   or probe", self.value)
+    }
+}
+
+/// How a library of `Library::libs` is linked, see `Library::linkage`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize),
+           serde(rename_all = "lowercase"))]
+pub enum Linkage {
+    Static,
+    Dynamic,
+    /// Not linked at all, e.g. `-lm` when targetting MSVC, whose C runtime
+    /// provides it, or a library left to another crate by
+    /// `Config::requires_policy`.
+    Skipped,
+}
+
+/// Whether `pkg-config` keeps `-L` flags for the system library directories,
+/// i.e. how `PKG_CONFIG_ALLOW_SYSTEM_LIBS` is set, see `Config::system_libs`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SystemLibs {
+    /// Set `PKG_CONFIG_ALLOW_SYSTEM_LIBS` to `1`, keeping them even if the
+    /// environment sets it to `0` or `false`.
+    ForceOn,
+    /// Unset `PKG_CONFIG_ALLOW_SYSTEM_LIBS`, dropping them even if it's set
+    /// in the environment.
+    ForceOff,
+    /// Use `PKG_CONFIG_ALLOW_SYSTEM_LIBS` as set in the environment, if at
+    /// all, unless it's `0` or `false`.
+    #[default]
+    Inherit,
+}
+
+/// How `PKG_CONFIG_PATH` and `PKG_CONFIG_LIBDIR` combine into the search
+/// path, see `Config::search_mode`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SearchMode {
+    /// As `pkg-config` does: the directories of `PKG_CONFIG_PATH`, then
+    /// those of `PKG_CONFIG_LIBDIR` or, if it isn't set, the default search
+    /// path.
+    #[default]
+    Default,
+    /// The directories of `PKG_CONFIG_PATH`, then those of
+    /// `PKG_CONFIG_LIBDIR`, and nothing else: the default search path is
+    /// never used.
+    PathThenLibdir,
+    /// The directories of `PKG_CONFIG_LIBDIR` or, if it isn't set, the
+    /// default search path. `PKG_CONFIG_PATH` is ignored.
+    LibdirOnly,
+}
+
+/// Which libraries of a package's `Requires` a probe links, see
+/// `Config::requires_policy`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RequiresPolicy {
+    /// Link everything `pkg-config --libs` returns, including the libraries
+    /// of required packages, transitively.
+    #[default]
+    EmitAll,
+    /// Only link the package's own libraries, leaving those of its required
+    /// packages to be linked by whoever probes them.
+    DirectOnly,
+    /// Leave the libraries of the required packages in the map, direct or
+    /// not, to the `-sys` crate with the `links` key they map to, e.g.
+    /// `"glib-2.0"` to `"glib"`. Those of other packages are linked.
+    DeferToDeps(BTreeMap<String, String>),
 }
 
 impl From<bool> for Statik {
@@ -101,39 +346,287 @@ impl From<bool> for Statik {
 }
 
 #[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Config {
     statik: Option<Statik>,
     statik_blacklist: Vec<String>,
+    statik_whitelist: Vec<String>,
+    whole_archive: Vec<String>,
+    static_archive_patterns: Vec<String>,
     atleast_version: Option<String>,
+    at_most_version: Option<String>,
+    min_pkg_config_version: Option<String>,
+    fragment_filter: Option<String>,
+    pure: bool,
+    target: Option<String>,
+    host: Option<String>,
+    #[cfg_attr(feature = "serde", serde(with = "report::os_strings"))]
     extra_args: Vec<OsString>,
+    #[cfg_attr(feature = "serde", serde(with = "report::query_args"))]
+    query_args: Vec<(Query, OsString)>,
     cargo_metadata: bool,
+    defer_metadata: bool,
     env_metadata: bool,
-    print_system_libs: bool,
+    rerun_on_system_packages: bool,
+    system_libs: SystemLibs,
+    libpkgconf: bool,
+    #[cfg(feature = "vendored-resolver")]
+    vendored_resolver: bool,
+    standalone: bool,
+    // Callbacks can't be serialized, and are left out.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    trace: Option<TraceFn>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    or_else: Option<OrElseFn>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    on_command: Option<OnCommandFn>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    on_output: Option<OnOutputFn>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    variable_assertions: Vec<(String, VariableCheckFn)>,
+    lock_file: Option<PathBuf>,
+    negative_cache: Option<(PathBuf, Duration)>,
+    root: Option<PathBuf>,
+    sysroot: Option<PathBuf>,
+    disable_uninstalled: Option<bool>,
+    top_build_dir: Option<PathBuf>,
+    define_prefix: Option<bool>,
+    search_mode: SearchMode,
+    requires_policy: RequiresPolicy,
+    system_library_path: Option<Vec<PathBuf>>,
+    system_include_path: Option<Vec<PathBuf>>,
+    fallback_paths: Vec<PathBuf>,
+    extra_include_paths: Vec<PathBuf>,
+    extra_link_paths: Vec<PathBuf>,
+    drop_system_link_paths: bool,
+    verify_archives: bool,
+    warnings: bool,
+    forward_stderr: bool,
+    standard_variables: bool,
+    cfg_defines: Vec<String>,
+    validate_paths: PathPolicy,
+    expected_kind: Option<LibraryKind>,
+    try_link: bool,
+    retries: u32,
+    macos_package_managers: bool,
+    normalize_windows_paths: bool,
+    ignore_statik_env: bool,
+    hermetic: bool,
+}
+
+type TraceFn = Arc<dyn Fn(&Trace) + Send + Sync>;
+type OrElseFn = Arc<dyn Fn(Error) -> Result<Library, Error> + Send + Sync>;
+type OnCommandFn = Arc<dyn Fn(&mut Command) -> Result<(), String> + Send + Sync>;
+type OnOutputFn = Arc<dyn Fn(&mut Output) + Send + Sync>;
+type VariableCheckFn = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// What to do about `-L` and `-F` directories that don't exist, see
+/// `Config::validate_paths`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathPolicy {
+    /// Don't check the directories.
+    #[default]
+    Ignore,
+    /// Print a `cargo:warning=` line for each missing directory.
+    Warn,
+    /// Fail the probe with `Error::InvalidPaths`.
+    Error,
+}
+
+/// The `pkg-config` invocations an argument added with `Config::arg_for` is
+/// passed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Query {
+    /// The queries of `--libs` and `--cflags`, or their variants.
+    Flags,
+    /// The queries of `--modversion`.
+    Version,
+    /// The queries of `--variable` and `--print-variables`.
+    Variable,
+}
+
+/// The shape of what a package resolves to, see `Library::kind`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LibraryKind {
+    /// Libraries to link with `-l`, and possibly frameworks.
+    Libs,
+    /// macOS frameworks, without any `-l` library.
+    FrameworksOnly,
+    /// Neither libraries nor frameworks, e.g. a header-only C++ library.
+    HeaderOnly,
 }
 
+/// An event reported to the callback set with `Config::trace`.
 #[derive(Debug)]
+#[non_exhaustive]
+pub enum Trace<'a> {
+    /// A `pkg-config` command about to be run. Its `Debug` output includes
+    /// the environment variables it's given.
+    Command(&'a Command),
+    /// The output of the command that was just run.
+    Output(&'a Output),
+    /// The flags parsed out of the `--libs --cflags` output for a package.
+    Flags { package: &'a str, flags: &'a [String] },
+    /// A line of cargo metadata, without the `cargo:` prefix.
+    Metadata(&'a str),
+}
+
+impl<'a> fmt::Display for Trace<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Trace::Command(cmd) => write!(f, "running {:?}", cmd),
+            Trace::Output(output) => {
+                write!(f, "{}: {}", output.status, String::from_utf8_lossy(&output.stdout).trim())?;
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if !stderr.trim().is_empty() {
+                    write!(f, "\n--- stderr\n{}", stderr.trim())?;
+                }
+                Ok(())
+            }
+            Trace::Flags { package, flags } => write!(f, "flags of {}: {:?}", package, flags),
+            Trace::Metadata(line) => write!(f, "cargo:{}", line),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(clippy::manual_non_exhaustive)]
 pub struct Library {
     pub libs: Vec<String>,
     pub link_paths: Vec<PathBuf>,
     pub frameworks: Vec<String>,
     pub framework_paths: Vec<PathBuf>,
+    /// Frameworks passed with `-weak_framework`, which may be missing at
+    /// runtime.
+    pub weak_frameworks: Vec<String>,
+    /// Frameworks passed with `-needed_framework`, which are linked even if
+    /// no symbol is used from them.
+    pub needed_frameworks: Vec<String>,
+    pub include_paths: Vec<PathBuf>,
+    /// Directories passed with `-isystem`.
+    pub system_include_paths: Vec<PathBuf>,
+    /// Directories passed with `-iquote`.
+    pub quote_include_paths: Vec<PathBuf>,
+    /// Directories passed with `-idirafter`.
+    pub after_include_paths: Vec<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(with = "report::sorted"))]
+    pub defines: HashMap<String, Option<String>>,
+    /// The other compile flags, e.g. `-pthread` or `-std=gnu11`, with the
+    /// arguments of options such as `-arch x86_64` as separate words. Flags
+    /// only meaningful to the linker are left out.
+    pub compile_flags: Vec<String>,
+    pub version: String,
+    /// The libraries of `libs` that are linked statically.
+    pub static_libs: Vec<String>,
+    /// How each `-l` flag was handled, including the libraries left out of
+    /// `libs`.
+    #[cfg_attr(feature = "serde", serde(with = "report::sorted"))]
+    pub linkage: HashMap<String, Linkage>,
+    /// Libraries linked statically with the `+whole-archive` modifier, see
+    /// `Config::whole_archive`.
+    pub whole_archive: Vec<String>,
+    /// The file names of the libraries passed with `-l:<file>`, which the
+    /// linker looks up verbatim, e.g. `libfoo.so.1`. They aren't in `libs`.
+    pub verbatim_libs: Vec<String>,
+    /// How `-pthread` was handled, if the flags have it: `Static` or
+    /// `Dynamic`, decided like for `-lpthread`, if it added `pthread` to
+    /// `libs` because the target has a separate `libpthread`, e.g. Linux or
+    /// MinGW, or `Skipped` if the C library
+    /// provides the threads, e.g. on macOS, Android or with MSVC. It's kept
+    /// in `compile_flags` either way.
+    pub pthread: Option<Linkage>,
+    /// The `pkg-config` invocations that found the library, as shell
+    /// command lines including the environment variables set for them.
+    ///
+    /// Empty if the library was resolved in-process or wasn't probed.
+    pub probe_commands: Vec<String>,
+    /// The lines `pkg-config` printed on stderr while finding the library,
+    /// e.g. warnings about malformed `.pc` files, see
+    /// `Config::forward_stderr`.
+    pub warnings: Vec<String>,
+    /// The `prefix` variable of the package, see
+    /// `Config::standard_variables`.
+    pub prefix: Option<PathBuf>,
+    /// The `exec_prefix` variable of the package.
+    pub exec_prefix: Option<PathBuf>,
+    /// The `libdir` variable of the package.
+    pub libdir: Option<PathBuf>,
+    /// The `includedir` variable of the package.
+    pub includedir: Option<PathBuf>,
+    /// The variables checked with `Config::assert_variable`, with their
+    /// values.
+    pub variables: HashMap<String, String>,
+    /// The `PKG_CONFIG_SYSROOT_DIR` of the probe, if any.
+    sysroot: Option<PathBuf>,
+    /// The name of the package found, if it was probed.
+    package: Option<String>,
+    /// The `pkg-config` executable run to find it, if any.
+    pkg_config: Option<String>,
+    /// The `.pc` file the package was found in, if it was probed and the
+    /// file could be found. It's looked up in the directories configured for
+    /// the package, and for packages not resolved in-process, in the default
+    /// search path of `pkg-config`, which is asked once per process.
+    pub pc_path: Option<PathBuf>,
+    /// Whether it's a builtin package of `pkg-config`, see `is_virtual`.
+    virtual_package: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _priv: (),
+}
+
+/// Source language of the code compiled against a `Library`, see
+/// `Library::compile_args`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    C,
+    Cxx,
+}
+
+/// Compile-time information about a header-only package, as found by
+/// `Config::probe_headers`.
+#[derive(Debug)]
+#[allow(clippy::manual_non_exhaustive)]
+pub struct Includes {
     pub include_paths: Vec<PathBuf>,
+    /// Directories passed with `-isystem`.
+    pub system_include_paths: Vec<PathBuf>,
+    /// Directories passed with `-iquote`.
+    pub quote_include_paths: Vec<PathBuf>,
+    /// Directories passed with `-idirafter`.
+    pub after_include_paths: Vec<PathBuf>,
     pub defines: HashMap<String, Option<String>>,
     pub version: String,
     _priv: (),
 }
 
+/// A package exposing code generators or other build tools, as found by
+/// `Config::probe_tool_package`.
+#[derive(Debug)]
+#[allow(clippy::manual_non_exhaustive)]
+pub struct ToolPackage {
+    /// The variables of the package naming an executable in its `bindir` or
+    /// `libexecdir`, e.g. `vapigen` for `vapigen.pc`, with their paths.
+    pub tools: HashMap<String, PathBuf>,
+    pub version: String,
+    _priv: (),
+}
+
 /// Represents all reasons `pkg-config` might not succeed or be run at all.
+#[non_exhaustive]
 pub enum Error {
     /// Aborted because of `*_NO_PKG_CONFIG` environment variable.
     ///
     /// Contains the name of the responsible environment variable.
     EnvNoPkgConfig(String),
 
-    /// Cross compilation detected.
+    /// Cross compilation detected, and not allowed.
     ///
-    /// Override with `PKG_CONFIG_ALLOW_CROSS=1`.
-    CrossCompilation,
+    /// Override with `PKG_CONFIG_ALLOW_CROSS=1`. Contains the reason for the
+    /// decision.
+    CrossCompilation { reason: String },
 
     /// Failed to run `pkg-config`.
     ///
@@ -142,36 +635,225 @@ pub enum Error {
 
     /// `pkg-config` did not exit sucessfully.
     ///
-    /// Contains the command and output.
-    Failure { command: String, output: Output },
+    /// Contains the command and output, and the system packages that may
+    /// provide the package it didn't find, see `distro_packages`.
+    Failure { command: String, output: Output, suggestions: Vec<Suggestion> },
 
-    #[doc(hidden)]
-    // please don't match on this, we're likely to add more variants over time
-    __Nonexhaustive,
+    /// The package was found, but its version doesn't satisfy the
+    /// requirements.
+    ///
+    /// Contains the package name, the version found and the requirement.
+    VersionMismatch { package: String, found: String, required: String },
+
+    /// None of the alternative package names passed to `Config::probe_any`
+    /// could be probed.
+    ///
+    /// Contains each name that was tried, with the error it failed with.
+    AlternativesFailed { tried: Errors },
+
+    /// libpkgconf or the vendored resolver failed to resolve the package
+    /// in-process.
+    ///
+    /// Contains the package name, the messages reported and the system
+    /// packages that may provide the package if it wasn't found.
+    InProcess { package: String, message: String, suggestions: Vec<Suggestion> },
+
+    /// The lock file passed to `Config::enforce_lock` couldn't be read.
+    ///
+    /// Contains the path of the lock file and the cause.
+    LockFile { path: PathBuf, cause: io::Error },
+
+    /// The package was found in a different major or minor version than the
+    /// one recorded in the lock file.
+    ///
+    /// Contains the package name, the locked version and the version found.
+    LockMismatch { package: String, locked: String, found: String },
+
+    /// A tool variable of a package probed with `Config::probe_tool_package`
+    /// doesn't name an executable file.
+    ///
+    /// Contains the package name, the variable and its value.
+    ToolNotExecutable { package: String, variable: String, path: PathBuf },
+
+    /// The package was recorded as missing in the cache file passed to
+    /// `Config::negative_cache`.
+    ///
+    /// Contains the package name and the system packages that may provide it.
+    NotFoundCached { package: String, suggestions: Vec<Suggestion> },
+
+    /// The framework passed to `Config::probe_framework` wasn't found.
+    ///
+    /// Contains the framework name and the directories searched.
+    FrameworkNotFound { framework: String, searched: Vec<PathBuf> },
+
+    /// The callback set with `Config::on_command` refused to run a command.
+    ///
+    /// Contains the command and the reason given.
+    Vetoed { command: String, reason: String },
+
+    /// Some `-L` or `-F` directories of the package don't exist, see
+    /// `Config::validate_paths`.
+    ///
+    /// Contains the package name and the missing directories.
+    InvalidPaths { package: String, missing: Vec<PathBuf> },
+
+    /// The `[package.metadata.pkg-config]` table read by
+    /// `probe_from_metadata` couldn't be read or parsed.
+    ///
+    /// Contains the path of the manifest and what's wrong with it.
+    Metadata { path: PathBuf, message: String },
+
+    /// The `pkg-config` executable is older than the version required with
+    /// `Config::min_pkg_config_version`.
+    ///
+    /// Contains the version found and the version required.
+    ToolTooOld { found: String, required: String },
+
+    /// The package doesn't resolve to the shape of result required with
+    /// `Config::expect_kind`.
+    ///
+    /// Contains the package name, the kind required and what's wrong.
+    UnexpectedKind { package: String, expected: LibraryKind, problem: String },
+
+    /// A test program couldn't be linked against the package, see
+    /// `Config::try_link`.
+    ///
+    /// Contains the package name, the compiler command and its errors.
+    LinkFailed { package: String, command: String, stderr: String },
+
+    /// The command kept failing to start with an error that's usually
+    /// transient, like `Text file busy`, until `Config::retries` ran out.
+    ///
+    /// Contains the command, how many times it was tried and the last error.
+    SpawnFailed { command: String, attempts: u32, cause: io::Error },
+
+    /// `pkg-config` printed something that must be text, like a version, a
+    /// library name or a define, that isn't valid UTF-8. Paths are read from
+    /// its output as they are.
+    ///
+    /// Contains the command and its output, decoded lossily.
+    InvalidOutput { command: String, output: String },
+
+    /// A variable checked with `Config::assert_variable` isn't defined, or
+    /// its value failed the check.
+    ///
+    /// Contains the package name, the variable and its value, if defined.
+    VariableAssertion { package: String, variable: String, value: Option<String> },
+
+    /// The package was probed with `Config::hermetic`, but would have been
+    /// resolved by the installed `pkg-config` rather than the vendored
+    /// resolver, which takes the `vendored-resolver` feature.
+    ///
+    /// Contains the package name.
+    NotHermetic { package: String },
 }
 
-impl error::Error for Error {
-    fn description(&self) -> &str {
+impl Error {
+    /// Whether the package (or, for `AlternativesFailed`, every alternative)
+    /// couldn't be found in the search path.
+    ///
+    /// A package found in the wrong version isn't "not found", see
+    /// `VersionMismatch`, and neither is one whose requires are missing.
+    pub fn is_not_found(&self) -> bool {
         match *self {
-            Error::EnvNoPkgConfig(_) => "pkg-config requested to be aborted",
-            Error::CrossCompilation => {
-                "pkg-config doesn't handle cross compilation. \
-                 Use PKG_CONFIG_ALLOW_CROSS=1 to override"
+            Error::Failure { ref output, .. } => {
+                distro::requested_missing(&String::from_utf8_lossy(&output.stderr)).is_some()
+            }
+            Error::InProcess { ref package, ref message, .. } => {
+                distro::missing_package(message) == Some(&package[..])
             }
-            Error::Command { .. } => "failed to run pkg-config",
-            Error::Failure { .. } => "pkg-config did not exit sucessfully",
-            Error::__Nonexhaustive => panic!(),
+            Error::NotFoundCached { .. } => true,
+            Error::FrameworkNotFound { .. } => true,
+            Error::AlternativesFailed { ref tried } => tried.iter().all(|(_, e)| e.is_not_found()),
+            _ => false,
+        }
+    }
+
+    /// Whether the `pkg-config` executable itself couldn't be found.
+    pub fn is_tool_missing(&self) -> bool {
+        match *self {
+            Error::Command { ref cause, .. } => cause.kind() == io::ErrorKind::NotFound,
+            _ => false,
         }
     }
+}
 
-    fn cause(&self) -> Option<&error::Error> {
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
             Error::Command { ref cause, .. } => Some(cause),
+            Error::LockFile { ref cause, .. } => Some(cause),
+            Error::SpawnFailed { ref cause, .. } => Some(cause),
             _ => None,
         }
     }
 }
 
+/// The errors of several probes, each with the name of the package that
+/// failed, see `probe_all` and `Config::probe_any`.
+///
+/// Dereferences to the `(name, error)` pairs, in the order of the probes.
+#[derive(Debug, Default)]
+pub struct Errors {
+    errors: Vec<(String, Error)>,
+}
+
+impl Errors {
+    /// Record the failure of the probe of `name`.
+    pub fn push(&mut self, name: &str, error: Error) {
+        self.errors.push((name.to_string(), error));
+    }
+
+    /// The `(name, error)` pairs.
+    pub fn into_inner(self) -> Vec<(String, Error)> {
+        self.errors
+    }
+}
+
+impl ops::Deref for Errors {
+    type Target = [(String, Error)];
+
+    fn deref(&self) -> &[(String, Error)] {
+        &self.errors
+    }
+}
+
+impl IntoIterator for Errors {
+    type Item = (String, Error);
+    type IntoIter = std::vec::IntoIter<(String, Error)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Errors {
+    type Item = &'a (String, Error);
+    type IntoIter = std::slice::Iter<'a, (String, Error)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.iter()
+    }
+}
+
+impl error::Error for Errors {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.errors.first().map(|e| &e.1 as &(dyn error::Error + 'static))
+    }
+}
+
+impl fmt::Display for Errors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for (i, (name, err)) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "--- {}\n{}", name, err)?;
+        }
+        Ok(())
+    }
+}
+
 // Workaround for temporary lack of impl Debug for Output in stable std
 struct OutputDebugger<'a>(&'a Output);
 
@@ -179,13 +861,13 @@ struct OutputDebugger<'a>(&'a Output);
 impl<'a> fmt::Debug for OutputDebugger<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let stdout_utf8 = str::from_utf8(&self.0.stdout);
-        let stdout_debug: &fmt::Debug = match stdout_utf8 {
+        let stdout_debug: &dyn fmt::Debug = match stdout_utf8 {
             Ok(ref str) => str,
             Err(_) => &self.0.stdout
         };
 
         let stderr_utf8 = str::from_utf8(&self.0.stderr);
-        let stderr_debug: &fmt::Debug = match stderr_utf8 {
+        let stderr_debug: &dyn fmt::Debug = match stderr_utf8 {
             Ok(ref str) => str,
             Err(_) => &self.0.stderr
         };
@@ -207,20 +889,138 @@ impl fmt::Debug for Error {
                  .field(name)
                  .finish()
             }
-            Error::CrossCompilation => write!(f, "CrossCompilation"),
+            Error::CrossCompilation { ref reason } => {
+                f.debug_struct("CrossCompilation")
+                 .field("reason", reason)
+                 .finish()
+            }
             Error::Command { ref command, ref cause } => {
                 f.debug_struct("Command")
                  .field("command", command)
                  .field("cause", cause)
                  .finish()
             }
-            Error::Failure { ref command, ref output } => {
+            Error::Failure { ref command, ref output, ref suggestions } => {
                 f.debug_struct("Failure")
                  .field("command", command)
                  .field("output", &OutputDebugger(output))
+                 .field("suggestions", suggestions)
+                 .finish()
+            }
+            Error::VersionMismatch { ref package, ref found, ref required } => {
+                f.debug_struct("VersionMismatch")
+                 .field("package", package)
+                 .field("found", found)
+                 .field("required", required)
+                 .finish()
+            }
+            Error::AlternativesFailed { ref tried } => {
+                f.debug_struct("AlternativesFailed")
+                 .field("tried", tried)
+                 .finish()
+            }
+            Error::InProcess { ref package, ref message, ref suggestions } => {
+                f.debug_struct("InProcess")
+                 .field("package", package)
+                 .field("message", message)
+                 .field("suggestions", suggestions)
+                 .finish()
+            }
+            Error::LockFile { ref path, ref cause } => {
+                f.debug_struct("LockFile")
+                 .field("path", path)
+                 .field("cause", cause)
+                 .finish()
+            }
+            Error::LockMismatch { ref package, ref locked, ref found } => {
+                f.debug_struct("LockMismatch")
+                 .field("package", package)
+                 .field("locked", locked)
+                 .field("found", found)
+                 .finish()
+            }
+            Error::ToolNotExecutable { ref package, ref variable, ref path } => {
+                f.debug_struct("ToolNotExecutable")
+                 .field("package", package)
+                 .field("variable", variable)
+                 .field("path", path)
+                 .finish()
+            }
+            Error::NotFoundCached { ref package, ref suggestions } => {
+                f.debug_struct("NotFoundCached")
+                 .field("package", package)
+                 .field("suggestions", suggestions)
+                 .finish()
+            }
+            Error::FrameworkNotFound { ref framework, ref searched } => {
+                f.debug_struct("FrameworkNotFound")
+                 .field("framework", framework)
+                 .field("searched", searched)
+                 .finish()
+            }
+            Error::Vetoed { ref command, ref reason } => {
+                f.debug_struct("Vetoed")
+                 .field("command", command)
+                 .field("reason", reason)
+                 .finish()
+            }
+            Error::InvalidPaths { ref package, ref missing } => {
+                f.debug_struct("InvalidPaths")
+                 .field("package", package)
+                 .field("missing", missing)
+                 .finish()
+            }
+            Error::Metadata { ref path, ref message } => {
+                f.debug_struct("Metadata")
+                 .field("path", path)
+                 .field("message", message)
+                 .finish()
+            }
+            Error::ToolTooOld { ref found, ref required } => {
+                f.debug_struct("ToolTooOld")
+                 .field("found", found)
+                 .field("required", required)
+                 .finish()
+            }
+            Error::UnexpectedKind { ref package, ref expected, ref problem } => {
+                f.debug_struct("UnexpectedKind")
+                 .field("package", package)
+                 .field("expected", expected)
+                 .field("problem", problem)
+                 .finish()
+            }
+            Error::LinkFailed { ref package, ref command, ref stderr } => {
+                f.debug_struct("LinkFailed")
+                 .field("package", package)
+                 .field("command", command)
+                 .field("stderr", stderr)
+                 .finish()
+            }
+            Error::SpawnFailed { ref command, attempts, ref cause } => {
+                f.debug_struct("SpawnFailed")
+                 .field("command", command)
+                 .field("attempts", &attempts)
+                 .field("cause", cause)
+                 .finish()
+            }
+            Error::InvalidOutput { ref command, ref output } => {
+                f.debug_struct("InvalidOutput")
+                 .field("command", command)
+                 .field("output", output)
+                 .finish()
+            }
+            Error::VariableAssertion { ref package, ref variable, ref value } => {
+                f.debug_struct("VariableAssertion")
+                 .field("package", package)
+                 .field("variable", variable)
+                 .field("value", value)
+                 .finish()
+            }
+            Error::NotHermetic { ref package } => {
+                f.debug_struct("NotHermetic")
+                 .field("package", package)
                  .finish()
             }
-            Error::__Nonexhaustive => panic!(),
         }
     }
 }
@@ -231,16 +1031,15 @@ impl fmt::Display for Error {
             Error::EnvNoPkgConfig(ref name) => {
                 write!(f, "Aborted because {} is set", name)
             }
-            Error::CrossCompilation => {
-                write!(f, "Cross compilation detected. \
-                       Use PKG_CONFIG_ALLOW_CROSS=1 to override")
+            Error::CrossCompilation { ref reason } => {
+                write!(f, "Cross compilation detected: {}", reason)
             }
             Error::Command { ref command, ref cause } => {
                 write!(f, "Failed to run `{}`: {}", command, cause)
             }
-            Error::Failure { ref command, ref output } => {
-                let stdout = str::from_utf8(&output.stdout).unwrap();
-                let stderr = str::from_utf8(&output.stderr).unwrap();
+            Error::Failure { ref command, ref output, ref suggestions } => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let stderr = String::from_utf8_lossy(&output.stderr);
                 write!(f, "`{}` did not exit successfully: {}", command, output.status)?;
                 if !stdout.is_empty() {
                     write!(f, "\n--- stdout\n{}", stdout)?;
@@ -248,62 +1047,603 @@ impl fmt::Display for Error {
                 if !stderr.is_empty() {
                     write!(f, "\n--- stderr\n{}", stderr)?;
                 }
+                match distro::missing_package(&stderr) {
+                    Some(package) => write_hint(f, package, suggestions),
+                    None => Ok(()),
+                }
+            }
+            Error::VersionMismatch { ref package, ref found, ref required } => {
+                write!(f, "Package `{}` has version {}, but {} is required",
+                       package, found, required)
+            }
+            Error::AlternativesFailed { ref tried } => {
+                write!(f, "None of the alternative packages could be found\n{}", tried)
+            }
+            Error::InProcess { ref package, ref message, ref suggestions } => {
+                write!(f, "Failed to resolve `{}` in-process: {}", package, message)?;
+                write_hint(f, package, suggestions)
+            }
+            Error::LockFile { ref path, ref cause } => {
+                write!(f, "Failed to read lock file `{}`: {}", path.display(), cause)
+            }
+            Error::LockMismatch { ref package, ref locked, ref found } => {
+                write!(f, "Package `{}` is locked to version {}, but version {} was found",
+                       package, locked, found)
+            }
+            Error::ToolNotExecutable { ref package, ref variable, ref path } => {
+                write!(f, "Tool `{}` of package `{}` is not executable: {}",
+                       variable, package, path.display())
+            }
+            Error::NotFoundCached { ref package, ref suggestions } => {
+                write!(f, "Package `{}` was not found by an earlier build (cached)", package)?;
+                write_hint(f, package, suggestions)
+            }
+            Error::FrameworkNotFound { ref framework, ref searched } => {
+                write!(f, "Framework `{}` was not found in:", framework)?;
+                for dir in searched {
+                    write!(f, "\n    {}", dir.display())?;
+                }
+                Ok(())
+            }
+            Error::Vetoed { ref command, ref reason } => {
+                write!(f, "Refused to run `{}`: {}", command, reason)
+            }
+            Error::InvalidPaths { ref package, ref missing } => {
+                write!(f, "Package `{}` has search paths that don't exist:", package)?;
+                for path in missing {
+                    write!(f, "\n    {}", path.display())?;
+                }
                 Ok(())
             }
-            Error::__Nonexhaustive => panic!(),
+            Error::Metadata { ref path, ref message } => {
+                write!(f, "Invalid `[package.metadata.pkg-config]` in {}: {}",
+                       path.display(), message)
+            }
+            Error::ToolTooOld { ref found, ref required } => {
+                write!(f, "pkg-config {} is too old, version {} or newer is required\n\
+                           --- hint\nInstall a newer pkg-config or pkgconf, or set \
+                           PKG_CONFIG to the path of one", found, required)
+            }
+            Error::UnexpectedKind { ref package, expected, ref problem } => {
+                write!(f, "Package `{}` was expected to have {}, but {}",
+                       package, diagnose::describe_kind(expected), problem)
+            }
+            Error::LinkFailed { ref package, ref command, ref stderr } => {
+                write!(f, "Failed to link a test program against `{}` with `{}`",
+                       package, command)?;
+                if !stderr.is_empty() {
+                    write!(f, "\n--- stderr\n{}", stderr)?;
+                }
+                write!(f, "\n--- hint\nThe flags of its .pc file may name libraries or \
+                           directories that don't exist, or are for another target")
+            }
+            Error::SpawnFailed { ref command, attempts, ref cause } => {
+                write!(f, "Failed to run `{}` after {} attempts: {}", command, attempts, cause)
+            }
+            Error::InvalidOutput { ref command, ref output } => {
+                write!(f, "`{}` printed output that isn't valid UTF-8: {}", command, output)
+            }
+            Error::VariableAssertion { ref package, ref variable, value: Some(ref value) } => {
+                write!(f, "Variable `{}` of package `{}` has the unexpected value `{}`",
+                       variable, package, value)
+            }
+            Error::VariableAssertion { ref package, ref variable, value: None } => {
+                write!(f, "Package `{}` doesn't define the variable `{}`", package, variable)
+            }
+            Error::NotHermetic { ref package } => {
+                write!(f, "Package `{}` can't be probed hermetically without the vendored \
+                           resolver; enable the `vendored-resolver` feature", package)
+            }
         }
     }
 }
 
-/// Deprecated in favor of the probe_library function
-#[doc(hidden)]
-pub fn find_library(name: &str) -> Result<Library, String> {
-    probe_library(name).map_err(|e| e.to_string())
+/// Suggest the system packages providing the missing package `package`, if
+/// any are known.
+fn write_hint(f: &mut fmt::Formatter, package: &str, suggestions: &[Suggestion])
+              -> Result<(), fmt::Error> {
+    if suggestions.is_empty() {
+        return Ok(());
+    }
+    write!(f, "\n--- hint\n`{}` may be provided by a system package:", package)?;
+    for suggestion in suggestions {
+        write!(f, "\n    {}", suggestion)?;
+    }
+    Ok(())
 }
 
-/// Simple shortcut for using all default options for finding a library.
-pub fn probe_library(name: &str) -> Result<Library, Error> {
-    Config::new().probe(name)
+/// What the `pkg-config` executable would take from its arguments and
+/// environment, for the in-process backends.
+#[cfg(any(feature = "libpkgconf", feature = "vendored-resolver"))]
+struct Options {
+    statik: bool,
+    pure: bool,
+    path: Option<String>,
+    libdir: Option<String>,
+    sysroot: Option<String>,
+    system_libs: bool,
+    system_cflags: bool,
+    /// Whether `-uninstalled.pc` files are preferred.
+    uninstalled: bool,
+    top_build_dir: Option<String>,
+    /// Whether `prefix` is redefined from the location of `.pc` files.
+    define_prefix: bool,
 }
 
-/// Run `pkg-config` to get the value of a variable from a package using
-/// --variable.
-pub fn get_variable(package: &str, variable: &str) -> Result<String, Error> {
-    let arg = format!("--variable={}", variable);
-    let cfg = Config::new();
-    let out = run(cfg.command(package, &[&arg]))?;
-    Ok(str::from_utf8(&out).unwrap().trim_right().to_owned())
+/// Why an in-process backend failed to resolve a package.
+#[cfg(any(feature = "libpkgconf", feature = "vendored-resolver"))]
+enum Failure {
+    /// The package exists and a version constraint wasn't satisfied, with
+    /// the version found and the messages to report if it's not the one of
+    /// the package itself.
+    VersionMismatch(String, String),
+    /// Anything else, with the messages to report.
+    Other(String),
 }
 
-impl Config {
-    /// Creates a new set of configuration options which are all initially set
-    /// to "blank".
-    pub fn new() -> Config {
-        Config {
-            statik: None,
-            statik_blacklist: vec![],
-            atleast_version: None,
-            extra_args: vec![],
-            print_system_libs: true,
-            cargo_metadata: true,
-            env_metadata: false,
-        }
-    }
+/// Cargo metadata collected from probes using `Config::defer_metadata`.
+struct Emitter {
+    /// The directives to print, each with the `PKG_CONFIG_RS_METADATA_FILE`
+    /// of the probe that deferred it.
+    pending: Vec<(String, Option<OsString>)>,
+    emitted: Vec<String>,
+}
 
-    /// Indicate whether the `--static` flag should be passed.
-    ///
-    /// This will override the inference from environment variables described in
-    /// the crate documentation.
-    pub fn statik<S>(&mut self, statik: S) -> &mut Config
-        where S: Into<Statik>
-    {
-        self.statik = Some(statik.into());
-        self
-    }
+/// The packages `pkg-config` and pkgconf provide without a `.pc` file, to
+/// probe the tool itself, e.g. for its version.
+const BUILTIN_PACKAGES: &[&str] = &["pkg-config", "pkgconf"];
 
-    pub fn statik_blacklist<S>(&mut self, blacklist: Vec<S>) -> &mut Config
-        where String: From<S>, S: Clone
-    {
+/// The environment variables `pkg-config` and the probes read that can change
+/// the result of a probe, which are part of `Config::fingerprint` along with
+/// their target-specific variants.
+const FINGERPRINT_VARS: &[&str] = &[
+    "PKG_CONFIG",
+    "PKG_CONFIG_PATH",
+    "PKG_CONFIG_LIBDIR",
+    "PKG_CONFIG_SYSROOT_DIR",
+    "PKG_CONFIG_ALLOW_CROSS",
+    "PKG_CONFIG_ALLOW_SYSTEM_CFLAGS",
+    "PKG_CONFIG_ALLOW_SYSTEM_LIBS",
+    "PKG_CONFIG_SYSTEM_INCLUDE_PATH",
+    "PKG_CONFIG_SYSTEM_LIBRARY_PATH",
+    "PKG_CONFIG_DISABLE_UNINSTALLED",
+    "PKG_CONFIG_TOP_BUILD_DIR",
+    "PKG_CONFIG_MSYS_ROOT",
+    "PKG_CONFIG_NO_VENDORED",
+    "PKG_CONFIG_PURE_DEPGRAPH",
+    "PKG_CONFIG_DONT_DEFINE_PREFIX",
+    "PKG_CONFIG_DONT_RELOCATE_PATHS",
+    "PKG_CONFIG_FDO_SYSROOT_RULES",
+    "PKG_CONFIG_PRESERVE_SYSROOT",
+    "PKG_CONFIG_MSVC_SYNTAX",
+    "MSYSTEM",
+];
+
+/// Whether each `pkg-config` executable used so far is pkgconf.
+static PKGCONF: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
+/// The `--version` of each `pkg-config` executable checked against
+/// `Config::min_pkg_config_version` so far.
+static TOOL_VERSIONS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Whether each `pkg-config` executable used with `Config::define_prefix`
+/// so far supports `--define-prefix`.
+static DEFINE_PREFIX: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
+static METADATA: Mutex<Emitter> = Mutex::new(Emitter {
+    pending: Vec::new(),
+    emitted: Vec::new(),
+});
+
+/// The default search path of each `pkg-config` executable asked.
+static DEFAULT_PC_PATH: Mutex<Vec<(String, Vec<PathBuf>)>> = Mutex::new(Vec::new());
+
+/// The default library directories of each C compiler and target used so
+/// far, see `Config::default_link_dirs`.
+static DEFAULT_LINK_DIRS: Mutex<Vec<(String, Vec<PathBuf>)>> = Mutex::new(Vec::new());
+
+/// Functions registered with `register_postprocessor`.
+static POSTPROCESSORS: Mutex<Vec<fn(&mut Library)>> = Mutex::new(Vec::new());
+
+/// Register a function applied to the `Library` of every subsequent probe,
+/// before its cargo metadata is printed.
+///
+/// This allows enforcing policies across all probes of a build script in one
+/// place, e.g. removing link paths or forbidding some libraries. Functions
+/// run in the order they were registered. If they change `libs` or
+/// `link_paths`, whether each library is linked statically is decided again
+/// from the result.
+pub fn register_postprocessor(f: fn(&mut Library)) {
+    POSTPROCESSORS.lock().unwrap().push(f);
+}
+
+/// Print the cargo metadata deferred by probes using
+/// `Config::defer_metadata`, in the order each directive was first seen.
+///
+/// Directives are only ever printed once, even if they're deferred again by
+/// later probes.
+pub fn emit_metadata() {
+    let mut emitter = METADATA.lock().unwrap();
+    let pending = std::mem::take(&mut emitter.pending);
+    for (s, file) in pending {
+        print_line(format!("cargo:{}", s), file.as_deref());
+        emitter.emitted.push(s);
+    }
+}
+
+/// Whether `path` is a file that can be executed.
+fn is_executable(path: &Path) -> bool {
+    match fs::metadata(path) {
+        #[cfg(unix)]
+        Ok(ref metadata) => {
+            use std::os::unix::fs::PermissionsExt;
+            metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+        }
+        #[cfg(not(unix))]
+        Ok(ref metadata) => metadata.is_file(),
+        Err(_) => false,
+    }
+}
+
+/// Print the cargo directive `s`, unless it's being captured.
+fn print_cargo(s: &str) {
+    print_line(format!("cargo:{}", s), None);
+}
+
+/// Print `line`, unless it's being captured, and append it to `file`, the
+/// `PKG_CONFIG_RS_METADATA_FILE` of the probe that printed it.
+fn print_line(line: String, file: Option<&OsStr>) {
+    let line = match capture::record(line) {
+        Some(line) => line,
+        None => return,
+    };
+    // Cargo sets `OUT_DIR` when running build scripts only, and not for the
+    // unit tests of this crate.
+    if cfg!(all(feature = "build-script-check", not(test))) && env::var_os("OUT_DIR").is_none() {
+        panic!("pkg-config: cargo metadata printed outside of a build script, where it \
+                doesn't affect linking; use `Config::standalone` to run probes at runtime");
+    }
+    println!("{}", line);
+    if let Some(path) = file {
+        let package = env::var("CARGO_PKG_NAME").unwrap_or_default();
+        // One write per line, so that build scripts running concurrently
+        // don't interleave their lines.
+        let _ = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(format!("{}: {}\n", package, line).as_bytes()));
+    }
+}
+
+/// The `pkgconfig` directories of the sysroot `sysroot`, in the order
+/// `pkg-config` searches them on the Debian-style `target`: those of
+/// `/usr/local`, then those of `/usr`, the multiarch one of the target first.
+fn sysroot_pkgconfig_dirs(sysroot: &Path, target: Option<&str>) -> Vec<PathBuf> {
+    let multiarch = target.and_then(diagnose::debian_multiarch);
+    let mut dirs = Vec::new();
+    for &(prefix, lib64) in &[("usr/local", false), ("usr", true)] {
+        let prefix = sysroot.join(prefix);
+        if let Some(ref multiarch) = multiarch {
+            dirs.push(prefix.join("lib").join(multiarch).join("pkgconfig"));
+        }
+        dirs.push(prefix.join("lib/pkgconfig"));
+        if lib64 {
+            dirs.push(prefix.join("lib64/pkgconfig"));
+        }
+        dirs.push(prefix.join("share/pkgconfig"));
+    }
+    dirs.push(sysroot.join("lib/pkgconfig"));
+    dirs
+}
+
+/// Run the probe `f`, then print the cargo metadata it printed with the
+/// `rerun-if` directives first, sorted and once each, so that they're in
+/// the same place whether the probe succeeded or failed.
+fn rerun_first<F, R>(config: &Config, f: F) -> R
+    where F: FnOnce() -> R
+{
+    let ((file, result), lines) = capture::capture(|| (config.metadata_file(), f()));
+    let (mut rerun, other): (Vec<_>, Vec<_>) =
+        lines.into_iter().partition(|line| line.starts_with("cargo:rerun-if-"));
+    rerun.sort();
+    rerun.dedup();
+    for line in rerun.into_iter().chain(other) {
+        print_line(line, file.as_deref());
+    }
+    result
+}
+
+/// Serve the cache of `PKG_CONFIG_RS_SERVER` if this build script was
+/// spawned to, and otherwise let its probes use it.
+///
+/// The server is a copy of the build script, which this function turns into
+/// the server instead of running the build script again, so it must be
+/// called first thing in `main`:
+///
+/// ```no_run
+/// pkg_config::probe_server();
+/// pkg_config::probe_library("foo").unwrap();
+/// ```
+#[cfg(all(feature = "probe-server", unix))]
+pub fn probe_server() {
+    server::init();
+}
+
+/// Deprecated in favor of the probe_library function
+#[doc(hidden)]
+pub fn find_library(name: &str) -> Result<Library, String> {
+    probe_library(name).map_err(|e| e.to_string())
+}
+
+/// Simple shortcut for using all default options for finding a library.
+pub fn probe_library(name: &str) -> Result<Library, Error> {
+    Config::new().probe(name)
+}
+
+/// Probe the packages declared in the `[package.metadata.pkg-config]` table
+/// of the `Cargo.toml` of the crate being built, returning the libraries
+/// found by the key they're declared with.
+///
+/// Each package is declared with its minimum version, or a table of its
+/// `name` (if it differs from the key), `version`, `max-version`, the cargo
+/// `feature` that enables it and whether it's `optional`:
+///
+/// ```toml
+/// [package.metadata.pkg-config]
+/// zlib = "1.2"
+/// gtk = { name = "gtk+-3.0", version = "3.24", feature = "gtk" }
+///
+/// [package.metadata.pkg-config.libpng]
+/// version = "1.6"
+/// optional = true
+/// ```
+///
+/// Only the subset of TOML above is understood, line by line: `[...]` table
+/// headers, `key = value` lines whose values are basic strings, booleans or
+/// single-line inline tables of them, and `#` comments. Literal and
+/// multi-line strings, arrays and dotted keys aren't supported in the table;
+/// the rest of the manifest is skipped.
+///
+/// Packages are probed with the default `Config`, with `probe_many`, so the
+/// cargo metadata of all of them is printed together. Optional packages that
+/// aren't found are left out of the result; any other failure is returned.
+/// `Cargo.toml` is watched with `cargo:rerun-if-changed`.
+pub fn probe_from_metadata() -> Result<HashMap<String, Library>, Error> {
+    metadata::probe()
+}
+
+/// Probe several packages, each with its own `Config`, on a few threads.
+///
+/// The results are returned in the order of `configs`. The cargo metadata
+/// of each probe is held back until all of them are done and then printed in
+/// that order too, so the output is the same as with sequential probes.
+pub fn probe_many<I>(configs: I) -> Vec<(String, Result<Library, Error>)>
+    where I: IntoIterator<Item = (String, Config)>
+{
+    let jobs = configs.into_iter().collect::<Vec<_>>();
+    let threads = thread::available_parallelism().map_or(4, |n| n.get()).min(8).min(jobs.len());
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..jobs.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    let (name, config) = match jobs.get(i) {
+                        Some(job) => job,
+                        None => break,
+                    };
+                    let result = capture::capture(|| (config.metadata_file(), config.probe(name)));
+                    results.lock().unwrap()[i] = Some(result);
+                }
+            });
+        }
+    });
+
+    let results = results.into_inner().unwrap();
+    jobs.into_iter().zip(results).map(|((name, _), result)| {
+        let ((file, result), lines) = result.unwrap();
+        for line in lines {
+            print_line(line, file.as_deref());
+        }
+        (name, result)
+    }).collect()
+}
+
+/// Probe several packages like `probe_many`, failing with the errors of all
+/// the probes that failed if any did.
+pub fn probe_all<I>(configs: I) -> Result<Vec<Library>, Errors>
+    where I: IntoIterator<Item = (String, Config)>
+{
+    let mut libraries = Vec::new();
+    let mut errors = Errors::default();
+    for (name, result) in probe_many(configs) {
+        match result {
+            Ok(library) => libraries.push(library),
+            Err(err) => errors.push(&name, err),
+        }
+    }
+    if errors.is_empty() { Ok(libraries) } else { Err(errors) }
+}
+
+/// Run `pkg-config` to get the value of a variable from a package using
+/// --variable.
+pub fn get_variable(package: &str, variable: &str) -> Result<String, Error> {
+    let arg = format!("--variable={}", variable);
+    let cfg = Config::new();
+    let mut cmd = cfg.command(package, &[&arg]);
+    let out = run(&mut cmd, &cfg)?;
+    Ok(utf8(out, &cmd)?.trim_end().to_owned())
+}
+
+/// Results of the sanity checks run by `self_check`.
+#[derive(Debug)]
+#[allow(clippy::manual_non_exhaustive)]
+pub struct SelfCheck {
+    /// The `pkg-config` executable that was checked.
+    pub executable: String,
+    /// The version reported by `pkg-config --version`.
+    pub version: Result<String, Error>,
+    /// The default search path, i.e. the `pc_path` variable of the builtin
+    /// `pkg-config` package.
+    pub pc_path: Result<String, Error>,
+    /// Whether the builtin `pkg-config` package can be found.
+    pub builtin_package: Result<(), Error>,
+    /// The directories of `PKG_CONFIG_PATH` followed by those of `pc_path`,
+    /// see `validate_search_path`.
+    pub search_path: Vec<SearchPathEntry>,
+    _priv: (),
+}
+
+impl SelfCheck {
+    /// Whether all checks succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.version.is_ok() && self.pc_path.is_ok() && self.builtin_package.is_ok()
+    }
+}
+
+impl fmt::Display for SelfCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        writeln!(f, "executable: {}", self.executable)?;
+        match self.version {
+            Ok(ref version) => writeln!(f, "version: {}", version)?,
+            Err(ref err) => writeln!(f, "version: error: {}", err)?,
+        }
+        match self.pc_path {
+            Ok(ref pc_path) => writeln!(f, "pc_path: {}", pc_path)?,
+            Err(ref err) => writeln!(f, "pc_path: error: {}", err)?,
+        }
+        match self.builtin_package {
+            Ok(()) => write!(f, "builtin package: ok")?,
+            Err(ref err) => write!(f, "builtin package: error: {}", err)?,
+        }
+        for entry in &self.search_path {
+            write!(f, "\nsearch path: {}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run a series of sanity checks against the `pkg-config` tool that probes
+/// would use: query its version, its default search path, and look up its
+/// builtin `pkg-config` package.
+///
+/// The report's `Display` output is meant for CI preflight steps and bug
+/// reports.
+pub fn self_check() -> SelfCheck {
+    let cfg = Config::new();
+    let mut cmd = cfg.base_command("pkg-config", &["--version"]);
+    let version = run(&mut cmd, &cfg).and_then(|out| Ok(utf8(out, &cmd)?.trim().to_owned()));
+    let pc_path = get_variable("pkg-config", "pc_path");
+    let builtin_package = run(&mut cfg.command("pkg-config", &["--exists"]), &cfg).map(|_| ());
+    let mut search_path = Vec::new();
+    if let Ok(path) = cfg.targetted_env_var("PKG_CONFIG_PATH") {
+        search_path.extend(validate_search_path(OsStr::new(&path)));
+    }
+    if let Ok(ref pc_path) = pc_path {
+        search_path.extend(validate_search_path(OsStr::new(pc_path)));
+    }
+    SelfCheck {
+        executable: cfg.executable(),
+        version,
+        pc_path,
+        builtin_package,
+        search_path,
+        _priv: (),
+    }
+}
+
+impl Config {
+    /// Creates a new set of configuration options which are all initially set
+    /// to "blank".
+    pub fn new() -> Config {
+        Config {
+            statik: None,
+            statik_blacklist: vec![],
+            statik_whitelist: vec![],
+            whole_archive: vec![],
+            static_archive_patterns: vec![],
+            atleast_version: None,
+            at_most_version: None,
+            min_pkg_config_version: None,
+            fragment_filter: None,
+            pure: false,
+            target: None,
+            host: None,
+            extra_args: vec![],
+            query_args: vec![],
+            system_libs: SystemLibs::ForceOn,
+            cargo_metadata: true,
+            defer_metadata: false,
+            env_metadata: false,
+            rerun_on_system_packages: false,
+            libpkgconf: cfg!(feature = "libpkgconf"),
+            #[cfg(feature = "vendored-resolver")]
+            vendored_resolver: true,
+            standalone: false,
+            trace: None,
+            or_else: None,
+            on_command: None,
+            on_output: None,
+            variable_assertions: Vec::new(),
+            lock_file: None,
+            negative_cache: None,
+            root: None,
+            sysroot: None,
+            disable_uninstalled: None,
+            top_build_dir: None,
+            define_prefix: None,
+            search_mode: SearchMode::Default,
+            requires_policy: RequiresPolicy::EmitAll,
+            system_library_path: None,
+            system_include_path: None,
+            fallback_paths: Vec::new(),
+            extra_include_paths: Vec::new(),
+            extra_link_paths: Vec::new(),
+            drop_system_link_paths: false,
+            verify_archives: false,
+            warnings: false,
+            forward_stderr: false,
+            standard_variables: false,
+            cfg_defines: Vec::new(),
+            validate_paths: PathPolicy::Ignore,
+            expected_kind: None,
+            try_link: false,
+            retries: 3,
+            macos_package_managers: false,
+            normalize_windows_paths: false,
+            ignore_statik_env: false,
+            hermetic: false,
+        }
+    }
+
+    /// Indicate whether the `--static` flag should be passed.
+    ///
+    /// This will override the inference from environment variables described in
+    /// the crate documentation.
+    pub fn statik<S>(&mut self, statik: S) -> &mut Config
+        where S: Into<Statik>
+    {
+        self.statik = Some(statik.into());
+        self
+    }
+
+    /// Whether the environment variables described in the crate
+    /// documentation, e.g. `FOO_STATIC` or `PKG_CONFIG_ALL_STATIC`, choose
+    /// the mode when `statik` isn't called. They do by default.
+    ///
+    /// Disabling this makes packages link dynamically whatever the
+    /// environment says, e.g. for licensing reasons; `statik_from_env` still
+    /// tells what it would have chosen.
+    pub fn statik_env_override(&mut self, enabled: bool) -> &mut Config {
+        self.ignore_statik_env = !enabled;
+        self
+    }
+
+    pub fn statik_blacklist<S>(&mut self, blacklist: Vec<S>) -> &mut Config
+        where String: From<S>, S: Clone
+    {
         self.statik_blacklist.extend(
             blacklist
                 .iter()
@@ -318,12 +1658,151 @@ impl Config {
         self.statik_blacklist.iter().any(|s| s == &val)
     }
 
+    /// Restrict static linking to the libraries named in `whitelist`.
+    ///
+    /// Once a whitelist is configured, the libraries on it are linked
+    /// statically whatever the `statik` mode is, and every other library is
+    /// linked dynamically. Whitelisted libraries are still checked for a
+    /// static archive in the link paths unless the mode is `Statik::Force`.
+    /// The blacklist takes precedence over the whitelist.
+    pub fn statik_whitelist<S>(&mut self, whitelist: Vec<S>) -> &mut Config
+        where String: From<S>, S: Clone
+    {
+        self.statik_whitelist.extend(
+            whitelist
+                .iter()
+                .map(|s| String::from(s.clone()))
+        );
+        self
+    }
+
+    pub fn statik_whitelist_contains<S>(&self, val: S) -> bool
+        where String: PartialEq<S>
+    {
+        self.statik_whitelist.iter().any(|s| s == &val)
+    }
+
+    /// Link the libraries named in `names` with the `+whole-archive` modifier
+    /// when they are linked statically, so that objects nothing refers to
+    /// directly (e.g. plugins registering themselves from constructors) are
+    /// kept.
+    ///
+    /// The modifier requires Rust 1.61; with older compilers these libraries
+    /// are linked as plain static libraries.
+    pub fn whole_archive<S>(&mut self, names: Vec<S>) -> &mut Config
+        where String: From<S>, S: Clone
+    {
+        self.whole_archive.extend(
+            names
+                .iter()
+                .map(|s| String::from(s.clone()))
+        );
+        self
+    }
+
+    /// Add a file name pattern recognized as a static archive when deciding
+    /// whether a library can be linked statically, with `{}` standing for the
+    /// library name (e.g. `"{}_static.a"`).
+    ///
+    /// `lib{}.a` is always recognized, as is `{}.a` when targeting MinGW.
+    /// Names ending in `.dll.a` are MinGW import libraries and never count as
+    /// static archives.
+    pub fn static_archive_pattern(&mut self, pattern: &str) -> &mut Config {
+        self.static_archive_patterns.push(pattern.to_string());
+        self
+    }
+
     /// Indicate that the library must be at least version `vers`.
     pub fn atleast_version(&mut self, vers: &str) -> &mut Config {
         self.atleast_version = Some(vers.to_string());
         self
     }
 
+    /// Indicate that the library must be at most version `vers`.
+    pub fn at_most_version(&mut self, vers: &str) -> &mut Config {
+        self.at_most_version = Some(vers.to_string());
+        self
+    }
+
+    /// Require the `pkg-config` executable to be at least version `vers`,
+    /// e.g. `"0.29"` for options older ones don't know.
+    ///
+    /// Its `--version` is only asked once per executable. Probes resolved
+    /// in-process aren't checked.
+    pub fn min_pkg_config_version(&mut self, vers: &str) -> &mut Config {
+        self.min_pkg_config_version = Some(vers.to_string());
+        self
+    }
+
+    /// Require the highest of the minimum versions of `features` whose
+    /// cargo feature is enabled, e.g. `&[("v3_22", "3.22"), ("v3_24", "3.24")]`
+    /// for a crate exposing newer APIs of the library behind features.
+    ///
+    /// Returns the feature that raised the minimum version, if any, e.g. to
+    /// mention it when the probe fails. A higher version already set with
+    /// `atleast_version` is kept.
+    pub fn version_from_features(&mut self, features: &[(&str, &str)]) -> Option<String> {
+        let mut chosen: Option<(&str, &str)> = None;
+        for &(feature, version) in features {
+            if !feature_enabled(feature) {
+                continue;
+            }
+            match chosen.map(|c| c.1).or(self.atleast_version.as_deref()) {
+                Some(minimum) if compare_versions(version, minimum) != cmp::Ordering::Greater => {}
+                _ => chosen = Some((feature, version)),
+            }
+        }
+        let (feature, version) = chosen?;
+        self.atleast_version = Some(version.to_string());
+        Some(feature.to_string())
+    }
+
+    /// Only keep the flags of the given fragment types in the `--libs` and
+    /// `--cflags` output, e.g. `"lL"` for `-l` and `-L` flags only.
+    ///
+    /// This maps to pkgconf's `--fragment-filter` option so that the tool's
+    /// own classification of the flags is used. With other `pkg-config`
+    /// implementations the output is filtered by this crate instead, keeping
+    /// each flag whose first letter after the `-` is one of `types`.
+    pub fn fragment_filter(&mut self, types: &str) -> &mut Config {
+        self.fragment_filter = Some(types.to_string());
+        self
+    }
+
+    /// Indicate whether static probes should use pkgconf's `--pure` mode.
+    /// Defaults to `false`.
+    ///
+    /// In this mode the dependency graph of a static probe is computed as for
+    /// dynamic linking, leaving out `Libs.private` and `Requires.private`.
+    /// freedesktop.org's `pkg-config` has no such mode, so `--static` isn't
+    /// passed to it at all, which yields the same flags.
+    ///
+    /// Either way, repeated `-l` flags are then collapsed into their last
+    /// occurrence, as pkgconf does for static links, so that the same build
+    /// script links identically with both tools.
+    pub fn pure(&mut self, pure: bool) -> &mut Config {
+        self.pure = pure;
+        self
+    }
+
+    /// Probe for the target `triple` instead of the `TARGET` cargo sets for
+    /// build scripts.
+    ///
+    /// This selects the target-scoped environment variables (e.g.
+    /// `PKG_CONFIG_PATH_<triple>`) and decides whether the probe is a cross
+    /// compile. Tools running outside of build scripts should set `host` as
+    /// well, as the target is otherwise assumed to differ from the host.
+    pub fn target(&mut self, triple: &str) -> &mut Config {
+        self.target = Some(triple.to_string());
+        self
+    }
+
+    /// Override the `HOST` cargo sets for build scripts, see `target`.
+    pub fn host(&mut self, triple: &str) -> &mut Config {
+        self.host = Some(triple.to_string());
+        self
+    }
+
     /// Add an argument to pass to pkg-config.
     ///
     /// It's placed after all of the arguments generated by this library.
@@ -332,6 +1811,17 @@ impl Config {
         self
     }
 
+    /// Add an argument to pass to pkg-config for the `query` invocations
+    /// only, e.g. an option older versions don't accept with `--modversion`.
+    ///
+    /// It's placed after the arguments added with `arg`. The version and
+    /// flags of a package are then queried separately, which otherwise the
+    /// reference `pkg-config` answers in a single invocation.
+    pub fn arg_for<S: AsRef<OsStr>>(&mut self, query: Query, arg: S) -> &mut Config {
+        self.query_args.push((query, arg.as_ref().to_os_string()));
+        self
+    }
+
     /// Define whether metadata should be emitted for cargo allowing it to
     /// automatically link the binary. Defaults to `true`.
     pub fn cargo_metadata(&mut self, cargo_metadata: bool) -> &mut Config {
@@ -339,246 +1829,3083 @@ impl Config {
         self
     }
 
+    /// Define whether cargo metadata should be collected by a process-wide
+    /// emitter instead of being printed right away. Defaults to `false`.
+    ///
+    /// Deferred metadata is deduplicated across all probes of the build
+    /// script, keeping the order in which each directive was first seen, and
+    /// is printed by `emit_metadata`. This avoids repeating the link
+    /// directives of dependencies shared by several probed packages.
+    pub fn defer_metadata(&mut self, defer_metadata: bool) -> &mut Config {
+        self.defer_metadata = defer_metadata;
+        self
+    }
+
     /// Define whether metadata should be emitted for cargo allowing to
     /// automatically rebuild when environment variables change. Defaults to
     /// `false`.
+    ///
+    /// The `rerun-if` directives of a probe are printed sorted, before its
+    /// link directives, including when it fails.
     pub fn env_metadata(&mut self, env_metadata: bool) -> &mut Config {
         self.env_metadata = env_metadata;
         self
     }
 
+    /// Define whether metadata should be emitted for cargo allowing to
+    /// automatically rebuild when system packages are installed, upgraded or
+    /// removed. Defaults to `false`.
+    ///
+    /// This emits `rerun-if-changed` for the database files of the package
+    /// managers found on the system (dpkg, rpm, pacman, apk, MacPorts and
+    /// Nix) and the `.pc` files linked into the Homebrew prefix, so that
+    /// cached build script results are invalidated when the system libraries
+    /// change even if the build files didn't.
+    ///
+    /// Note that once a build script emits any `rerun-if` directive, cargo no
+    /// longer reruns it when the package's own files change unless those are
+    /// listed as well.
+    pub fn rerun_on_system_packages(&mut self, rerun: bool) -> &mut Config {
+        self.rerun_on_system_packages = rerun;
+        self
+    }
+
     /// Enable or disable the `PKG_CONFIG_ALLOW_SYSTEM_LIBS` environment
     /// variable.
     ///
-    /// This env var is enabled by default.
+    /// This env var is enabled by default. Disabling it leaves it as set in
+    /// the environment, like `system_libs(SystemLibs::Inherit)`.
     pub fn print_system_libs(&mut self, print: bool) -> &mut Config {
-        self.print_system_libs = print;
+        self.system_libs(if print { SystemLibs::ForceOn } else { SystemLibs::Inherit })
+    }
+
+    /// Choose whether `pkg-config` keeps `-L` flags for the system library
+    /// directories, e.g. `-L/usr/lib`, by setting or unsetting
+    /// `PKG_CONFIG_ALLOW_SYSTEM_LIBS`.
+    ///
+    /// The default is `SystemLibs::ForceOn`, which sets it to `1` whatever
+    /// the environment says, and `SystemLibs::ForceOff` always unsets it.
+    /// Only with `SystemLibs::Inherit` is the environment read: a value set
+    /// for the target specifically, e.g. `PKG_CONFIG_ALLOW_SYSTEM_LIBS_<target>`
+    /// or `TARGET_PKG_CONFIG_ALLOW_SYSTEM_LIBS`, takes precedence, `0` or
+    /// `false` unset it, and any other value is passed on as is (and tracked
+    /// with `rerun-if-env-changed` if `env_metadata` is enabled). See
+    /// `effective_system_libs` for the outcome.
+    pub fn system_libs(&mut self, system_libs: SystemLibs) -> &mut Config {
+        self.system_libs = system_libs;
         self
     }
 
-    /// Deprecated in favor fo the `probe` function
-    #[doc(hidden)]
-    pub fn find(&self, name: &str) -> Result<Library, String> {
-        self.probe(name).map_err(|e| e.to_string())
+    /// The value of `PKG_CONFIG_ALLOW_SYSTEM_LIBS` probes run `pkg-config`
+    /// with, `None` if they unset it, as decided by `system_libs` and the
+    /// environment.
+    pub fn effective_system_libs(&self) -> Option<String> {
+        match self.system_libs {
+            SystemLibs::ForceOn => Some("1".to_string()),
+            SystemLibs::ForceOff => None,
+            SystemLibs::Inherit => match self.targetted_env_var("PKG_CONFIG_ALLOW_SYSTEM_LIBS") {
+                Ok(ref value) if value == "0" || value.eq_ignore_ascii_case("false") => None,
+                Ok(value) => Some(value),
+                Err(_) => None,
+            },
+        }
     }
 
-    /// Run `pkg-config` to find the library `name`.
+    /// Choose how `PKG_CONFIG_PATH` and `PKG_CONFIG_LIBDIR` (or their
+    /// target-specific variants) make up the search path.
     ///
-    /// This will use all configuration previously set to specify how
-    /// `pkg-config` is run.
-    pub fn probe(&self, name: &str) -> Result<Library, Error> {
-        let abort_var_name = format!("{}_NO_PKG_CONFIG", envify(name));
-        if self.env_var_os(&abort_var_name).is_some() {
-            return Err(Error::EnvNoPkgConfig(abort_var_name))
-        } else if !target_supported() {
-            return Err(Error::CrossCompilation);
-        }
+    /// A `cargo:warning` is printed when the two variables are set in a way
+    /// that likely doesn't do what was intended, e.g. when a directory is in
+    /// both or a variable is set but ignored.
+    pub fn search_mode(&mut self, mode: SearchMode) -> &mut Config {
+        self.search_mode = mode;
+        self
+    }
 
-        let mut library = Library::new();
+    /// Choose which libraries of the packages in `Requires` (and
+    /// `Requires.private` when linking statically) this probe links, so
+    /// that the `-sys` crates of a dependency tree don't each link the
+    /// libraries of the packages below them again.
+    ///
+    /// With `RequiresPolicy::DeferToDeps`, a required package is only left
+    /// to its `-sys` crate if that crate is a dependency, as far as cargo
+    /// tells: it sets `DEP_<links>_*` variables for dependencies printing
+    /// any metadata, e.g. with `Library::emit_dep_paths`. Libraries left out
+    /// are marked `Linkage::Skipped`.
+    ///
+    /// The requires are listed by running `pkg-config`, so probes resolved
+    /// in-process link everything.
+    pub fn requires_policy(&mut self, policy: RequiresPolicy) -> &mut Config {
+        self.requires_policy = policy;
+        self
+    }
+
+    /// Find packages in the sysroot `dir` of the target, overriding
+    /// `PKG_CONFIG_SYSROOT_DIR` (or its target-specific variants).
+    ///
+    /// Unless `PKG_CONFIG_LIBDIR` is set, the `pkgconfig` directories of
+    /// `dir` replace the default search path, including multiarch ones such as
+    /// `usr/lib/aarch64-linux-gnu/pkgconfig`, and `dir` is prefixed to the
+    /// absolute `-I` and `-L` directories found there. Building for another
+    /// target is allowed, as with `PKG_CONFIG_SYSROOT_DIR`.
+    ///
+    /// With the `vendored-resolver` feature, packages are resolved by it
+    /// directly, so that neither the host nor the sysroot need `pkg-config`.
+    pub fn sysroot<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Config {
+        self.sysroot = Some(dir.into());
+        self
+    }
 
-        let output = run(self.command(name, &["--libs", "--cflags"]))?;
-        library.parse_libs_cflags(name, &output, self);
+    /// Choose whether `<name>-uninstalled.pc` files, which describe packages
+    /// in their build tree, are ignored rather than preferred over
+    /// `<name>.pc`, overriding `PKG_CONFIG_DISABLE_UNINSTALLED`.
+    pub fn disable_uninstalled(&mut self, disable: bool) -> &mut Config {
+        self.disable_uninstalled = Some(disable);
+        self
+    }
 
-        let output = run(self.command(name, &["--modversion"]))?;
-        library.parse_modversion(str::from_utf8(&output).unwrap());
+    /// Set the `pc_top_builddir` variable of `-uninstalled.pc` files to
+    /// `dir`, overriding `PKG_CONFIG_TOP_BUILD_DIR`. It's `$(top_builddir)`
+    /// if neither is set, for makefiles to expand.
+    pub fn top_build_dir<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Config {
+        self.top_build_dir = Some(dir.into());
+        self
+    }
 
-        Ok(library)
+    /// Choose whether the `prefix` variable of a `.pc` file in a
+    /// `<prefix>/lib/pkgconfig` (or `share/pkgconfig`) directory is
+    /// redefined as that `<prefix>`, like `--define-prefix` does, so that
+    /// relocatable SDKs work wherever they're unpacked. `pkg-config` only
+    /// does so by default on Windows.
+    ///
+    /// The flag is only passed to `pkg-config` versions that support it.
+    /// With the `vendored-resolver` feature, packages are resolved by it
+    /// instead for older versions, otherwise the setting is ignored.
+    pub fn define_prefix(&mut self, define: bool) -> &mut Config {
+        self.define_prefix = Some(define);
+        self
     }
 
-    /// Deprecated in favor of the top level `get_variable` function
-    #[doc(hidden)]
-    pub fn get_variable(package: &str, variable: &str) -> Result<String, String> {
-        get_variable(package, variable).map_err(|e| e.to_string())
+    /// Set the default library search path of the linker, overriding
+    /// `PKG_CONFIG_SYSTEM_LIBRARY_PATH` (or its target-specific variants).
+    ///
+    /// `-L` flags for these directories aren't added to `link_paths` nor
+    /// printed as cargo metadata, as the linker searches them anyway. They
+    /// are still used to find static archives.
+    pub fn system_library_path<I, P>(&mut self, paths: I) -> &mut Config
+        where I: IntoIterator<Item = P>, P: Into<PathBuf>
+    {
+        self.system_library_path = Some(paths.into_iter().map(Into::into).collect());
+        self
     }
 
-    fn targetted_env_var(&self, var_base: &str) -> Result<String, env::VarError> {
-        if let Ok(target) = env::var("TARGET") {
-            let host = env::var("HOST")?;
-            let kind = if host == target { "HOST" } else { "TARGET" };
-            let target_u = target.replace("-", "_");
+    /// Set the default include path of the compiler, overriding
+    /// `PKG_CONFIG_SYSTEM_INCLUDE_PATH` (or its target-specific variants).
+    ///
+    /// `-I` flags for these directories aren't added to `include_paths`.
+    pub fn system_include_path<I, P>(&mut self, paths: I) -> &mut Config
+        where I: IntoIterator<Item = P>, P: Into<PathBuf>
+    {
+        self.system_include_path = Some(paths.into_iter().map(Into::into).collect());
+        self
+    }
 
-            self.env_var(&format!("{}_{}", var_base, target))
-                .or_else(|_| self.env_var(&format!("{}_{}", var_base, target_u)))
-                .or_else(|_| self.env_var(&format!("{}_{}", kind, var_base)))
-                .or_else(|_| self.env_var(var_base))
-        } else {
-            self.env_var(var_base)
+    /// Check that the static archives found for libraries that may be linked
+    /// statically are real archives with an index of the symbols they define,
+    /// linking the libraries dynamically otherwise.
+    ///
+    /// This catches empty and stub archives that would only fail at link
+    /// time. It doesn't apply to `Statik::Force`, which never looks for the
+    /// archives.
+    pub fn verify_archives(&mut self, verify: bool) -> &mut Config {
+        self.verify_archives = verify;
+        self
+    }
+
+    /// Check the results of probes for signs of a broken setup, printing a
+    /// `cargo:warning=` line for each problem found.
+    ///
+    /// This reports link paths that don't exist, packages without any flags
+    /// and shared libraries built for another architecture than the target,
+    /// as far as their ELF or Mach-O header tells. Such results otherwise
+    /// only fail later, with confusing linker errors.
+    ///
+    /// When cross compiling, libraries built for the host are reported even
+    /// without `warnings`, along with how to configure `PKG_CONFIG_LIBDIR`
+    /// for the target.
+    pub fn warnings(&mut self, warnings: bool) -> &mut Config {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Print what `pkg-config` prints on stderr while finding a package,
+    /// which is otherwise only kept in `Library::warnings`, as
+    /// `cargo:warning=` lines. Defaults to `false`.
+    pub fn forward_stderr(&mut self, forward: bool) -> &mut Config {
+        self.forward_stderr = forward;
+        self
+    }
+
+    /// Also query the `prefix`, `exec_prefix`, `libdir` and `includedir`
+    /// variables of packages found, setting the fields of the same name of
+    /// `Library`.
+    ///
+    /// Each variable takes another `pkg-config` invocation, recorded in
+    /// `Library::probe_commands`. Variables a package doesn't define are left
+    /// as `None`, and so are all of them for packages resolved in-process.
+    pub fn standard_variables(&mut self, enabled: bool) -> &mut Config {
+        self.standard_variables = enabled;
+        self
+    }
+
+    /// Query the variable `variable` of packages found, failing the probe
+    /// with `Error::VariableAssertion` if it isn't defined or `check`
+    /// rejects its value, e.g. `|dir| !dir.is_empty()` for a `plugindir`
+    /// the build script needs. Values that pass are kept in
+    /// `Library::variables`.
+    ///
+    /// Each variable takes another `pkg-config` invocation, recorded in
+    /// `Library::probe_commands`. Packages resolved in-process aren't
+    /// checked.
+    pub fn assert_variable<F>(&mut self, variable: &str, check: F) -> &mut Config
+        where F: Fn(&str) -> bool + Send + Sync + 'static
+    {
+        self.variable_assertions.push((variable.to_string(), Arc::new(check)));
+        self
+    }
+
+    /// Print `cargo:rustc-cfg=<define>` for each of `defines` the package
+    /// defines with `-D`, so that e.g. `#[cfg(HAVE_FOO)]` follows what the
+    /// library was built with.
+    ///
+    /// A `rustc-check-cfg` line is printed for each of `defines` too, so that
+    /// those left unset don't trigger `unexpected_cfgs` warnings. Calling this
+    /// again adds more defines.
+    pub fn cfg_from_defines(&mut self, defines: &[&str]) -> &mut Config {
+        self.cfg_defines.extend(defines.iter().map(|d| d.to_string()));
+        self
+    }
+
+    /// Fail probes with `Error::UnexpectedKind` unless the package resolves
+    /// to the shape of result `kind`, checked for what that kind needs:
+    ///
+    /// * `LibraryKind::Libs` - at least one `-l` library.
+    /// * `LibraryKind::FrameworksOnly` - frameworks but no `-l` library, each
+    ///   found in the `-F` directories, the SDK named by `SDKROOT` or the
+    ///   standard framework directories.
+    /// * `LibraryKind::HeaderOnly` - no library nor framework, and `-I`
+    ///   directories that exist.
+    pub fn expect_kind(&mut self, kind: LibraryKind) -> &mut Config {
+        self.expected_kind = Some(kind);
+        self
+    }
+
+    /// After probing, compile and link a trivial C program against the flags
+    /// found, failing with `Error::LinkFailed` if that fails. Broken `.pc`
+    /// files then fail the probe, rather than the final link of the crate,
+    /// with a much more confusing error. Defaults to `false`.
+    ///
+    /// The compiler is `CC` (or its target-specific variants), or `cc` when
+    /// not cross compiling. Without one, and for MSVC targets, whose
+    /// compiler takes other arguments, the check is skipped.
+    pub fn try_link(&mut self, try_link: bool) -> &mut Config {
+        self.try_link = try_link;
+        self
+    }
+
+    /// Retry running `pkg-config` up to `retries` times, with a growing
+    /// delay, when it fails to start with an error that's usually transient
+    /// on busy machines, like `Text file busy` or an interrupted system call.
+    /// The final failure is `Error::SpawnFailed`. Defaults to 3.
+    pub fn retries(&mut self, retries: u32) -> &mut Config {
+        self.retries = retries;
+        self
+    }
+
+    /// Check that the `-L` and `-F` directories of packages found exist,
+    /// warning about or failing on those that don't. Defaults to
+    /// `PathPolicy::Ignore`.
+    ///
+    /// Missing directories usually come from `.pc` files with a stale
+    /// prefix, e.g. after moving a Homebrew or MSYS2 installation.
+    pub fn validate_paths(&mut self, policy: PathPolicy) -> &mut Config {
+        self.validate_paths = policy;
+        self
+    }
+
+    /// Search `paths` for `.pc` files too, after the directories of
+    /// `PKG_CONFIG_PATH` and before those of `PKG_CONFIG_LIBDIR` or the
+    /// default search path, e.g. for the `.pc` files of a prebuilt SDK.
+    ///
+    /// Packages in `PKG_CONFIG_PATH` thus take precedence, so that users can
+    /// still point builds at their own copies. Calling this again adds more
+    /// directories after those given so far. Adding `.pc` files to the
+    /// directories makes cargo rerun the build script. Like `PKG_CONFIG_PATH`,
+    /// they're ignored with `root` and `SearchMode::LibdirOnly`.
+    pub fn fallback_paths<I, P>(&mut self, paths: I) -> &mut Config
+        where I: IntoIterator<Item = P>, P: Into<PathBuf>
+    {
+        self.fallback_paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add `dir` to the `include_paths` of packages found, after their own,
+    /// e.g. for headers generated into `OUT_DIR`, so that `compile_args`,
+    /// `clang_args` and the like give the complete set of flags.
+    pub fn extra_include_path<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Config {
+        self.extra_include_paths.push(dir.into());
+        self
+    }
+
+    /// Add `dir` to the `link_paths` of packages found, after their own,
+    /// which prints it as `cargo:rustc-link-search` as well.
+    pub fn extra_link_path<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Config {
+        self.extra_link_paths.push(dir.into());
+        self
+    }
+
+    /// When building for macOS, also search the `.pc` files of Homebrew and
+    /// MacPorts, after those of `PKG_CONFIG_PATH`.
+    ///
+    /// The Homebrew prefix is taken from `HOMEBREW_PREFIX`, else from `brew
+    /// --prefix`, else it's `/opt/homebrew` or `/usr/local`, whichever has
+    /// Homebrew installed. Besides the prefix itself, the `.pc` files of
+    /// keg-only formulas such as `openssl@3` or `libffi` are searched, which
+    /// `pkg-config` doesn't find on its own. MacPorts is searched in
+    /// `/opt/local`.
+    pub fn macos_package_managers(&mut self, enabled: bool) -> &mut Config {
+        self.macos_package_managers = enabled;
+        self
+    }
+
+    /// When building for Windows, normalize the paths of libraries found and
+    /// those printed in cargo metadata: only backslashes as separators, no
+    /// `\\?\` prefix and uppercase drive letters.
+    ///
+    /// `.pc` files often mix separators, which confuses some tools reading
+    /// the metadata.
+    pub fn normalize_windows_paths(&mut self, enabled: bool) -> &mut Config {
+        self.normalize_windows_paths = enabled;
+        self
+    }
+
+    /// Leave the default library directories of the C toolchain, e.g.
+    /// `/usr/lib`, out of the `rustc-link-search` metadata of libraries that
+    /// aren't linked statically.
+    ///
+    /// The linker searches them anyway, and passing them to rustc can make it
+    /// pick up libraries from there instead of from its own sysroot. The
+    /// directories are those listed by `$CC -print-search-dirs`, or the usual
+    /// ones for the target if that fails, so this runs the C compiler once.
+    /// Disabled by default.
+    pub fn drop_system_link_paths(&mut self, drop: bool) -> &mut Config {
+        self.drop_system_link_paths = drop;
+        self
+    }
+
+    /// Use this crate outside of a build script, e.g. in a command line tool.
+    ///
+    /// In standalone mode nothing is ever printed (no cargo metadata, deferred
+    /// or not) and the variables cargo sets for build scripts, `TARGET`,
+    /// `HOST` and `RUSTC`, are ignored. Use `target` and `host` to probe for
+    /// another triple. Probes only return their results.
+    pub fn standalone(&mut self, standalone: bool) -> &mut Config {
+        self.standalone = standalone;
+        self
+    }
+
+    /// Make probes depend only on the configuration and the `.pc` files it
+    /// points at, e.g. checked-in ones added with `fallback_paths`, for
+    /// reproducible CI builds.
+    ///
+    /// The environment variables described in the crate documentation are
+    /// all ignored, so that e.g. `PKG_CONFIG_PATH` or `FOO_STATIC` don't
+    /// change the results, and no `rerun-if-env-changed` lines are printed
+    /// for them. Only the toolchain variables set by cargo, `TARGET`, `HOST`
+    /// and `CC`, are still read, and `PATH` to find the C compiler of
+    /// `try_link`. The default search path is disabled, as with
+    /// `SearchMode::PathThenLibdir`, and so are the system library and
+    /// include paths of `pkg-config` unless given with `system_library_path`
+    /// and `system_include_path`, the macOS package managers and the vcpkg
+    /// and CMake fallbacks.
+    ///
+    /// Packages are resolved by the vendored resolver rather than by
+    /// whichever `pkg-config` is installed, so this takes the
+    /// `vendored-resolver` feature: without it, or with
+    /// `vendored_resolver(false)`, `probe` fails with `Error::NotHermetic`,
+    /// and so do `probe_headers` and `probe_tool_package`, which always run
+    /// `pkg-config`. The flags keep the order of the `.pc` files, so the
+    /// same inputs print the same cargo metadata in the same order on every
+    /// machine, and the `fingerprint` no longer covers the environment, so
+    /// it is the same everywhere too.
+    pub fn hermetic(&mut self) -> &mut Config {
+        self.hermetic = true;
+        self.search_mode = SearchMode::PathThenLibdir;
+        self.macos_package_managers = false;
+        self
+    }
+
+    /// Report every command run, its output, the flags parsed for each package
+    /// and the cargo metadata printed to `f`.
+    ///
+    /// Setting the `PKG_CONFIG_TRACE` environment variable prints the same
+    /// events to stderr, which helps diagnosing which `.pc` files and search
+    /// paths a probe ends up using.
+    pub fn trace<F>(&mut self, f: F) -> &mut Config
+        where F: Fn(&Trace) + Send + Sync + 'static
+    {
+        self.trace = Some(Arc::new(f));
+        self
+    }
+
+    /// Check the versions found against the lock file at `path`, see the
+    /// `lock` module.
+    ///
+    /// Probing a package listed in the lock file fails with
+    /// `Error::LockMismatch` if its major or minor version differs from the
+    /// locked one. Packages missing from the lock file aren't checked.
+    pub fn enforce_lock<P: AsRef<Path>>(&mut self, path: P) -> &mut Config {
+        self.lock_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Remember the packages found missing in the file at `path`, for `ttl`,
+    /// so that later builds don't run `pkg-config` for them again.
+    ///
+    /// Without it, they're still remembered for the rest of the process, so
+    /// that repeated probes of optional packages fail fast. Both are keyed by
+    /// the `fingerprint` of the configuration. A package found in the cache
+    /// file fails with `Error::NotFoundCached`. Since the file is written by the
+    /// probes, it's not watched with `cargo:rerun-if-changed`.
+    pub fn negative_cache<P: AsRef<Path>>(&mut self, path: P, ttl: Duration) -> &mut Config {
+        self.negative_cache = Some((path.as_ref().to_path_buf(), ttl));
+        self
+    }
+
+    /// Call `f` with the error when a probe fails, e.g. to build a vendored
+    /// copy of the library instead.
+    ///
+    /// If `f` returns a `Library`, typically made with `Library::from_parts`,
+    /// the probe succeeds with it and its cargo metadata is printed as if
+    /// `pkg-config` had found it.
+    pub fn or_else<F>(&mut self, f: F) -> &mut Config
+        where F: Fn(Error) -> Result<Library, Error> + Send + Sync + 'static
+    {
+        self.or_else = Some(Arc::new(f));
+        self
+    }
+
+    /// Call `f` with every `pkg-config` command before it's run, e.g. to log
+    /// it or to add an argument.
+    ///
+    /// Returning an error vetoes the command: it isn't run and the probe
+    /// fails with `Error::Vetoed`, with the reason given.
+    pub fn on_command<F>(&mut self, f: F) -> &mut Config
+        where F: Fn(&mut Command) -> Result<(), String> + Send + Sync + 'static
+    {
+        self.on_command = Some(Arc::new(f));
+        self
+    }
+
+    /// Call `f` with the raw output of every `pkg-config` command run, before
+    /// its status is checked and its output parsed.
+    ///
+    /// `f` may change the output, e.g. to drop a flag the toolchain doesn't
+    /// understand.
+    pub fn on_output<F>(&mut self, f: F) -> &mut Config
+        where F: Fn(&mut Output) + Send + Sync + 'static
+    {
+        self.on_output = Some(Arc::new(f));
+        self
+    }
+
+    /// Resolve packages in-process with libpkgconf instead of running the
+    /// `pkg-config` executable.
+    ///
+    /// This is the default when the `libpkgconf` feature is enabled. Only
+    /// `probe` is affected; the environment variables the executable would
+    /// read are honored as usual.
+    #[cfg(feature = "libpkgconf")]
+    pub fn libpkgconf(&mut self, enabled: bool) -> &mut Config {
+        self.libpkgconf = enabled;
+        self
+    }
+
+    /// Fall back to the vendored pure-Rust resolver when the `pkg-config`
+    /// executable can't be found.
+    ///
+    /// This is the default when the `vendored-resolver` feature is enabled,
+    /// unless `PKG_CONFIG_NO_VENDORED` is set. Only `probe` is affected.
+    #[cfg(feature = "vendored-resolver")]
+    pub fn vendored_resolver(&mut self, enabled: bool) -> &mut Config {
+        self.vendored_resolver = enabled;
+        self
+    }
+
+    /// A hash of the settings and environment variables that affect probes,
+    /// as 16 hex digits.
+    ///
+    /// The hash is stable across releases of this crate and only changes
+    /// with the configuration, so it can key cached probe results. Callbacks
+    /// set with `trace`, `or_else`, `on_command` and `on_output` aren't part
+    /// of it.
+    pub fn fingerprint(&self) -> String {
+        fn opt(s: &Option<String>) -> String {
+            match *s {
+                Some(ref s) => format!("+{}", s),
+                None => "-".to_string(),
+            }
+        }
+        fn list<S: AsRef<str>>(items: &[S]) -> String {
+            items.iter().map(|s| format!("{}\0", s.as_ref())).collect()
+        }
+
+        let statik = match self.statik {
+            Some(ref statik) => statik.to_string(),
+            None => "-".to_string(),
+        };
+        let extra_args = self.extra_args.iter()
+                                        .map(|a| a.to_string_lossy().into_owned())
+                                        .chain(self.query_args.iter().map(|&(query, ref a)| {
+                                            format!("{:?}:{}", query, a.to_string_lossy())
+                                        }))
+                                        .collect::<Vec<_>>();
+        let mut settings = vec![
+            "v1".to_string(),
+            statik,
+            list(&self.statik_blacklist),
+            list(&self.statik_whitelist),
+            list(&self.whole_archive),
+            list(&self.static_archive_patterns),
+            opt(&self.atleast_version),
+            opt(&self.at_most_version),
+            opt(&self.fragment_filter),
+            opt(&self.target),
+            opt(&self.host),
+            list(&extra_args),
+            opt(&self.lock_file.as_ref().map(|p| p.display().to_string())),
+        ];
+        if let Some(ref root) = self.root {
+            settings.push(root.display().to_string());
+        }
+        if let Some(ref sysroot) = self.sysroot {
+            settings.push(format!("sysroot={}", sysroot.display()));
+        }
+        if let Some(disable) = self.disable_uninstalled {
+            settings.push(format!("disable_uninstalled={}", disable));
+        }
+        if let Some(ref dir) = self.top_build_dir {
+            settings.push(format!("top_build_dir={}", dir.display()));
+        }
+        if let Some(define) = self.define_prefix {
+            settings.push(format!("define_prefix={}", define));
+        }
+        settings.push(format!("{:?}", self.system_libs));
+        if self.validate_paths != PathPolicy::Ignore {
+            settings.push(format!("{:?}", self.validate_paths));
+        }
+        if let Some(kind) = self.expected_kind {
+            settings.push(format!("{:?}", kind));
+        }
+        if self.try_link {
+            settings.push("try_link".to_string());
+        }
+        if self.hermetic {
+            settings.push("hermetic".to_string());
+        }
+        if !self.variable_assertions.is_empty() {
+            let variables = self.variable_assertions.iter().map(|a| &a.0[..]).collect::<Vec<_>>();
+            settings.push(format!("assert_variable={}", variables.join(",")));
+        }
+        if self.search_mode != SearchMode::Default {
+            settings.push(format!("{:?}", self.search_mode));
+        }
+        if self.requires_policy != RequiresPolicy::EmitAll {
+            settings.push(format!("{:?}", self.requires_policy));
+        }
+        if !self.fallback_paths.is_empty() {
+            settings.push(list(&self.fallback_paths.iter().map(|p| p.display().to_string())
+                                                   .collect::<Vec<_>>()));
+        }
+        for &(flag, paths) in &[("-I", &self.extra_include_paths),
+                                ("-L", &self.extra_link_paths)] {
+            settings.extend(paths.iter().map(|p| format!("{}{}", flag, p.display())));
+        }
+        for paths in &[&self.system_library_path, &self.system_include_path] {
+            if let Some(ref paths) = **paths {
+                settings.push(list(&paths.iter().map(|p| p.display().to_string())
+                                         .collect::<Vec<_>>()));
+            }
+        }
+        #[cfg(feature = "vendored-resolver")]
+        let vendored_resolver = self.vendored_resolver;
+        #[cfg(not(feature = "vendored-resolver"))]
+        let vendored_resolver = false;
+        for &flag in &[self.pure, self.cargo_metadata, self.defer_metadata,
+                       self.env_metadata, self.rerun_on_system_packages,
+                       self.libpkgconf, vendored_resolver,
+                       self.standalone, self.drop_system_link_paths, self.verify_archives,
+                       self.ignore_statik_env, self.standard_variables,
+                       self.macos_package_managers, self.normalize_windows_paths] {
+            settings.push(if flag { "1" } else { "0" }.to_string());
+        }
+
+        // What `pkg-config` or a probe reads that can change its result,
+        // including the per-package `*_STATIC`, `*_DYNAMIC` and
+        // `*_NO_PKG_CONFIG`, but not e.g. `PKG_CONFIG_TRACE`.
+        let target = self.target_triple();
+        let scoped = |k: &str, var: &str| {
+            k == var || k == format!("HOST_{}", var) || k == format!("TARGET_{}", var) ||
+            target.as_ref().is_some_and(|target| {
+                k == format!("{}_{}", var, target) ||
+                k == format!("{}_{}", var, target.replace('-', "_"))
+            })
+        };
+        let mut vars = env::vars_os()
+            .map(|(k, v)| (k.to_string_lossy().into_owned(), v.to_string_lossy().into_owned()))
+            .filter(|(k, _)| {
+                (!self.standalone && (k == "TARGET" || k == "HOST")) ||
+                (!self.hermetic &&
+                 (FINGERPRINT_VARS.iter().any(|var| scoped(k, var)) ||
+                  k.ends_with("_STATIC") || k.ends_with("_DYNAMIC") ||
+                  k.ends_with("_NO_PKG_CONFIG") ||
+                  (self.macos_package_managers && k == "HOMEBREW_PREFIX")))
+            })
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>();
+        vars.sort();
+        settings.extend(vars);
+
+        fnv1a(&list(&settings))
+    }
+
+    /// Deprecated in favor fo the `probe` function
+    #[doc(hidden)]
+    pub fn find(&self, name: &str) -> Result<Library, String> {
+        self.probe(name).map_err(|e| e.to_string())
+    }
+
+    /// Run `pkg-config` to find the library `name`.
+    ///
+    /// This will use all configuration previously set to specify how
+    /// `pkg-config` is run.
+    pub fn probe(&self, name: &str) -> Result<Library, Error> {
+        rerun_first(self, || self.finish(self.resolve(name)))
+    }
+
+    /// Start settings for the package `name` only, on top of these ones.
+    ///
+    /// This lets one `Config` hold the settings shared by several probes,
+    /// each tweaked as needed, instead of a modified copy per package.
+    pub fn package(&self, name: &str) -> PackageConfig {
+        PackageConfig::new(self, name)
+    }
+
+    /// Call `f` with a copy of these settings for each of `targets`, e.g. to
+    /// get the flags of every architecture of a universal binary, returning
+    /// the results by target.
+    ///
+    /// Each copy probes for its target as with `target`, so it reads that
+    /// target's environment variables, e.g. `PKG_CONFIG_PATH_<triple>` or
+    /// `PKG_CONFIG_SYSROOT_DIR_<triple>`, and `f` can give it a `sysroot` of
+    /// its own. Cached results are kept apart, as they're keyed by the
+    /// `fingerprint`. Cargo metadata is only printed for the target cargo is
+    /// building for, as cargo would link the others' libraries into it.
+    pub fn for_each_target<F, R>(&self, targets: &[&str], mut f: F) -> Vec<(String, R)>
+        where F: FnMut(&mut Config) -> R
+    {
+        let building = self.target_triple();
+        targets.iter().map(|&target| {
+            let mut config = self.clone();
+            config.target(target);
+            if building.as_deref() != Some(target) {
+                config.cargo_metadata(false);
+            }
+            (target.to_string(), f(&mut config))
+        }).collect()
+    }
+
+    /// Fall back to `or_else` if `result` is an error, then postprocess the
+    /// library and print its cargo metadata.
+    fn finish(&self, result: Result<Library, Error>) -> Result<Library, Error> {
+        let mut library = match result {
+            Ok(library) => library,
+            Err(err) => match self.or_else {
+                Some(ref or_else) => or_else(err)?,
+                None => return Err(err),
+            },
+        };
+
+        if self.normalizes_paths() {
+            library.normalize_paths();
+        }
+        let postprocessors = POSTPROCESSORS.lock().unwrap().clone();
+        if !postprocessors.is_empty() {
+            let (libs, link_paths) = (library.libs.clone(), library.link_paths.clone());
+            for postprocessor in postprocessors {
+                postprocessor(&mut library);
+            }
+            if library.libs != libs || library.link_paths != link_paths {
+                library.relink(self);
+            }
+        }
+        library.print_cargo_metadata(self);
+
+        Ok(library)
+    }
+
+    /// Find `name` with `pkg-config`, without printing its cargo metadata.
+    fn resolve(&self, name: &str) -> Result<Library, Error> {
+        let abort_var_name = format!("{}_NO_PKG_CONFIG", envify(name));
+        if self.env_var_os(&abort_var_name).is_some() {
+            return Err(Error::EnvNoPkgConfig(abort_var_name))
+        }
+        self.track_env(name);
+        self.check_cross()?;
+        self.check_hermetic(name, true)?;
+
+        self.print_package_manager_state();
+        for warning in self.search_path_warnings() {
+            self.print_metadata(&format!("warning={}", warning));
+        }
+        if self.search_path_var().is_some() {
+            for path in &self.fallback_paths {
+                self.print_metadata(&format!("rerun-if-changed={}", path.display()));
+            }
+        }
+
+        let mut library = Library::new();
+
+        let fingerprint = self.fingerprint();
+        if let Some(err) = cache::lookup(&fingerprint, name) {
+            return Err(err);
+        }
+        if let Some((ref path, ttl)) = self.negative_cache {
+            if cache::lookup_file(path, &fingerprint, name, ttl) {
+                return Err(Error::NotFoundCached {
+                    package: name.to_owned(),
+                    suggestions: self.suggestions(name),
+                });
+            }
+        }
+        let query = self.query(name, &mut library.probe_commands, &mut library.warnings);
+        if self.forward_stderr {
+            for line in &library.warnings {
+                self.print_metadata(&format!("warning={}: {}", name, line));
+            }
+        }
+        let (flags, version) = match query {
+            Ok(result) => result,
+            Err(err) => {
+                #[cfg(any(feature = "vcpkg", feature = "cmake-fallback"))]
+                {
+                    if let Some(library) = self.fallback_library(name, &err) {
+                        self.check_version(name, &library.version)?;
+                        self.check_lock(name, &library.version)?;
+                        return self.check_library(name, library);
+                    }
+                }
+                cache::insert(&fingerprint, name, &err);
+                if let Some((ref path, _)) = self.negative_cache {
+                    if err.is_not_found() {
+                        let _ = cache::record_file(path, &fingerprint, name);
+                    }
+                }
+                return Err(err);
+            }
+        };
+        library.parse_libs_cflags(name, &flags, self)?;
+        library.parse_modversion(&version);
+        extend_paths(&mut library.include_paths, &self.extra_include_paths);
+        extend_paths(&mut library.link_paths, &self.extra_link_paths);
+        library.sysroot = self.sysroot_dir().map(PathBuf::from);
+        library.package = Some(name.to_owned());
+        if !library.probe_commands.is_empty() {
+            library.pkg_config = Some(self.executable());
+        }
+        self.check_lock(name, &library.version)?;
+        library.pc_path = self.find_pc_file(name, &self.configured_pc_path());
+        // In-process resolvers don't search the default directories.
+        if library.pc_path.is_none() && !library.probe_commands.is_empty() &&
+           self.libdir_var().is_none() {
+            library.pc_path = self.find_pc_file(name, &self.default_pc_path());
+        }
+        library.virtual_package = library.pc_path.is_none() && BUILTIN_PACKAGES.contains(&name);
+        // Builtin packages have no `.pc` file, variables or flags to check.
+        if library.virtual_package {
+            return Ok(library);
+        }
+        // In-process resolvers run no commands and have no requires to list.
+        if !library.probe_commands.is_empty() {
+            for lib in self.deferred_libs(name, &mut library.probe_commands)? {
+                if let Some(file) = lib.strip_prefix(':') {
+                    if library.verbatim_libs.iter().any(|l| l == file) {
+                        library.verbatim_libs.retain(|l| l != file);
+                        library.linkage.insert(file.to_string(), Linkage::Skipped);
+                    }
+                } else if library.libs.contains(&lib) {
+                    library.libs.retain(|l| *l != lib);
+                    library.static_libs.retain(|l| *l != lib);
+                    library.whole_archive.retain(|l| *l != lib);
+                    library.linkage.insert(lib, Linkage::Skipped);
+                }
+            }
+        }
+        // In-process resolvers run no commands and have no variables to ask.
+        if self.standard_variables && !library.probe_commands.is_empty() {
+            library.prefix = self.query_variable(name, "prefix", &mut library.probe_commands)?;
+            library.exec_prefix =
+                self.query_variable(name, "exec_prefix", &mut library.probe_commands)?;
+            library.libdir = self.query_variable(name, "libdir", &mut library.probe_commands)?;
+            library.includedir =
+                self.query_variable(name, "includedir", &mut library.probe_commands)?;
+        }
+        if !library.probe_commands.is_empty() {
+            for (variable, check) in &self.variable_assertions {
+                let value = self.query_text_variable(name, variable, &mut library.probe_commands)?;
+                match value {
+                    Some(ref value) if check(value) => {
+                        library.variables.insert(variable.clone(), value.clone());
+                    }
+                    value => {
+                        return Err(Error::VariableAssertion {
+                            package: name.to_owned(),
+                            variable: variable.clone(),
+                            value,
+                        });
+                    }
+                }
+            }
+        }
+        self.check_library(name, library)
+    }
+
+    /// The checks and warnings of `validate_paths`, `expect_kind`,
+    /// `try_link` and `warnings` for the library found for `name`.
+    fn check_library(&self, name: &str, library: Library) -> Result<Library, Error> {
+        let missing = diagnose::missing_paths(&library);
+        match self.validate_paths {
+            PathPolicy::Error if !missing.is_empty() => {
+                return Err(Error::InvalidPaths { package: name.to_owned(), missing });
+            }
+            // `warnings` reports them as well.
+            PathPolicy::Warn if !self.warnings => {
+                for path in &missing {
+                    let warning = diagnose::missing_path_warning(name, path);
+                    self.print_metadata(&format!("warning={}", warning));
+                }
+            }
+            _ => {}
+        }
+        if let Some(expected) = self.expected_kind {
+            let sdkroot = self.env_var_os("SDKROOT").map(PathBuf::from);
+            if let Some(problem) = diagnose::check_kind(&library, expected, sdkroot.as_deref()) {
+                return Err(Error::UnexpectedKind { package: name.to_owned(), expected, problem });
+            }
+        }
+        if self.try_link {
+            self.try_link_library(name, &library)?;
+        }
+        let target = self.target_triple();
+        // Cross compiling with the host's libraries, whatever `warnings` is.
+        let mismatches = match target {
+            Some(ref target) if self.host_triple().as_ref() != Some(target) => {
+                diagnose::arch_mismatches(name, &library, Some(target))
+            }
+            _ => Vec::new(),
+        };
+        let warnings = if self.warnings {
+            diagnose::warnings(name, &library, target.as_deref())
+        } else {
+            mismatches.clone()
+        };
+        for warning in warnings {
+            self.print_metadata(&format!("warning={}", warning));
+        }
+        match target {
+            Some(ref target) if !mismatches.is_empty() => {
+                for hint in diagnose::cross_hint(target) {
+                    self.print_metadata(&format!("warning={}", hint));
+                }
+            }
+            _ => {}
+        }
+
+        Ok(library)
+    }
+
+    /// Find `name` with vcpkg or in the CMake packages instead, if `err`
+    /// says `pkg-config` doesn't know it or isn't installed.
+    #[cfg(any(feature = "vcpkg", feature = "cmake-fallback"))]
+    fn fallback_library(&self, name: &str, err: &Error) -> Option<Library> {
+        if self.hermetic || !(err.is_not_found() || err.is_tool_missing()) {
+            return None;
+        }
+        #[cfg(feature = "vcpkg")]
+        {
+            if let Some(library) = self.vcpkg_library(name) {
+                return Some(library);
+            }
+        }
+        #[cfg(feature = "cmake-fallback")]
+        {
+            // The host's prefixes only have packages for the host.
+            let native = self.target_triple() == self.host_triple();
+            let prefix_path = self.env_var_os("CMAKE_PREFIX_PATH");
+            if let Some(library) = cmake::find(name, prefix_path, native) {
+                return Some(library);
+            }
+        }
+        None
+    }
+
+    /// Check `version` against `atleast_version` and `at_most_version`, for
+    /// the versions not checked by `pkg-config` itself. An unknown version
+    /// satisfies neither.
+    fn check_version(&self, name: &str, version: &str) -> Result<(), Error> {
+        let mut required = Vec::new();
+        let mut satisfied = true;
+        if let Some(ref minimum) = self.atleast_version {
+            required.push(format!(">= {}", minimum));
+            satisfied &= compare_versions(version, minimum) != cmp::Ordering::Less;
+        }
+        if let Some(ref maximum) = self.at_most_version {
+            required.push(format!("<= {}", maximum));
+            satisfied &= compare_versions(version, maximum) != cmp::Ordering::Greater;
+        }
+        if !required.is_empty() && (version.is_empty() || !satisfied) {
+            return Err(Error::VersionMismatch {
+                package: name.to_owned(),
+                found: if version.is_empty() { "unknown".to_owned() } else { version.to_owned() },
+                required: required.join(", "),
+            });
+        }
+        Ok(())
+    }
+
+    /// Find `name` with vcpkg instead, for MSVC targets where `pkg-config`
+    /// failed.
+    #[cfg(feature = "vcpkg")]
+    fn vcpkg_library(&self, name: &str) -> Option<Library> {
+        if !self.target_triple().is_some_and(|t| t.ends_with("-pc-windows-msvc")) {
+            return None;
+        }
+        let found = vcpkg::Config::new().cargo_metadata(false).find_package(name).ok()?;
+        let mut library = Library::new();
+        library.link_paths = found.link_paths;
+        library.include_paths = found.include_paths;
+        library.libs = found.found_names;
+        if found.is_static {
+            library.static_libs = library.libs.clone();
+        }
+        let linkage = if found.is_static { Linkage::Static } else { Linkage::Dynamic };
+        for lib in &library.libs {
+            library.linkage.insert(lib.clone(), linkage);
+        }
+        Some(library)
+    }
+
+    fn check_lock(&self, name: &str, version: &str) -> Result<(), Error> {
+        let path = match self.lock_file {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        self.print_metadata(&format!("rerun-if-changed={}", path.display()));
+        let entries = lock::read(path).map_err(|cause| Error::LockFile {
+            path: path.clone(),
+            cause,
+        })?;
+        match entries.into_iter().find(|e| e.0 == name) {
+            Some((_, locked)) if !lock::same_minor(&locked, version) => {
+                Err(Error::LockMismatch {
+                    package: name.to_owned(),
+                    locked,
+                    found: version.to_owned(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `pkg-config` to find `name` in each of several independent
+    /// installation prefixes, e.g. a vendored prefix and `/usr`, returning
+    /// every library found along with its prefix.
+    ///
+    /// Each prefix is searched through its `lib/pkgconfig`, `lib64/pkgconfig`
+    /// and `share/pkgconfig` directories only, ignoring `PKG_CONFIG_PATH`.
+    /// No cargo metadata is printed and `or_else` isn't applied: pick one of
+    /// the results and call `Library::emit_cargo_metadata` with it.
+    pub fn union_roots<P: AsRef<Path>>(&self, name: &str, roots: &[P])
+                                       -> Vec<(PathBuf, Library)> {
+        roots.iter().filter_map(|root| {
+            let mut config = self.clone();
+            config.root = Some(root.as_ref().to_path_buf());
+            match config.resolve(name) {
+                Ok(library) => Some((root.as_ref().to_path_buf(), library)),
+                Err(_) => None,
+            }
+        }).collect()
+    }
+
+    /// Run `pkg-config` to find the first of several alternative names for the
+    /// same library, e.g. `&["lua5.4", "lua5.3", "lua"]`.
+    ///
+    /// Candidates are tried in order and the first successful probe is
+    /// returned. If all of them fail, the returned error records every name
+    /// that was tried along with the reason it failed.
+    pub fn probe_any(&self, names: &[&str]) -> Result<Library, Error> {
+        rerun_first(self, || {
+            let mut tried = Errors::default();
+            for name in names {
+                match self.resolve(name) {
+                    Ok(library) => return self.finish(Ok(library)),
+                    Err(err) => tried.push(name, err),
+                }
+            }
+            self.finish(Err(Error::AlternativesFailed { tried }))
+        })
+    }
+
+    /// Run `pkg-config` to find the header-only package `name`.
+    ///
+    /// Unlike `probe`, only `--cflags` is queried and no link metadata is
+    /// emitted, so packages whose `.pc` file has no `Libs` are handled
+    /// without spurious errors.
+    pub fn probe_headers(&self, name: &str) -> Result<Includes, Error> {
+        rerun_first(self, || self.resolve_headers(name))
+    }
+
+    fn resolve_headers(&self, name: &str) -> Result<Includes, Error> {
+        let abort_var_name = format!("{}_NO_PKG_CONFIG", envify(name));
+        if self.env_var_os(&abort_var_name).is_some() {
+            return Err(Error::EnvNoPkgConfig(abort_var_name))
+        }
+        self.check_cross()?;
+        self.check_hermetic(name, false)?;
+        self.check_tool_version(&mut Vec::new())?;
+
+        self.print_package_manager_state();
+
+        let mut includes = Includes::new();
+
+        let output = run(&mut self.flags_command(name, &["--cflags"]), self)
+            .map_err(|e| self.version_mismatch(name, e))?;
+        includes.parse_cflags(&output, self);
+        extend_paths(&mut includes.include_paths, &self.extra_include_paths);
+
+        let mut cmd = self.command(name, &["--modversion"]);
+        let output = run(&mut cmd, self)?;
+        includes.version.push_str(utf8(output, &cmd)?.trim());
+
+        Ok(includes)
+    }
+
+    /// Find the macOS framework `name`, e.g. `CoreAudio`, which has no `.pc`
+    /// file, and print its cargo metadata like `probe`.
+    ///
+    /// The framework is searched in the SDK named by `SDKROOT`, if set, then
+    /// in `/Library/Frameworks` and `/System/Library/Frameworks`. Version
+    /// requirements are checked against the `CFBundleShortVersionString` of
+    /// its `Info.plist`.
+    pub fn probe_framework(&self, name: &str) -> Result<Library, Error> {
+        rerun_first(self, || self.finish(self.resolve_framework(name)))
+    }
+
+    fn resolve_framework(&self, name: &str) -> Result<Library, Error> {
+        let sdkroot = self.env_var_os("SDKROOT").map(PathBuf::from);
+        let dirs = framework::search_dirs(sdkroot.as_deref());
+        let dir = match framework::find(name, &dirs) {
+            Some(dir) => dir,
+            None => {
+                return Err(Error::FrameworkNotFound { framework: name.to_owned(),
+                                                      searched: dirs.clone() });
+            }
+        };
+        let path = dir.join(format!("{}.framework", name));
+
+        let mut library = Library::new();
+        library.version = framework::version(&path).unwrap_or_default();
+        self.check_version(name, &library.version)?;
+
+        library.frameworks.push(name.to_owned());
+        library.framework_paths.push(dir.clone());
+        let headers = path.join("Headers");
+        if headers.is_dir() {
+            library.include_paths.push(headers);
+        }
+        Ok(library)
+    }
+
+    /// Run `pkg-config` to find the package `name`, which only provides
+    /// build tools such as code generators.
+    ///
+    /// Libs and cflags aren't queried and no link metadata is emitted.
+    /// Instead, the variables naming a file in the package's `bindir` or
+    /// `libexecdir` (`${exec_prefix}/bin` and `${exec_prefix}/libexec` by
+    /// default) are collected, and each of them must be executable.
+    pub fn probe_tool_package(&self, name: &str) -> Result<ToolPackage, Error> {
+        let abort_var_name = format!("{}_NO_PKG_CONFIG", envify(name));
+        if self.env_var_os(&abort_var_name).is_some() {
+            return Err(Error::EnvNoPkgConfig(abort_var_name))
+        }
+        self.check_cross()?;
+        self.check_hermetic(name, false)?;
+
+        let mut cmd = self.command(name, &["--modversion"]);
+        let output = run(&mut cmd, self).map_err(|e| self.version_mismatch(name, e))?;
+        let version = utf8(output, &cmd)?.trim().to_string();
+
+        let mut cmd = self.base_command(name, &["--print-variables", name]);
+        let output = run(&mut cmd, self)?;
+        let mut variables = HashMap::new();
+        for variable in utf8(output, &cmd)?.lines() {
+            let arg = format!("--variable={}", variable);
+            let output = run(&mut self.base_command(name, &[&arg, name]), self)?;
+            variables.insert(variable.to_string(), PathBuf::from(os_string(trim_output(output))));
+        }
+
+        let exec_prefix = variables.get("exec_prefix").cloned();
+        let tool_dir = |var: &str, default: &str| {
+            variables.get(var).cloned()
+                     .or_else(|| exec_prefix.as_ref().map(|p| p.join(default)))
+        };
+        let tool_dirs = [tool_dir("bindir", "bin"), tool_dir("libexecdir", "libexec")];
+
+        let mut tools = HashMap::new();
+        for (variable, path) in variables {
+            if !tool_dirs.iter().any(|dir| dir.is_some() && path.parent() == dir.as_deref()) {
+                continue;
+            }
+            if !is_executable(&path) {
+                return Err(Error::ToolNotExecutable {
+                    package: name.to_owned(),
+                    variable,
+                    path,
+                });
+            }
+            tools.insert(variable, path);
+        }
+
+        Ok(ToolPackage { tools, version, _priv: () })
+    }
+
+    /// Deprecated in favor of the top level `get_variable` function
+    #[doc(hidden)]
+    pub fn get_variable(package: &str, variable: &str) -> Result<String, String> {
+        get_variable(package, variable).map_err(|e| e.to_string())
+    }
+
+    fn target_triple(&self) -> Option<String> {
+        if self.standalone {
+            return self.target.clone();
+        }
+        self.target.clone().or_else(|| env::var("TARGET").ok())
+    }
+
+    fn host_triple(&self) -> Option<String> {
+        if self.standalone {
+            return self.host.clone();
+        }
+        self.host.clone().or_else(|| env::var("HOST").ok())
+    }
+
+    /// Only use pkg-config in host == target situations by default.
+    ///
+    /// `PKG_CONFIG_ALLOW_CROSS` set to `0` or `false` forbids cross
+    /// compilation and any other value allows it. If it's unset, cross
+    /// compilation is allowed when a sysroot, or a search path specific to
+    /// the target, is configured.
+    fn check_cross(&self) -> Result<(), Error> {
+        let target = self.target_triple();
+        let host = self.host_triple();
+        if target == host {
+            return Ok(());
+        }
+        let target = target.unwrap_or_default();
+        let host = host.unwrap_or_default();
+        let reason = match self.env_var_os("PKG_CONFIG_ALLOW_CROSS") {
+            Some(value) => {
+                let value = value.to_string_lossy();
+                if value != "0" && !value.eq_ignore_ascii_case("false") {
+                    return Ok(());
+                }
+                format!("PKG_CONFIG_ALLOW_CROSS={} forbids building for {} on {}",
+                        value, target, host)
+            }
+            None => {
+                if self.sysroot_dir().is_some() ||
+                   self.target_scoped_env_var("PKG_CONFIG_PATH") ||
+                   self.target_scoped_env_var("PKG_CONFIG_LIBDIR") {
+                    return Ok(());
+                }
+                format!("building for {} on {} without a sysroot; set \
+                         PKG_CONFIG_SYSROOT_DIR, PKG_CONFIG_PATH_{} or \
+                         PKG_CONFIG_ALLOW_CROSS=1 to allow it",
+                        target, host, target)
+            }
+        };
+        Err(Error::CrossCompilation { reason })
+    }
+
+    /// Fail if `hermetic` is set and `name` would be resolved by the
+    /// installed `pkg-config`, `resolver` telling whether the probe can use
+    /// the vendored resolver at all.
+    fn check_hermetic(&self, name: &str, resolver: bool) -> Result<(), Error> {
+        if !self.hermetic {
+            return Ok(());
+        }
+        #[cfg(feature = "vendored-resolver")]
+        {
+            if resolver && self.use_vendored_resolver() {
+                return Ok(());
+            }
+        }
+        let _ = resolver;
+        Err(Error::NotHermetic { package: name.to_string() })
+    }
+
+    fn targetted_env_var(&self, var_base: &str) -> Result<String, env::VarError> {
+        if let Some(target) = self.target_triple() {
+            let kind = if self.host_triple().as_ref() == Some(&target) { "HOST" } else { "TARGET" };
+            let target_u = target.replace("-", "_");
+
+            self.env_var(&format!("{}_{}", var_base, target))
+                .or_else(|_| self.env_var(&format!("{}_{}", var_base, target_u)))
+                .or_else(|_| self.env_var(&format!("{}_{}", kind, var_base)))
+                .or_else(|_| self.env_var(var_base))
+        } else {
+            self.env_var(var_base)
+        }
+    }
+
+    /// Whether `<var_base>_<target>` or `TARGET_<var_base>` is set, i.e.
+    /// `var_base` is configured for the target specifically.
+    fn target_scoped_env_var(&self, var_base: &str) -> bool {
+        let target = match self.target_triple() {
+            Some(target) => target,
+            None => return false,
+        };
+        self.env_var_os(&format!("{}_{}", var_base, target)).is_some() ||
+        self.env_var_os(&format!("{}_{}", var_base, target.replace("-", "_"))).is_some() ||
+        self.env_var_os(&format!("TARGET_{}", var_base)).is_some()
+    }
+
+    /// Whether the variable `name` is ignored, see `hermetic`.
+    fn ignores_env_var(&self, name: &str) -> bool {
+        self.hermetic && !(name == "CC" || name.starts_with("CC_") || name.ends_with("_CC"))
+    }
+
+    fn env_var(&self, name: &str) -> Result<String, env::VarError> {
+        if self.ignores_env_var(name) {
+            return Err(env::VarError::NotPresent);
+        }
+        if self.env_metadata && !self.standalone {
+            print_cargo(&format!("rerun-if-env-changed={}", name));
+        }
+        env::var(name)
+    }
+
+    fn env_var_os(&self, name: &str) -> Option<OsString> {
+        if self.ignores_env_var(name) {
+            return None;
+        }
+        if self.env_metadata && !self.standalone {
+            print_cargo(&format!("rerun-if-env-changed={}", name));
+        }
+        env::var_os(name)
+    }
+
+    /// The system packages that may provide the missing package `name`, see
+    /// `distro_packages`.
+    fn suggestions(&self, name: &str) -> Vec<Suggestion> {
+        distro::suggestions(name, self.env_var_os("PKG_CONFIG_DISTRO_PACKAGES"))
+    }
+
+    /// The file to append the cargo metadata printed with this
+    /// configuration to, see `PKG_CONFIG_RS_METADATA_FILE`.
+    fn metadata_file(&self) -> Option<OsString> {
+        self.env_var_os("PKG_CONFIG_RS_METADATA_FILE")
+    }
+
+    fn is_static(&self, name: &str) -> Statik {
+        if self.statik_blacklist_contains(name) {
+            Statik::No
+        } else {
+            match self.statik {
+                Some(ref statik) => statik.clone(),
+                None if self.ignore_statik_env => Statik::No,
+                None => self.statik_from_env(name).unwrap_or(Statik::No),
+            }
+        }
+    }
+
+    /// Turns a failed, version-constrained query into `Error::VersionMismatch`
+    /// if the package itself can be found in a version not satisfying them.
+    fn version_mismatch(&self, name: &str, err: Error) -> Error {
+        let constrained = self.atleast_version.is_some() || self.at_most_version.is_some();
+        match err {
+            Error::Failure { .. } if constrained => {}
+            err => return err,
+        }
+
+        let mut cmd = self.base_command(name, &["--modversion"]);
+        cmd.arg(name);
+        match run(&mut cmd, self) {
+            Ok(output) => {
+                let found = String::from_utf8_lossy(&output).trim().to_owned();
+                self.check_version(name, &found).err().unwrap_or(err)
+            }
+            Err(_) => err,
+        }
+    }
+
+    fn static_archive_patterns(&self) -> Vec<String> {
+        let mut patterns = vec!["lib{}.a".to_string()];
+        if self.target_triple().is_some_and(|t| t.contains("windows-gnu")) {
+            patterns.push("{}.a".to_string());
+        }
+        patterns.extend(self.static_archive_patterns.iter().cloned());
+        patterns
+    }
+
+    /// Decide whether the library `lib` of a package probed in mode `statik`
+    /// is linked statically, given the link paths seen so far.
+    fn links_statically(&self, statik: &Statik, lib: &str, dirs: &[PathBuf],
+                        patterns: &[String]) -> bool {
+        if self.statik_blacklist_contains(lib) {
+            return false;
+        }
+        let statik = if self.statik_whitelist.is_empty() {
+            statik
+        } else if !self.statik_whitelist_contains(lib) {
+            return false;
+        } else if *statik != Statik::No {
+            statik
+        } else {
+            &Statik::Yes
+        };
+        let archive = match *statik {
+            Statik::Force => return true,
+            Statik::No => return false,
+            Statik::Yes => static_archive(lib, dirs, patterns),
+            Statik::Prefer => find_archive(lib, dirs, patterns),
+            Statik::Probe => {
+                find_archive(lib, dirs, patterns)
+                    .or_else(|| find_archive(lib, &self.default_link_dirs(), patterns))
+            }
+        };
+        archive.is_some_and(|path| !self.verify_archives || archive::has_symbols(&path))
+    }
+
+    /// Build the command for a query printing flags, i.e. `--libs` and/or
+    /// `--cflags`.
+    fn flags_command(&self, name: &str, args: &[&str]) -> Command {
+        let mut cmd = self.command(name, args);
+        if let Some(ref types) = self.fragment_filter {
+            if self.is_pkgconf() {
+                cmd.arg(format!("--fragment-filter={}", types));
+            }
+        }
+        cmd
+    }
+
+    /// Apply `fragment_filter` to the flags split out of a `flags_command`
+    /// output, unless the tool already did.
+    fn filter_fragments(&self, words: Vec<OsString>) -> Vec<OsString> {
+        match self.fragment_filter {
+            Some(ref types) if self.libpkgconf || !self.is_pkgconf() => {
+                words.into_iter()
+                     .filter(|word| {
+                         let word = word.to_string_lossy();
+                         word.starts_with('-') &&
+                         word[1..].chars().next().is_some_and(|c| types.contains(c))
+                     })
+                     .collect()
+            }
+            _ => words,
+        }
+    }
+
+    /// Whether the `pkg-config` executable is pkgconf, which alone supports
+    /// `--about`. The answer is cached per executable.
+    fn is_pkgconf(&self) -> bool {
+        let exe = self.executable();
+        if let Some(&(_, is_pkgconf)) = PKGCONF.lock().unwrap().iter().find(|e| e.0 == exe) {
+            return is_pkgconf;
+        }
+        let is_pkgconf = run(&mut self.tool_command(&["--about"]), self).is_ok();
+        PKGCONF.lock().unwrap().push((exe, is_pkgconf));
+        is_pkgconf
+    }
+
+    /// Whether the `pkg-config` executable supports `--define-prefix`,
+    /// which pkgconf and `pkg-config` 0.29 do. The answer is cached per
+    /// executable, and a vetoed check counts as no.
+    fn supports_define_prefix(&self) -> bool {
+        let exe = self.executable();
+        if let Some(&(_, supported)) = DEFINE_PREFIX.lock().unwrap().iter().find(|e| e.0 == exe) {
+            return supported;
+        }
+        let mut cmd = self.tool_command(&["--define-prefix", "--version"]);
+        let supported = run(&mut cmd, self).is_ok();
+        DEFINE_PREFIX.lock().unwrap().push((exe, supported));
+        supported
+    }
+
+    /// Check the version of the `pkg-config` executable against
+    /// `min_pkg_config_version`, recording the command run in `commands`. A
+    /// missing executable is left for the probe to report, or fall back from.
+    fn check_tool_version(&self, commands: &mut Vec<String>) -> Result<(), Error> {
+        let required = match self.min_pkg_config_version {
+            Some(ref required) => required,
+            None => return Ok(()),
+        };
+        let exe = self.executable();
+        // Not locked while running, which `on_command` may reenter.
+        let cached = TOOL_VERSIONS.lock().unwrap().iter().find(|e| e.0 == exe).cloned();
+        let found = match cached {
+            Some((_, found)) => found,
+            None => {
+                let mut cmd = self.tool_command(&["--version"]);
+                let result = run(&mut cmd, self);
+                commands.push(command_line(&cmd));
+                let output = match result {
+                    Ok(output) => output,
+                    Err(err @ Error::Vetoed { .. }) => return Err(err),
+                    Err(_) => return Ok(()),
+                };
+                let found = String::from_utf8_lossy(&output).trim().to_string();
+                TOOL_VERSIONS.lock().unwrap().push((exe, found.clone()));
+                found
+            }
+        };
+        if compare_versions(&found, required) == cmp::Ordering::Less {
+            return Err(Error::ToolTooOld { found, required: required.clone() });
+        }
+        Ok(())
+    }
+
+    fn command(&self, name: &str, args: &[&str]) -> Command {
+        let mut cmd = self.base_command(name, args);
+        cmd.args(self.constraints(name));
+        cmd
+    }
+
+    /// The package arguments for `name`, one per version constraint.
+    fn constraints(&self, name: &str) -> Vec<String> {
+        let mut constraints = Vec::new();
+        if let Some(ref version) = self.atleast_version {
+            constraints.push(format!("{} >= {}", name, version));
+        }
+        if let Some(ref version) = self.at_most_version {
+            constraints.push(format!("{} <= {}", name, version));
+        }
+        if constraints.is_empty() {
+            constraints.push(name.to_string());
+        }
+        constraints
+    }
+
+    /// Query the output of `--libs --cflags` and the version of `name`,
+    /// recording the commands run in `commands`.
+    ///
+    /// The reference `pkg-config` prints the version lines followed by the
+    /// flags in a single invocation. pkgconf only prints the version, so its
+    /// version is read from the `.pc` file when possible, and otherwise its
+    /// flags take a second invocation.
+    fn query(&self, name: &str, commands: &mut Vec<String>, warnings: &mut Vec<String>)
+             -> Result<(Vec<u8>, String), Error> {
+        #[cfg(feature = "libpkgconf")]
+        {
+            if self.libpkgconf {
+                return libpkgconf::query(name, &self.constraints(name),
+                                         &self.in_process_options(name))
+                    .map_err(|failure| self.in_process_error(name, failure));
+            }
+        }
+        // A sysroot is resolved host-side, without running `pkg-config`.
+        #[cfg(feature = "vendored-resolver")]
+        {
+            // So is a package to relocate when `pkg-config` can't, and any
+            // package when hermetic, whatever `pkg-config` is installed.
+            let relocate = self.define_prefix == Some(true) && !self.supports_define_prefix();
+            if (self.sysroot.is_some() || relocate || self.hermetic) &&
+               self.use_vendored_resolver() {
+                return self.vendored_query(name);
+            }
+        }
+        #[cfg(all(feature = "probe-server", unix))]
+        {
+            // Hooks may change what's run and its output, beyond what the
+            // fingerprint covers.
+            if self.env_var_os("PKG_CONFIG_RS_SERVER").is_some() &&
+               self.on_command.is_none() && self.on_output.is_none() {
+                let key = format!("{} {} {}", self.fingerprint(), self.pc_files_stamp(), name);
+                if let Some(answer) = server::get(&key) {
+                    commands.extend(answer.commands);
+                    warnings.extend(answer.warnings);
+                    return Ok((answer.flags, answer.version));
+                }
+                let (start, warnings_start) = (commands.len(), warnings.len());
+                let (flags, version) = self.run_query(name, commands, warnings)?;
+                let answer = server::Answer {
+                    flags,
+                    version,
+                    commands: commands[start..].to_vec(),
+                    warnings: warnings[warnings_start..].to_vec(),
+                };
+                server::put(&key, &answer);
+                return Ok((answer.flags, answer.version));
+            }
+        }
+        self.run_query(name, commands, warnings)
+    }
+
+    /// A hash of the paths and modification times of the `.pc` files in the
+    /// search path, so that cached answers aren't reused once one changes.
+    #[cfg(all(feature = "probe-server", unix))]
+    fn pc_files_stamp(&self) -> String {
+        let mut dirs = self.configured_pc_path();
+        if self.libdir_var().is_none() {
+            dirs.extend(self.default_pc_path());
+        }
+        let mut files = Vec::new();
+        for dir in dirs {
+            for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "pc") {
+                    let modified = entry.metadata().and_then(|m| m.modified()).ok();
+                    files.push(format!("{} {:?}", path.display(), modified));
+                }
+            }
+        }
+        files.sort();
+        fnv1a(&files.join("\n"))
+    }
+
+    /// The default search path of the `pkg-config` executable, asked once
+    /// per executable.
+    fn default_pc_path(&self) -> Vec<PathBuf> {
+        let exe = self.executable();
+        let mut cache = DEFAULT_PC_PATH.lock().unwrap();
+        if let Some(entry) = cache.iter().find(|e| e.0 == exe) {
+            return entry.1.clone();
+        }
+        let mut cmd = self.base_command("pkg-config", &["--variable=pc_path", "pkg-config"]);
+        let dirs = run(&mut cmd, self).map(|out| parse_search_path(&os_string(trim_output(out))))
+                                      .unwrap_or_default();
+        cache.push((exe, dirs.clone()));
+        dirs
+    }
+
+    /// Run `pkg-config` for the version and flags of `name`, recording the
+    /// commands run in `commands` and what they printed on stderr in
+    /// `warnings`.
+    fn run_query(&self, name: &str, commands: &mut Vec<String>, warnings: &mut Vec<String>)
+                 -> Result<(Vec<u8>, String), Error> {
+        self.check_tool_version(commands)?;
+        // Arguments for either query alone can't go to a single invocation.
+        let combined = self.query_args.iter().all(|a| a.0 == Query::Variable);
+        if combined && self.is_pkgconf() {
+            if let Some(version) = self.pc_file_version(name) {
+                let mut cmd = self.flags_command(name, &["--libs", "--cflags"]);
+                let result = run_output(&mut cmd, self);
+                commands.push(command_line(&cmd));
+                let output = result.map_err(|e| self.version_mismatch(name, e))?;
+                extend_stderr(warnings, &output.stderr);
+                return Ok((output.stdout, version));
+            }
+        }
+        // Recorded after running, so that changes made by `on_command` show.
+        let mut cmd = if combined {
+            self.flags_command(name, &["--modversion", "--libs", "--cflags"])
+        } else {
+            self.command(name, &["--modversion"])
+        };
+        let result = run_output(&mut cmd, self);
+        commands.push(command_line(&cmd));
+        let output = match result {
+            #[cfg(feature = "vendored-resolver")]
+            Err(ref e) if e.is_tool_missing() && self.use_vendored_resolver() => {
+                commands.clear();
+                return self.vendored_query(name);
+            }
+            result => result.map_err(|e| self.version_mismatch(name, e))?,
+        };
+        extend_stderr(warnings, &output.stderr);
+
+        // One version line per package argument. The flags may hold paths
+        // that aren't UTF-8, so only the version is decoded.
+        let versions = self.constraints(name).len();
+        let stdout = output.stdout.strip_suffix(b"\n").unwrap_or(&output.stdout);
+        let mut lines = stdout.split(|&b| b == b'\n');
+        let version = lines.next().unwrap_or_default().to_vec();
+        let version = utf8(version, &cmd)?.trim_end().to_string();
+        let rest = lines.skip(versions - 1).collect::<Vec<_>>();
+        if !rest.is_empty() {
+            return Ok((rest.join(&b' '), version));
+        }
+
+        let mut cmd = self.flags_command(name, &["--libs", "--cflags"]);
+        let result = run_output(&mut cmd, self);
+        commands.push(command_line(&cmd));
+        let output = result.map_err(|e| self.version_mismatch(name, e))?;
+        extend_stderr(warnings, &output.stderr);
+        Ok((output.stdout, version))
+    }
+
+    /// The directories `pkg-config` is told to search, before or instead of
+    /// its default ones.
+    fn configured_pc_path(&self) -> Vec<PathBuf> {
+        self.search_path_var().into_iter()
+                              .chain(self.libdir_var())
+                              .flat_map(|dirs| parse_search_path(dirs.as_ref()))
+                              .collect()
+    }
+
+    /// The version in the `.pc` file `pkg-config` would read for `name`, if
+    /// it can be told without running it. Arguments added with `arg` may
+    /// define variables, so they rule it out.
+    fn pc_file_version(&self, name: &str) -> Option<String> {
+        if !self.extra_args.is_empty() || name.contains(['/', '\\']) || name.ends_with(".pc") {
+            return None;
+        }
+        let mut dirs = self.configured_pc_path();
+        if self.libdir_var().is_none() {
+            dirs.extend(self.default_pc_path());
+        }
+        let path = self.find_pc_file(name, &dirs)?;
+        pc_version(&path, &fs::read_to_string(&path).ok()?)
+    }
+
+    /// The `.pc` file of `name` in `dirs`, without running `pkg-config`.
+    fn find_pc_file(&self, name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+        let mut files = vec![format!("{}.pc", name)];
+        if self.uninstalled_allowed() {
+            files.insert(0, format!("{}-uninstalled.pc", name));
+        }
+        files.iter().find_map(|file| {
+            dirs.iter().map(|dir| dir.join(file)).find(|path| path.is_file())
+        })
+    }
+
+    /// Whether `-uninstalled.pc` files are preferred, see
+    /// `disable_uninstalled`.
+    fn uninstalled_allowed(&self) -> bool {
+        match self.disable_uninstalled {
+            Some(disable) => !disable,
+            None => self.env_var_os("PKG_CONFIG_DISABLE_UNINSTALLED").is_none(),
+        }
+    }
+
+    /// The libraries of the requires of `name` left to other crates by
+    /// `requires_policy`, recording the commands run in `commands`.
+    ///
+    /// `--libs-only-l` lists the libraries of the requires too, so those of
+    /// the packages still linked by this probe are kept: the libraries they
+    /// list that none of their own requires do.
+    fn deferred_libs(&self, name: &str, commands: &mut Vec<String>)
+                     -> Result<Vec<String>, Error> {
+        // The packages still linked, with their requires.
+        let mut kept = Vec::new();
+        let deferred = match self.requires_policy {
+            RequiresPolicy::EmitAll => return Ok(Vec::new()),
+            RequiresPolicy::DirectOnly => {
+                let requires = self.print_requires(name, name, commands)?;
+                kept.push((name.to_string(), requires.clone()));
+                requires
+            }
+            RequiresPolicy::DeferToDeps(ref links) => {
+                let mut deferred = Vec::new();
+                let mut seen = Vec::new();
+                let mut queue = vec![name.to_string()];
+                while let Some(package) = queue.pop() {
+                    let requires = self.print_requires(name, &package, commands)?;
+                    for required in &requires {
+                        if seen.contains(required) {
+                            continue;
+                        }
+                        seen.push(required.clone());
+                        match links.get(required) {
+                            Some(links) if self.is_dependency(links) => {
+                                deferred.push(required.clone())
+                            }
+                            _ => queue.push(required.clone()),
+                        }
+                    }
+                    kept.push((package, requires));
+                }
+                deferred
+            }
+        };
+        if deferred.is_empty() {
+            return Ok(deferred);
+        }
+        let mut kept_libs = Vec::new();
+        for (package, requires) in &kept {
+            let mut own = self.libs_only_l(name, slice::from_ref(package), commands)?;
+            if !requires.is_empty() {
+                let inherited = self.libs_only_l(name, requires, commands)?;
+                own.retain(|lib| !inherited.contains(lib));
+            }
+            kept_libs.extend(own);
+        }
+        let mut libs = self.libs_only_l(name, &deferred, commands)?;
+        libs.retain(|lib| !kept_libs.contains(lib));
+        Ok(libs)
+    }
+
+    /// The libraries of `packages` and their requires, as `-l` arguments
+    /// without the `-l`, recording the command run in `commands`.
+    fn libs_only_l(&self, name: &str, packages: &[String], commands: &mut Vec<String>)
+                   -> Result<Vec<String>, Error> {
+        let mut args = vec!["--libs-only-l"];
+        if self.is_static(name) != Statik::No {
+            args.push("--static");
+        }
+        args.extend(packages.iter().map(|p| p.as_str()));
+        let mut cmd = self.base_command(name, &args);
+        let result = run(&mut cmd, self);
+        commands.push(command_line(&cmd));
+        let output = String::from_utf8_lossy(&result?).into_owned();
+        Ok(output.split_whitespace()
+                 .filter_map(|word| word.strip_prefix("-l"))
+                 .map(|lib| lib.to_string())
+                 .collect())
+    }
+
+    /// Whether a dependency of the crate being built has the `links` key
+    /// `links`, as far as the `DEP_<links>_*` variables cargo sets for those
+    /// printing metadata tell.
+    fn is_dependency(&self, links: &str) -> bool {
+        let prefix = format!("DEP_{}_", envify(links));
+        if self.ignores_env_var(&prefix) {
+            return false;
+        }
+        let names = env::vars_os().filter_map(|(key, _)| key.into_string().ok())
+                                  .filter(|key| key.starts_with(&prefix))
+                                  .collect::<Vec<_>>();
+        names.iter().any(|key| self.env_var_os(key).is_some())
+    }
+
+    /// The names of the packages `package` requires, including the private
+    /// ones when `name` is linked statically, recording the commands run in
+    /// `commands`.
+    fn print_requires(&self, name: &str, package: &str, commands: &mut Vec<String>)
+                      -> Result<Vec<String>, Error> {
+        let mut options = vec!["--print-requires"];
+        if self.is_static(name) != Statik::No {
+            options.push("--print-requires-private");
+        }
+        let mut requires = Vec::new();
+        for option in options {
+            let mut cmd = self.base_command(name, &[option, package]);
+            let result = run(&mut cmd, self);
+            commands.push(command_line(&cmd));
+            let output = String::from_utf8_lossy(&result?).into_owned();
+            // One `name [op version]` line per package.
+            requires.extend(output.lines().filter_map(|l| l.split_whitespace().next())
+                                          .map(|p| p.to_string()));
+        }
+        Ok(requires)
+    }
+
+    /// Query the variable `variable` of `name`, if it's defined, recording
+    /// the command run in `commands`.
+    fn query_variable(&self, name: &str, variable: &str, commands: &mut Vec<String>)
+                      -> Result<Option<PathBuf>, Error> {
+        let (value, _) = self.variable_output(name, variable, commands)?;
+        Ok(if value.is_empty() { None } else { Some(PathBuf::from(os_string(value))) })
+    }
+
+    /// Like `query_variable`, for a variable that must be text.
+    fn query_text_variable(&self, name: &str, variable: &str, commands: &mut Vec<String>)
+                           -> Result<Option<String>, Error> {
+        let (value, cmd) = self.variable_output(name, variable, commands)?;
+        Ok(if value.is_empty() { None } else { Some(utf8(value, &cmd)?) })
+    }
+
+    /// The output of `--variable=<variable>` for `name`, without the
+    /// trailing newline, and the command run, also recorded in `commands`.
+    fn variable_output(&self, name: &str, variable: &str, commands: &mut Vec<String>)
+                       -> Result<(Vec<u8>, Command), Error> {
+        let arg = format!("--variable={}", variable);
+        let mut cmd = self.base_command(name, &[&arg, name]);
+        let result = run(&mut cmd, self);
+        commands.push(command_line(&cmd));
+        Ok((trim_output(result?), cmd))
+    }
+
+    #[cfg(feature = "vendored-resolver")]
+    fn use_vendored_resolver(&self) -> bool {
+        self.vendored_resolver && self.env_var_os("PKG_CONFIG_NO_VENDORED").is_none()
+    }
+
+    #[cfg(feature = "vendored-resolver")]
+    fn vendored_query(&self, name: &str) -> Result<(Vec<u8>, String), Error> {
+        resolver::query(name, &self.constraints(name), &self.in_process_options(name))
+            .map_err(|failure| self.in_process_error(name, failure))
+    }
+
+    #[cfg(any(feature = "libpkgconf", feature = "vendored-resolver"))]
+    fn in_process_options(&self, name: &str) -> Options {
+        Options {
+            statik: self.is_static(name) != Statik::No,
+            pure: self.pure,
+            path: self.search_path_var(),
+            libdir: self.libdir_var(),
+            sysroot: self.sysroot_dir(),
+            system_libs: self.allow_system_libs(),
+            system_cflags: self.env_var_os("PKG_CONFIG_ALLOW_SYSTEM_CFLAGS").is_some(),
+            uninstalled: self.uninstalled_allowed(),
+            top_build_dir: match self.top_build_dir {
+                Some(ref dir) => Some(dir.to_string_lossy().into_owned()),
+                None => self.env_var("PKG_CONFIG_TOP_BUILD_DIR").ok(),
+            },
+            define_prefix: self.define_prefix.unwrap_or(cfg!(windows)),
+        }
+    }
+
+    #[cfg(any(feature = "libpkgconf", feature = "vendored-resolver"))]
+    fn in_process_error(&self, name: &str, failure: Failure) -> Error {
+        match failure {
+            Failure::VersionMismatch(found, message) => match self.check_version(name, &found) {
+                Err(err) => err,
+                Ok(()) => Error::InProcess {
+                    package: name.to_owned(),
+                    message,
+                    suggestions: Vec::new(),
+                },
+            },
+            Failure::Other(message) => Error::InProcess {
+                package: name.to_owned(),
+                suggestions: if message.contains("not found") {
+                    self.suggestions(name)
+                } else {
+                    Vec::new()
+                },
+                message,
+            },
+        }
+    }
+
+    /// Read the variables `pkg-config` is run with for `name`, so that
+    /// they're tracked even if the probe fails before running it, e.g.
+    /// because a failure is cached.
+    fn track_env(&self, name: &str) {
+        if !self.env_metadata || self.standalone {
+            return;
+        }
+        self.executable();
+        self.search_path_var();
+        self.libdir_var();
+        self.sysroot_dir();
+        self.is_static(name);
+    }
+
+    fn executable(&self) -> String {
+        self.env_var("PKG_CONFIG").unwrap_or_else(|_| String::from("pkg-config"))
+    }
+
+    /// A command running the `pkg-config` executable with `args` alone, in
+    /// the environment every query gets.
+    fn tool_command(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(self.executable());
+        if self.hermetic {
+            for (var, _) in env::vars_os() {
+                if var.to_string_lossy().contains("PKG_CONFIG") {
+                    cmd.env_remove(var);
+                }
+            }
+        }
+        cmd.args(args);
+        cmd
+    }
+
+    fn base_command(&self, name: &str, args: &[&str]) -> Command {
+        let mut cmd = self.tool_command(&[]);
+        if self.is_static(name) != Statik::No {
+            if !self.pure {
+                cmd.arg("--static");
+            } else if self.is_pkgconf() {
+                cmd.args(["--static", "--pure"]);
+            }
+        }
+        match self.define_prefix {
+            Some(true) if self.supports_define_prefix() => cmd.arg("--define-prefix"),
+            Some(false) if self.supports_define_prefix() => cmd.arg("--dont-define-prefix"),
+            _ => &mut cmd,
+        };
+        let queries = queries(args);
+        cmd.args(args)
+           .args(&self.extra_args)
+           .args(self.query_args.iter().filter(|a| queries.contains(&a.0)).map(|a| &a.1));
+
+        match self.search_path_var() {
+            Some(value) => cmd.env("PKG_CONFIG_PATH", value),
+            None if self.root.is_some() || self.search_mode == SearchMode::LibdirOnly => {
+                cmd.env_remove("PKG_CONFIG_PATH")
+            }
+            None => &mut cmd,
+        };
+        if let Some(value) = self.libdir_var() {
+            cmd.env("PKG_CONFIG_LIBDIR", value);
+        }
+        if let Some(value) = self.sysroot_dir() {
+            cmd.env("PKG_CONFIG_SYSROOT_DIR", value);
+        }
+        match self.disable_uninstalled {
+            Some(true) => cmd.env("PKG_CONFIG_DISABLE_UNINSTALLED", "1"),
+            Some(false) => cmd.env_remove("PKG_CONFIG_DISABLE_UNINSTALLED"),
+            None => &mut cmd,
+        };
+        if let Some(ref dir) = self.top_build_dir {
+            cmd.env("PKG_CONFIG_TOP_BUILD_DIR", dir);
+        }
+        match self.effective_system_libs() {
+            Some(value) => cmd.env("PKG_CONFIG_ALLOW_SYSTEM_LIBS", value),
+            None => cmd.env_remove("PKG_CONFIG_ALLOW_SYSTEM_LIBS"),
+        };
+        for &(var, ref dirs) in &[("PKG_CONFIG_SYSTEM_LIBRARY_PATH", self.system_library_dirs()),
+                                  ("PKG_CONFIG_SYSTEM_INCLUDE_PATH", self.system_include_dirs())] {
+            if let Some(ref dirs) = *dirs {
+                if let Ok(value) = env::join_paths(dirs) {
+                    cmd.env(var, value);
+                }
+            }
+        }
+        cmd
+    }
+
+    /// Whether `-L` flags for the system library directories are kept.
+    #[cfg(any(feature = "libpkgconf", feature = "vendored-resolver"))]
+    fn allow_system_libs(&self) -> bool {
+        self.effective_system_libs().is_some()
+    }
+
+    /// The C compiler for the target, `CC` or, when not cross compiling,
+    /// `cc`.
+    fn c_compiler(&self) -> Option<String> {
+        let target = self.target_triple().unwrap_or_default();
+        let cross = self.host_triple().is_some_and(|host| host != target);
+        match self.targetted_env_var("CC") {
+            Ok(cc) => Some(cc),
+            Err(_) if !cross => Some("cc".to_string()),
+            Err(_) => None,
+        }
+    }
+
+    /// Link a test program against `library`, see `try_link`.
+    fn try_link_library(&self, name: &str, library: &Library) -> Result<(), Error> {
+        let target = self.target_triple().unwrap_or_default();
+        let compiler = match self.c_compiler() {
+            Some(ref cc) if !target.contains("msvc") => cc.clone(),
+            _ => return Ok(()),
+        };
+        let dir = env::var_os("OUT_DIR").map(PathBuf::from).unwrap_or_else(env::temp_dir);
+        let dir = dir.join(format!("pkg-config-try-link-{}-{}", name, std::process::id()));
+        diagnose::try_link(name, library, &compiler, &dir)
+    }
+
+    /// The directories the C toolchain's linker searches by default,
+    /// canonicalized.
+    fn default_link_dirs(&self) -> Vec<PathBuf> {
+        let target = self.target_triple().unwrap_or_default();
+        let compiler = self.c_compiler();
+        let key = format!("{} {}", compiler.as_deref().unwrap_or(""), target);
+        let mut cache = DEFAULT_LINK_DIRS.lock().unwrap();
+        if let Some(entry) = cache.iter().find(|e| e.0 == key) {
+            return entry.1.clone();
+        }
+
+        let mut dirs = compiler.and_then(|cc| compiler_link_dirs(&cc)).unwrap_or_default();
+        if dirs.is_empty() {
+            dirs = target_link_dirs(&target);
+        }
+        let mut canonical: Vec<PathBuf> = Vec::new();
+        for dir in dirs {
+            let dir = dir.canonicalize().unwrap_or(dir);
+            if !canonical.contains(&dir) {
+                canonical.push(dir);
+            }
+        }
+        cache.push((key, canonical.clone()));
+        canonical
+    }
+
+    /// The default library search path, if set with `system_library_path`
+    /// or `PKG_CONFIG_SYSTEM_LIBRARY_PATH`.
+    fn system_library_dirs(&self) -> Option<Vec<PathBuf>> {
+        self.system_library_path.clone().or_else(|| {
+            if self.hermetic {
+                return Some(Vec::new());
+            }
+            let value = self.targetted_env_var("PKG_CONFIG_SYSTEM_LIBRARY_PATH").ok()?;
+            Some(parse_search_path(value.as_ref()))
+        })
+    }
+
+    /// The default include path, if set with `system_include_path` or
+    /// `PKG_CONFIG_SYSTEM_INCLUDE_PATH`.
+    fn system_include_dirs(&self) -> Option<Vec<PathBuf>> {
+        self.system_include_path.clone().or_else(|| {
+            if self.hermetic {
+                return Some(Vec::new());
+            }
+            let value = self.targetted_env_var("PKG_CONFIG_SYSTEM_INCLUDE_PATH").ok()?;
+            Some(parse_search_path(value.as_ref()))
+        })
+    }
+
+    /// The `PKG_CONFIG_PATH` to use, followed by the `extra_search_dirs`,
+    /// none when probing a root or with `SearchMode::LibdirOnly`.
+    fn search_path_var(&self) -> Option<String> {
+        match self.root {
+            Some(_) => None,
+            None if self.search_mode == SearchMode::LibdirOnly => None,
+            None => {
+                let value = self.targetted_env_var("PKG_CONFIG_PATH").ok();
+                let extra = self.extra_search_dirs();
+                if extra.is_empty() {
+                    return value;
+                }
+                let mut dirs = value.map(|v| parse_search_path(v.as_ref())).unwrap_or_default();
+                dirs.extend(extra);
+                env::join_paths(dirs).ok().map(|v| v.to_string_lossy().into_owned())
+            }
+        }
+    }
+
+    /// Whether paths are normalized, see `normalize_windows_paths`.
+    fn normalizes_paths(&self) -> bool {
+        self.normalize_windows_paths &&
+        self.target_triple().is_some_and(|t| t.contains("-windows"))
+    }
+
+    /// The Windows path of the MSYS2 root, if the output of `pkg-config`
+    /// is to be translated from MSYS2 paths.
+    fn msys_root(&self) -> Option<String> {
+        if !self.target_triple().is_some_and(|t| t.contains("-windows-gnu")) {
+            return None;
+        }
+        self.env_var_os("MSYSTEM")?;
+        match self.env_var("PKG_CONFIG_MSYS_ROOT") {
+            Ok(root) => Some(root),
+            Err(_) => msys::root(),
+        }
+    }
+
+    /// The directories searched after those of `PKG_CONFIG_PATH`.
+    fn extra_search_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = self.fallback_paths.clone();
+        let macos = match self.target_triple() {
+            Some(target) => target.contains("-apple-darwin"),
+            None => cfg!(target_os = "macos"),
+        };
+        if self.macos_package_managers && macos {
+            let prefix = self.env_var_os("HOMEBREW_PREFIX").map(PathBuf::from);
+            if let Some(prefix) = brew::homebrew_prefix(prefix) {
+                dirs.extend(brew::homebrew_dirs(&prefix));
+            }
+            dirs.extend(brew::macports_dirs());
+        }
+        dirs
+    }
+
+    /// The `PKG_CONFIG_LIBDIR` to use, the `pkgconfig` directories of the
+    /// root being probed, if any, or else of the `sysroot` unless the
+    /// variable is set. With `SearchMode::PathThenLibdir`, it's empty rather
+    /// than unset, which disables the default search path.
+    fn libdir_var(&self) -> Option<String> {
+        match self.root {
+            Some(ref root) => {
+                let dirs = ["lib/pkgconfig", "lib64/pkgconfig", "share/pkgconfig"];
+                let dirs = env::join_paths(dirs.iter().map(|d| root.join(d))).ok()?;
+                Some(dirs.to_string_lossy().into_owned())
+            }
+            None => match self.targetted_env_var("PKG_CONFIG_LIBDIR") {
+                Ok(value) => Some(value),
+                Err(_) if self.sysroot.is_some() => {
+                    let target = self.target_triple();
+                    let dirs = sysroot_pkgconfig_dirs(self.sysroot.as_ref()?, target.as_deref());
+                    let dirs = env::join_paths(dirs).ok()?;
+                    Some(dirs.to_string_lossy().into_owned())
+                }
+                Err(_) if self.search_mode == SearchMode::PathThenLibdir => Some(String::new()),
+                Err(_) => None,
+            },
+        }
+    }
+
+    /// The sysroot of the target, set with `sysroot` or
+    /// `PKG_CONFIG_SYSROOT_DIR`.
+    fn sysroot_dir(&self) -> Option<String> {
+        match self.sysroot {
+            Some(ref sysroot) => Some(sysroot.to_string_lossy().into_owned()),
+            None => self.targetted_env_var("PKG_CONFIG_SYSROOT_DIR").ok(),
+        }
+    }
+
+    /// Problems with how `PKG_CONFIG_PATH` and `PKG_CONFIG_LIBDIR` are set,
+    /// given the search mode.
+    fn search_path_warnings(&self) -> Vec<String> {
+        if self.root.is_some() || self.hermetic {
+            return Vec::new();
+        }
+        let path = self.targetted_env_var("PKG_CONFIG_PATH").ok();
+        let libdir = self.targetted_env_var("PKG_CONFIG_LIBDIR").ok();
+        let dirs = |value: &Option<String>| {
+            value.as_ref().map(|v| parse_search_path(v.as_ref())).unwrap_or_default()
+        };
+        let (path_dirs, libdir_dirs) = (dirs(&path), dirs(&libdir));
+
+        let mut warnings = Vec::new();
+        match self.search_mode {
+            SearchMode::LibdirOnly if path.is_some() => {
+                warnings.push("PKG_CONFIG_PATH is set but ignored, as the search mode is \
+                               LibdirOnly".to_string());
+            }
+            SearchMode::PathThenLibdir if path_dirs.is_empty() && libdir_dirs.is_empty() => {
+                warnings.push("neither PKG_CONFIG_PATH nor PKG_CONFIG_LIBDIR is set, so no \
+                               directories are searched with the PathThenLibdir search mode"
+                              .to_string());
+            }
+            _ => {}
+        }
+        if self.search_mode != SearchMode::LibdirOnly {
+            for dir in path_dirs.iter().filter(|d| libdir_dirs.contains(d)) {
+                warnings.push(format!("{} is in both PKG_CONFIG_PATH and PKG_CONFIG_LIBDIR",
+                                      dir.display()));
+            }
+        }
+        warnings.into_iter().map(|w| format!("pkg-config: {}", w)).collect()
+    }
+
+    fn print_package_manager_state(&self) {
+        if self.rerun_on_system_packages {
+            let homebrew_prefix = self.env_var_os("HOMEBREW_PREFIX").map(PathBuf::from);
+            for path in package_manager_state(Path::new("/"), homebrew_prefix.as_deref()) {
+                self.print_metadata(&format!("rerun-if-changed={}", path.display()));
+            }
+        }
+    }
+
+    fn print_metadata(&self, s: &str) {
+        if !self.cargo_metadata || self.standalone {
+            return;
+        }
+        self.emit_trace(&Trace::Metadata(s));
+        if self.defer_metadata {
+            let mut emitter = METADATA.lock().unwrap();
+            let Emitter { ref mut pending, ref emitted } = *emitter;
+            if !pending.iter().map(|p| &p.0).chain(emitted).any(|m| m == s) {
+                pending.push((s.to_string(), self.metadata_file()));
+            }
+        } else {
+            print_cargo(s);
+        }
+    }
+
+    fn emit_trace(&self, event: &Trace) {
+        if let Some(ref trace) = self.trace {
+            trace(event);
+        }
+        if env::var_os("PKG_CONFIG_TRACE").is_some() {
+            eprintln!("pkg-config: {}", event);
+        }
+    }
+
+    /// The mode the environment variables described in the crate
+    /// documentation choose for the package `name`, if any of them is set.
+    ///
+    /// This is what a probe uses unless `statik` was called or
+    /// `statik_env_override(false)` ignores the environment, e.g. to warn
+    /// that a requested static link won't happen.
+    pub fn statik_from_env(&self, name: &str) -> Option<Statik> {
+        let name = envify(name);
+        if self.env_var_os(&format!("{}_STATIC_FORCE", name)).is_some() {
+            Some(Statik::Force)
+        } else if let Some(value) = self.env_var_os(&format!("{}_STATIC", name)) {
+            Some(parse_statik(&value))
+        } else if self.env_var_os(&format!("{}_DYNAMIC", name)).is_some() {
+            Some(Statik::No)
+        } else if let Some(value) = self.env_var_os("PKG_CONFIG_ALL_STATIC") {
+            Some(parse_statik(&value))
+        } else if self.env_var_os("PKG_CONFIG_ALL_DYNAMIC").is_some() {
+            Some(Statik::No)
+        } else {
+            None
+        }
+    }
+}
+
+impl Library {
+    fn new() -> Library {
+        Library {
+            libs: Vec::new(),
+            link_paths: Vec::new(),
+            include_paths: Vec::new(),
+            system_include_paths: Vec::new(),
+            quote_include_paths: Vec::new(),
+            after_include_paths: Vec::new(),
+            frameworks: Vec::new(),
+            framework_paths: Vec::new(),
+            weak_frameworks: Vec::new(),
+            needed_frameworks: Vec::new(),
+            defines: HashMap::new(),
+            compile_flags: Vec::new(),
+            version: String::new(),
+            static_libs: Vec::new(),
+            whole_archive: Vec::new(),
+            verbatim_libs: Vec::new(),
+            pthread: None,
+            probe_commands: Vec::new(),
+            warnings: Vec::new(),
+            linkage: HashMap::new(),
+            prefix: None,
+            exec_prefix: None,
+            libdir: None,
+            includedir: None,
+            variables: HashMap::new(),
+            sysroot: None,
+            package: None,
+            pkg_config: None,
+            pc_path: None,
+            virtual_package: false,
+            _priv: (),
+        }
+    }
+
+    /// Make a `Library` out of the results of another way of finding it, e.g.
+    /// a vendored build, for `Config::or_else`.
+    ///
+    /// The other fields start out empty and can be set afterwards, e.g.
+    /// `static_libs` for libraries built as static archives.
+    pub fn from_parts(libs: Vec<String>, link_paths: Vec<PathBuf>,
+                      include_paths: Vec<PathBuf>, version: &str) -> Library {
+        let mut library = Library::new();
+        library.libs = libs;
+        library.link_paths = link_paths;
+        library.include_paths = include_paths;
+        library.version = version.to_string();
+        library
+    }
+
+    /// Add `lib`, passed with `-l` after the `-L` directories `dirs`, to
+    /// `libs`, linked as `links_statically` decides.
+    fn push_lib(&mut self, lib: &str, statik: &Statik, dirs: &[PathBuf], patterns: &[String],
+                config: &Config) -> Linkage {
+        let linkage = if config.links_statically(statik, lib, dirs, patterns) {
+            Linkage::Static
+        } else {
+            Linkage::Dynamic
+        };
+        self.linkage.insert(lib.to_string(), linkage);
+        if linkage == Linkage::Static {
+            let whole_archive = config.whole_archive.iter().any(|s| s == lib) &&
+                                (config.standalone ||
+                                 rustc_minor_version().is_some_and(|v| v >= 61));
+            if whole_archive {
+                self.whole_archive.push(lib.to_string());
+            }
+            self.static_libs.push(lib.to_string());
+            // rustc only searches the `-L` directories, so those `Probe`
+            // found the archive in must be passed.
+            if *statik == Statik::Probe && find_archive(lib, dirs, patterns).is_none() {
+                let dir = find_archive(lib, &config.default_link_dirs(), patterns)
+                    .and_then(|path| path.parent().map(Path::to_path_buf));
+                if let Some(dir) = dir {
+                    if !self.link_paths.contains(&dir) {
+                        self.link_paths.push(dir);
+                    }
+                }
+            }
+        }
+        self.libs.push(lib.to_string());
+        linkage
+    }
+
+    fn parse_libs_cflags(&mut self, name: &str, output: &[u8], config: &Config)
+                         -> Result<(), Error> {
+        let mut is_msvc = false;
+        if let Some(target) = config.target_triple() {
+            if target.contains("msvc") {
+                is_msvc = true;
+            }
+        }
+        let msys_root = config.msys_root();
+        let native = |path: PathBuf| match msys_root {
+            Some(ref root) => msys::to_windows(&path.to_string_lossy(), root),
+            None => path,
+        };
+
+        let mut raw_words = join_include_flags(config.filter_fragments(split_flags(output)));
+        if config.pure {
+            raw_words = dedup_libs(raw_words);
+        }
+        // Paths are taken from the words as printed, anything else must be
+        // text, e.g. library names and defines.
+        if let Some(word) = raw_words.iter().find(|w| w.to_str().is_none() && !path_flag(w)) {
+            return Err(Error::InvalidOutput {
+                command: self.probe_commands.last().cloned().unwrap_or_else(|| name.to_string()),
+                output: word.to_string_lossy().into_owned(),
+            });
+        }
+        let words = raw_words.iter()
+                             .map(|w| w.to_string_lossy().into_owned())
+                             .collect::<Vec<_>>();
+        config.emit_trace(&Trace::Flags { package: name, flags: &words });
+        for word in &raw_words {
+            let flag = include_flag(word).map(|(flag, dir)| (flag, native(dir)));
+            match flag {
+                Some((IncludeFlag::System, dir)) => self.system_include_paths.push(dir),
+                Some((IncludeFlag::Quote, dir)) => self.quote_include_paths.push(dir),
+                Some((IncludeFlag::After, dir)) => self.after_include_paths.push(dir),
+                None => {}
+            }
+        }
+        // Options taking their argument as the next word are kept with it.
+        let mut i = 0;
+        while i < words.len() {
+            let word = &words[i][..];
+            let len = if FRAMEWORK_FLAGS.contains(&word) || ARGUMENT_FLAGS.contains(&word) {
+                cmp::min(2, words.len() - i)
+            } else {
+                1
+            };
+            let flags = &words[i..i + len];
+            i += len;
+            if compile_flag(word) && !self.compile_flags.windows(len).any(|f| f == flags) {
+                self.compile_flags.extend(flags.iter().cloned());
+            }
+        }
+        let parts = words.iter()
+                         .zip(&raw_words)
+                         .filter(|&(word, _)| word.len() > 2)
+                         .filter_map(|(word, raw)| Some((word.get(..2)?, &word[2..], raw)))
+                         .collect::<Vec<_>>();
+
+        let mut dirs = Vec::new();
+        let statik = config.is_static(name);
+        let patterns = config.static_archive_patterns();
+        let system_library_dirs = config.system_library_dirs().unwrap_or_default();
+        let system_include_dirs = config.system_include_dirs().unwrap_or_default();
+        for &(flag, val, raw) in &parts {
+            let path = || PathBuf::from(strip_flag(raw, 2));
+            match flag {
+                "-L" => {
+                    dirs.push(native(path()));
+                    if !system_library_dirs.iter().any(|d| *d == path()) {
+                        self.link_paths.push(native(path()));
+                    }
+                }
+                "-F" => {
+                    self.framework_paths.push(native(path()));
+                }
+                "-I" if !system_include_dirs.iter().any(|d| *d == path()) => {
+                    self.include_paths.push(native(path()));
+                }
+                "-l" if val.starts_with(':') => {
+                    let file = &val[1..];
+                    let linkage = if file.ends_with(".a") || file.ends_with(".lib") {
+                        Linkage::Static
+                    } else {
+                        Linkage::Dynamic
+                    };
+                    self.linkage.insert(file.to_string(), linkage);
+                    self.verbatim_libs.push(file.to_string());
+                }
+                "-l" => {
+                    // These are provided by the CRT with MSVC
+                    if is_msvc && ["m", "c", "pthread"].contains(&val) {
+                        self.linkage.insert(val.to_string(), Linkage::Skipped);
+                        continue;
+                    }
+                    self.push_lib(val, &statik, &dirs, &patterns, config);
+                }
+                "-D" => {
+                    let (name, value) = parse_define(val);
+                    self.defines.insert(name, value);
+                }
+                _ => {}
+            }
+        }
+        // Linkers only link `libpthread` for `-pthread` if they're given it,
+        // which rustc doesn't do.
+        if words.iter().any(|w| w == "-pthread") {
+            let links = match config.target_triple() {
+                Some(ref target) => links_pthread(target),
+                None => cfg!(any(all(unix, not(any(target_vendor = "apple",
+                                                   target_os = "android"))),
+                                 all(windows, target_env = "gnu"))),
+            };
+            let linkage = match self.linkage.get("pthread") {
+                Some(&linkage) => linkage,
+                None if !links => Linkage::Skipped,
+                None => self.push_lib("pthread", &statik, &dirs, &patterns, config),
+            };
+            self.linkage.entry("pthread".to_string()).or_insert(linkage);
+            self.pthread = Some(linkage);
+        }
+
+        let mut iter = words.iter()
+                            .flat_map(|arg| if let Some(arg) = arg.strip_prefix("-Wl,") {
+                                 arg.split(',').collect()
+                             } else {
+                                 vec![arg.as_ref()]
+                             });
+        while let Some(part) = iter.next() {
+            match part {
+                "-framework" => {
+                    if let Some(lib) = iter.next() {
+                        self.frameworks.push(lib.to_string());
+                    }
+                }
+                "-weak_framework" => {
+                    if let Some(lib) = iter.next() {
+                        self.weak_frameworks.push(lib.to_string());
+                    }
+                }
+                "-needed_framework" => {
+                    if let Some(lib) = iter.next() {
+                        self.needed_frameworks.push(lib.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Decide again how each library of `libs` is linked, once a
+    /// postprocessor changed them or the directories they're found in.
+    fn relink(&mut self, config: &Config) {
+        let statik = config.is_static(self.package.as_deref().unwrap_or_default());
+        let patterns = config.static_archive_patterns();
+        let mut dirs = self.link_paths.clone();
+        dirs.extend(config.system_library_dirs().unwrap_or_default());
+        self.static_libs.clear();
+        self.whole_archive.clear();
+        for lib in std::mem::take(&mut self.libs) {
+            self.push_lib(&lib, &statik, &dirs, &patterns, config);
+        }
+    }
+
+    /// Normalize all paths as Windows paths, see
+    /// `Config::normalize_windows_paths`.
+    fn normalize_paths(&mut self) {
+        for paths in [&mut self.link_paths, &mut self.framework_paths, &mut self.include_paths,
+                      &mut self.system_include_paths, &mut self.quote_include_paths,
+                      &mut self.after_include_paths] {
+            for path in paths.iter_mut() {
+                *path = msys::normalize(path);
+            }
+        }
+        for path in [&mut self.prefix, &mut self.exec_prefix, &mut self.libdir,
+                     &mut self.includedir] {
+            if let Some(ref mut path) = *path {
+                *path = msys::normalize(path);
+            }
+        }
+    }
+
+    fn parse_modversion(&mut self, output: &str) {
+        self.version.push_str(output.trim());
+    }
+
+    /// Print the cargo metadata to link against this library, the way a probe
+    /// with `config` does.
+    ///
+    /// This is meant for libraries made with `library::Builder` or
+    /// `Library::from_parts`; probes print their metadata already.
+    pub fn emit_cargo_metadata(&self, config: &Config) {
+        rerun_first(config, || self.print_cargo_metadata(config))
+    }
+
+    fn print_cargo_metadata(&self, config: &Config) {
+        // rustc looks for static archives in the `-L` directories only.
+        let default_dirs = if !config.drop_system_link_paths || !config.cargo_metadata ||
+                              config.standalone || self.link_paths.is_empty() ||
+                              !self.static_libs.is_empty() {
+            Vec::new()
+        } else {
+            config.default_link_dirs()
+        };
+        let display = |path: &PathBuf| if config.normalizes_paths() {
+            msys::normalize(path).display().to_string()
+        } else {
+            path.display().to_string()
+        };
+        for path in &self.link_paths {
+            if !default_dirs.is_empty() &&
+               default_dirs.contains(&path.canonicalize().unwrap_or_else(|_| path.clone())) {
+                continue;
+            }
+            config.print_metadata(&format!("rustc-link-search=native={}", display(path)));
+        }
+        for path in &self.framework_paths {
+            config.print_metadata(&format!("rustc-link-search=framework={}", display(path)));
+        }
+        for lib in &self.libs {
+            let meta = if self.whole_archive.contains(lib) {
+                format!("rustc-link-lib=static:+whole-archive={}", lib)
+            } else if self.static_libs.contains(lib) {
+                format!("rustc-link-lib=static={}", lib)
+            } else {
+                format!("rustc-link-lib={}", lib)
+            };
+            config.print_metadata(&meta);
+        }
+        // The `+verbatim` modifier is stable since Rust 1.67; before, the
+        // flag is passed straight to the linker.
+        let verbatim = !self.verbatim_libs.is_empty() &&
+                       (config.standalone || rustc_minor_version().is_some_and(|v| v >= 67));
+        for file in &self.verbatim_libs {
+            let meta = if !verbatim {
+                format!("rustc-link-arg=-l:{}", file)
+            } else if self.linkage.get(file) == Some(&Linkage::Static) {
+                format!("rustc-link-lib=static:+verbatim={}", file)
+            } else {
+                format!("rustc-link-lib=dylib:+verbatim={}", file)
+            };
+            config.print_metadata(&meta);
+        }
+        for framework in &self.frameworks {
+            config.print_metadata(&format!("rustc-link-lib=framework={}", framework));
+        }
+        // rustc has no link modifiers for weak and needed frameworks, so these
+        // are passed straight to the linker. Note that Cargo only applies
+        // `rustc-link-arg` to the targets of the package being built.
+        for framework in &self.weak_frameworks {
+            config.print_metadata(&format!("rustc-link-arg=-Wl,-weak_framework,{}", framework));
+        }
+        for framework in &self.needed_frameworks {
+            config.print_metadata(&format!("rustc-link-arg=-Wl,-needed_framework,{}", framework));
+        }
+        for define in &config.cfg_defines {
+            config.print_metadata(&format!("rustc-check-cfg=cfg({})", define));
+            if self.defines.contains_key(define) {
+                config.print_metadata(&format!("rustc-cfg={}", define));
+            }
+        }
+    }
+
+    /// Print the link paths and library names as `cargo:<prefix>_lib_paths`
+    /// and `cargo:<prefix>_libs` metadata, for downstream crates that need
+    /// to run against the library at build time.
+    ///
+    /// The paths are joined like `PATH` and the names with commas. Cargo
+    /// passes them on as `DEP_<links>_<PREFIX>_LIB_PATHS` and
+    /// `DEP_<links>_<PREFIX>_LIBS` to the build scripts of dependents, so the
+    /// package must set `links` in its manifest. They're printed the way a
+    /// probe with `config` prints its metadata.
+    ///
+    /// Fails without printing anything if a path contains the separator.
+    pub fn emit_dep_paths(&self, config: &Config, prefix: &str)
+                          -> Result<(), env::JoinPathsError> {
+        let paths = env::join_paths(&self.link_paths)?;
+        rerun_first(config, || {
+            config.print_metadata(&format!("{}_lib_paths={}", prefix, paths.to_string_lossy()));
+            config.print_metadata(&format!("{}_libs={}", prefix, self.libs.join(",")));
+        });
+        Ok(())
+    }
+
+    /// Whether the package resolved to libraries to link, only frameworks,
+    /// or neither, e.g. because it's header-only.
+    pub fn kind(&self) -> LibraryKind {
+        if !self.libs.is_empty() || !self.verbatim_libs.is_empty() {
+            LibraryKind::Libs
+        } else if !self.frameworks.is_empty() || !self.weak_frameworks.is_empty() ||
+                  !self.needed_frameworks.is_empty() {
+            LibraryKind::FrameworksOnly
+        } else {
+            LibraryKind::HeaderOnly
+        }
+    }
+
+    /// The name of the package found, e.g. the alternative that matched for
+    /// `Config::probe_any`. `None` if the library wasn't probed, e.g. made
+    /// with `Library::from_parts`.
+    pub fn name(&self) -> Option<&str> {
+        self.package.as_deref()
+    }
+
+    /// Whether this is a builtin package of `pkg-config` rather than one of
+    /// a `.pc` file, e.g. `pkg-config` or `pkgconf` to check the version of
+    /// the tool. Such packages have no flags, variables or artifacts, and
+    /// aren't checked by `Config::warnings` or `Config::standard_variables`.
+    pub fn is_virtual(&self) -> bool {
+        self.virtual_package
+    }
+
+    /// The `-I` include paths followed by the `-isystem` ones, in the order
+    /// the compiler searches them.
+    pub fn include_paths_with_system(&self) -> Vec<PathBuf> {
+        self.include_paths.iter()
+                          .chain(&self.system_include_paths)
+                          .cloned()
+                          .collect()
+    }
+
+    /// Resolve each of `libs` to the file the linker would pick up from
+    /// `link_paths`.
+    ///
+    /// Libraries that can't be found in `link_paths`, e.g. because they live
+    /// in the linker's default search path, are skipped.
+    pub fn artifacts(&self) -> Vec<Artifact> {
+        self.libs.iter()
+                 .filter_map(|lib| Artifact::resolve(lib, &self.link_paths))
+                 .collect()
+    }
+
+    /// The runtime dependencies of the shared libraries among `artifacts()`,
+    /// i.e. the sonames they list as `DT_NEEDED`, without duplicates.
+    ///
+    /// This is useful to generate bundling or install manifests.
+    #[cfg(feature = "elf")]
+    pub fn runtime_dependencies(&self) -> Vec<String> {
+        let mut needed = Vec::new();
+        for artifact in self.artifacts() {
+            for name in artifact.needed() {
+                if !needed.contains(&name) {
+                    needed.push(name);
+                }
+            }
+        }
+        needed
+    }
+
+    /// Whether any of `artifacts()` defines the symbol `symbol`, e.g. to
+    /// check that a system library was built with an optional feature before
+    /// committing to it.
+    ///
+    /// Returns `None` if none of them could be checked, e.g. because the
+    /// libraries are in the linker's default search path rather than
+    /// `link_paths`, or are shared libraries in a format other than ELF.
+    /// Symbols are matched by their exact name in the object files, which
+    /// on macOS and 32-bit Windows starts with an underscore.
+    ///
+    /// Static archives are always checked, shared libraries only with the
+    /// `elf` feature.
+    pub fn has_symbol(&self, symbol: &str) -> Option<bool> {
+        let mut checked = false;
+        for artifact in self.artifacts() {
+            if let Some(symbols) = artifact.symbols() {
+                if symbols.iter().any(|s| s == symbol) {
+                    return Some(true);
+                }
+                checked = true;
+            }
         }
+        if checked { Some(false) } else { None }
     }
 
-    fn env_var(&self, name: &str) -> Result<String, env::VarError> {
-        if self.env_metadata {
-            println!("cargo:rerun-if-env-changed={}", name);
+    /// Returns the compiler arguments needed to build `lang` sources against
+    /// this library, shaped for the `arguments` of a `compile_commands.json`
+    /// entry (the compiler and source file are left to the caller).
+    ///
+    /// Defines are sorted by name so that the output is stable.
+    pub fn compile_args(&self, lang: Language) -> Vec<String> {
+        let mut args = vec!["-x".to_string()];
+        args.push(match lang {
+            Language::C => "c",
+            Language::Cxx => "c++",
+        }.to_string());
+
+        for path in &self.quote_include_paths {
+            args.push("-iquote".to_string());
+            args.push(path.display().to_string());
+        }
+        for path in &self.include_paths {
+            args.push(format!("-I{}", path.display()));
+        }
+        for path in &self.system_include_paths {
+            args.push("-isystem".to_string());
+            args.push(path.display().to_string());
+        }
+        for path in &self.after_include_paths {
+            args.push("-idirafter".to_string());
+            args.push(path.display().to_string());
         }
-        env::var(name)
-    }
 
-    fn env_var_os(&self, name: &str) -> Option<OsString> {
-        if self.env_metadata {
-            println!("cargo:rerun-if-env-changed={}", name);
+        let mut defines = self.defines.iter().collect::<Vec<_>>();
+        defines.sort();
+        for (name, value) in defines {
+            match *value {
+                Some(ref value) => args.push(format!("-D{}={}", name, value)),
+                None => args.push(format!("-D{}", name)),
+            }
         }
-        env::var_os(name)
+        args.extend(self.compile_flags.iter().cloned());
+        args
     }
 
-    fn is_static(&self, name: &str) -> Statik {
-        if self.statik_blacklist_contains(name) {
-            Statik::No
-        } else {
-            match self.statik {
-                Some(ref statik) => statik.clone(),
-                None => self.infer_static(name),
+    /// Returns the arguments for clang, e.g. for `bindgen::Builder::clang_args`,
+    /// to parse headers of this library: include paths, defines and, if the
+    /// probe used `PKG_CONFIG_SYSROOT_DIR`, `--sysroot`.
+    #[cfg(feature = "bindgen")]
+    pub fn clang_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(ref sysroot) = self.sysroot {
+            args.push(format!("--sysroot={}", sysroot.display()));
+        }
+        // The include paths and defines of `compile_args`, without the
+        // language, which bindgen picks, or the other compile flags, which
+        // may be meant for GCC only.
+        let compile_args = self.compile_args(Language::C);
+        let end = compile_args.len() - self.compile_flags.len();
+        let mut words = compile_args.into_iter().take(end).skip(2);
+        while let Some(word) = words.next() {
+            match &word[..] {
+                "-iquote" | "-isystem" | "-idirafter" => {
+                    args.push(word + &words.next().unwrap_or_default());
+                }
+                _ => args.push(word),
             }
         }
+        args
     }
 
-    fn command(&self, name: &str, args: &[&str]) -> Command {
-        let exe = self.env_var("PKG_CONFIG").unwrap_or_else(|_| String::from("pkg-config"));
-        let mut cmd = Command::new(exe);
-        if self.is_static(name) != Statik::No {
-            cmd.arg("--static");
+    /// Returns the `-D` arguments for `cmake`, e.g. for `cmake::Config::arg`,
+    /// to make a CMake build find this library, as found by the find module
+    /// or config file whose variables start with `prefix` (e.g. `ZLIB`).
+    ///
+    /// These are `CMAKE_PREFIX_PATH`, the installation prefixes of the
+    /// library, `<prefix>_INCLUDE_DIR`, its first include path, and
+    /// `<prefix>_LIBRARY`, the file its first library resolves to (see
+    /// `artifacts`). Those that can't be determined are left out.
+    pub fn to_cmake_args(&self, prefix: &str) -> Vec<String> {
+        let mut prefixes = Vec::new();
+        match self.prefix {
+            Some(ref prefix) => prefixes.push(prefix.clone()),
+            None => {
+                // The parents of the `lib` and `include` directories.
+                for path in self.link_paths.iter().chain(&self.include_paths) {
+                    let parent = path.ancestors()
+                                     .find(|a| a.ends_with("lib") || a.ends_with("lib64") ||
+                                               a.ends_with("include"))
+                                     .and_then(Path::parent);
+                    if let Some(parent) = parent {
+                        if !prefixes.iter().any(|p| p == parent) {
+                            prefixes.push(parent.to_path_buf());
+                        }
+                    }
+                }
+            }
         }
-        cmd.args(args)
-           .args(&self.extra_args);
 
-        if let Ok(value) = self.targetted_env_var("PKG_CONFIG_PATH") {
-            cmd.env("PKG_CONFIG_PATH", value);
-        }
-        if let Ok(value) = self.targetted_env_var("PKG_CONFIG_LIBDIR") {
-            cmd.env("PKG_CONFIG_LIBDIR", value);
-        }
-        if let Ok(value) = self.targetted_env_var("PKG_CONFIG_SYSROOT_DIR") {
-            cmd.env("PKG_CONFIG_SYSROOT_DIR", value);
+        let mut args = Vec::new();
+        if !prefixes.is_empty() {
+            let prefixes = prefixes.iter().map(|p| p.display().to_string()).collect::<Vec<_>>();
+            args.push(format!("-DCMAKE_PREFIX_PATH={}", prefixes.join(";")));
         }
-        if self.print_system_libs {
-            cmd.env("PKG_CONFIG_ALLOW_SYSTEM_LIBS", "1");
+        if let Some(path) = self.include_paths.first() {
+            args.push(format!("-D{}_INCLUDE_DIR={}", prefix, path.display()));
         }
-        if let Some(ref version) = self.atleast_version {
-            cmd.arg(&format!("{} >= {}", name, version));
-        } else {
-            cmd.arg(name);
+        if let Some(artifact) = self.artifacts().first() {
+            args.push(format!("-D{}_LIBRARY={}", prefix, artifact.path.display()));
         }
-        cmd
+        args
     }
 
-    fn print_metadata(&self, s: &str) {
-        if self.cargo_metadata {
-            println!("cargo:{}", s);
-        }
+    /// Returns a record of this library for auditing: the package, its
+    /// version and flags, how each library is linked and the `pkg-config`
+    /// run to find it.
+    #[cfg(feature = "serde")]
+    pub fn report(&self) -> Report {
+        Report::new(self)
     }
 
-    fn infer_static(&self, name: &str) -> Statik {
-        let name = envify(name);
-        if self.env_var_os(&format!("{}_STATIC_FORCE", name)).is_some() {
-            Statik::Force
-        } else if self.env_var_os(&format!("{}_STATIC", name)).is_some() {
-            Statik::Yes
-        } else if self.env_var_os(&format!("{}_DYNAMIC", name)).is_some() {
-            Statik::No
-        } else if self.env_var_os("PKG_CONFIG_ALL_STATIC").is_some() {
-            Statik::Yes
-        } else if self.env_var_os("PKG_CONFIG_ALL_DYNAMIC").is_some() {
-            Statik::No
-        } else {
-            Statik::No
+    /// Returns `report` as JSON, e.g. to write into `OUT_DIR`.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        self.report().to_json()
+    }
+
+    /// Write this library as JSON to `path`, e.g. `OUT_DIR/foo-pkgconfig.json`,
+    /// for `read_json` to read back in another build script or a program
+    /// run at build time.
+    ///
+    /// Unlike `to_json`, every field is kept, so that the library read back
+    /// is the one probed, without rerunning `pkg-config` in the environment
+    /// that found it. Paths that aren't valid Unicode can't be written.
+    /// Maps are written sorted, so that the same library gives the same
+    /// file.
+    #[cfg(feature = "serde")]
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    /// Read a library written with `write_json`.
+    ///
+    /// A file that isn't such JSON is an `InvalidData` error.
+    #[cfg(feature = "serde")]
+    pub fn read_json<P: AsRef<Path>>(path: P) -> io::Result<Library> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Apply the include paths, defines and other compile flags of this
+    /// library to `build`.
+    ///
+    /// Defines are passed as `-D<name>=<value>`, exactly as they appeared in
+    /// the output of `pkg-config`.
+    #[cfg(feature = "cc")]
+    pub fn apply_to(&self, build: &mut cc::Build) {
+        apply_includes(build, &self.quote_include_paths, &self.include_paths,
+                       &self.system_include_paths, &self.after_include_paths);
+        apply_defines(build, &self.defines);
+        for flag in &self.compile_flags {
+            build.flag(flag);
         }
     }
 }
 
-impl Library {
-    fn new() -> Library {
-        Library {
-            libs: Vec::new(),
-            link_paths: Vec::new(),
+impl Includes {
+    /// Apply the include paths and defines of this package to `build`, see
+    /// `Library::apply_to`.
+    #[cfg(feature = "cc")]
+    pub fn apply_to(&self, build: &mut cc::Build) {
+        apply_includes(build, &self.quote_include_paths, &self.include_paths,
+                       &self.system_include_paths, &self.after_include_paths);
+        apply_defines(build, &self.defines);
+    }
+
+    fn new() -> Includes {
+        Includes {
             include_paths: Vec::new(),
-            frameworks: Vec::new(),
-            framework_paths: Vec::new(),
+            system_include_paths: Vec::new(),
+            quote_include_paths: Vec::new(),
+            after_include_paths: Vec::new(),
             defines: HashMap::new(),
             version: String::new(),
             _priv: (),
         }
     }
 
-    fn parse_libs_cflags(&mut self, name: &str, output: &[u8], config: &Config) {
-        let mut is_msvc = false;
-        if let Ok(target) = env::var("TARGET") {
-            if target.contains("msvc") {
-                is_msvc = true;
+    fn parse_cflags(&mut self, output: &[u8], config: &Config) {
+        let words = join_include_flags(config.filter_fragments(split_flags(output)));
+        let system_include_dirs = config.system_include_dirs().unwrap_or_default();
+        for raw in words.iter().filter(|l| l.len() > 2) {
+            match include_flag(raw) {
+                Some((IncludeFlag::System, dir)) => self.system_include_paths.push(dir),
+                Some((IncludeFlag::Quote, dir)) => self.quote_include_paths.push(dir),
+                Some((IncludeFlag::After, dir)) => self.after_include_paths.push(dir),
+                None => {}
+            }
+            let arg = raw.to_string_lossy();
+            let path = PathBuf::from(strip_flag(raw, 2));
+            match arg.get(..2).unwrap_or("") {
+                "-I" if !system_include_dirs.contains(&path) => self.include_paths.push(path),
+                "-D" => {
+                    let (name, value) = parse_define(&arg[2..]);
+                    self.defines.insert(name, value);
+                }
+                _ => {}
             }
         }
+    }
+}
 
-        let words = split_flags(output);
-        let parts = words.iter()
-                          .filter(|l| l.len() > 2)
-                          .map(|arg| (&arg[0..2], &arg[2..]))
-                          .collect::<Vec<_>>();
+/// Remove all but the last occurrence of each `-l` flag.
+fn dedup_libs<W: AsRef<OsStr> + Clone + PartialEq>(words: Vec<W>) -> Vec<W> {
+    let mut deduped = Vec::with_capacity(words.len());
+    for (i, word) in words.iter().enumerate() {
+        if word.as_ref().to_string_lossy().starts_with("-l") && words[i + 1..].contains(word) {
+            continue;
+        }
+        deduped.push(word.clone());
+    }
+    deduped
+}
 
-        let mut dirs = Vec::new();
-        let statik = config.is_static(name);
-        for &(flag, val) in &parts {
-            match flag {
-                "-L" => {
-                    let meta = format!("rustc-link-search=native={}", val);
-                    config.print_metadata(&meta);
-                    dirs.push(PathBuf::from(val));
-                    self.link_paths.push(PathBuf::from(val));
-                }
-                "-F" => {
-                    let meta = format!("rustc-link-search=framework={}", val);
-                    config.print_metadata(&meta);
-                    self.framework_paths.push(PathBuf::from(val));
-                }
-                "-I" => {
-                    self.include_paths.push(PathBuf::from(val));
-                }
-                "-l" => {
-                    // These are provided by the CRT with MSVC
-                    if is_msvc && ["m", "c", "pthread"].contains(&val) {
-                        continue;
-                    }
+/// Include directory flags other than `-I`.
+#[derive(Clone, Copy)]
+enum IncludeFlag {
+    System,
+    Quote,
+    After,
+}
 
-                    if match statik {
-                        Statik::Force => true,
-                        Statik::Yes => is_static_available(val, &dirs),
-                        Statik::No => false,
-                    } && !config.statik_blacklist_contains(val) {
-                        let meta = format!("rustc-link-lib=static={}", val);
-                        config.print_metadata(&meta);
-                    } else {
-                        let meta = format!("rustc-link-lib={}", val);
-                        config.print_metadata(&meta);
-                    }
+const INCLUDE_FLAGS: [(&str, IncludeFlag); 3] = [
+    ("-isystem", IncludeFlag::System),
+    ("-iquote", IncludeFlag::Quote),
+    ("-idirafter", IncludeFlag::After),
+];
 
-                    self.libs.push(val.to_string());
-                }
-                "-D" => {
-                    let mut iter = val.split("=");
-                    self.defines.insert(iter.next().unwrap().to_owned(), iter.next().map(|s| s.to_owned()));
-                }
-                _ => {}
+#[cfg(feature = "cc")]
+fn apply_includes(build: &mut cc::Build, quote: &[PathBuf], include: &[PathBuf],
+                  system: &[PathBuf], after: &[PathBuf]) {
+    for path in quote {
+        build.flag("-iquote").flag(path.to_string_lossy().as_ref());
+    }
+    build.includes(include);
+    for path in system {
+        build.flag("-isystem").flag(path.to_string_lossy().as_ref());
+    }
+    for path in after {
+        build.flag("-idirafter").flag(path.to_string_lossy().as_ref());
+    }
+}
+
+/// Pass `defines` to `build`, sorted by name so that the command line is
+/// stable.
+#[cfg(feature = "cc")]
+fn apply_defines(build: &mut cc::Build, defines: &HashMap<String, Option<String>>) {
+    let mut defines = defines.iter().collect::<Vec<_>>();
+    defines.sort();
+    for (name, value) in defines {
+        build.define(name, value.as_deref());
+    }
+}
+
+/// The flags taking the name of a framework as the next word.
+const FRAMEWORK_FLAGS: [&str; 3] = ["-framework", "-weak_framework", "-needed_framework"];
+
+/// Whether `word`, out of the output of `--libs --cflags`, is a compile flag
+/// other than the include paths and defines parsed into their own fields.
+///
+/// The cflags and libs can't be told apart in the output, so this excludes
+/// the flags that are only meaningful to the linker.
+fn compile_flag(word: &str) -> bool {
+    let prefixes = ["-I", "-D", "-L", "-l", "-F", "-Wl,", "-isystem", "-iquote", "-idirafter",
+                    "-fuse-ld=", "-static-"];
+    word.starts_with('-') &&
+    !prefixes.iter().any(|p| word.starts_with(p)) &&
+    !FRAMEWORK_FLAGS.contains(&word) &&
+    !["-rdynamic", "-static", "-shared", "-Xlinker"].contains(&word)
+}
+
+/// The options of the C compiler taking their argument as a separate word.
+const ARGUMENT_FLAGS: [&str; 8] = ["-arch", "-isysroot", "-include", "-imacros", "-target",
+                                   "-Xlinker", "-Xpreprocessor", "-Xclang"];
+
+/// Whether `-pthread` links `libpthread` for `target`, rather than only
+/// enabling the threads of the C library.
+fn links_pthread(target: &str) -> bool {
+    if target.contains("android") {
+        return false;
+    }
+    ["-linux", "-freebsd", "-netbsd", "-openbsd", "-dragonfly", "-solaris", "-illumos",
+     "-windows-gnu"].iter().any(|os| target.contains(os))
+}
+
+/// Join the `-isystem <dir>` style flags that take their value as a separate
+/// word into a single `-isystem<dir>` word.
+fn join_include_flags(words: Vec<OsString>) -> Vec<OsString> {
+    let mut joined: Vec<OsString> = Vec::with_capacity(words.len());
+    let mut words = words.into_iter();
+    while let Some(mut word) = words.next() {
+        if INCLUDE_FLAGS.iter().any(|&(flag, _)| word == flag) {
+            if let Some(dir) = words.next() {
+                word.push(dir);
+                joined.push(word);
             }
+        } else {
+            joined.push(word);
         }
+    }
+    joined
+}
 
-        let mut iter = words.iter()
-                            .flat_map(|arg| if arg.starts_with("-Wl,") {
-                                 arg[4..].split(',').collect()
-                             } else {
-                                 vec![arg.as_ref()]
-                             });
-        while let Some(part) = iter.next() {
-            if part != "-framework" {
-                continue
-            }
-            if let Some(lib) = iter.next() {
-                let meta = format!("rustc-link-lib=framework={}", lib);
-                config.print_metadata(&meta);
-                self.frameworks.push(lib.to_string());
+/// Whether `word` is a flag taking a directory, which may not be UTF-8.
+fn path_flag(word: &OsStr) -> bool {
+    let text = word.to_string_lossy();
+    ["-L", "-F", "-I"].iter().any(|flag| text.starts_with(flag)) || include_flag(word).is_some()
+}
+
+/// Parse a joined `-isystem<dir>`, `-iquote<dir>` or `-idirafter<dir>` flag.
+fn include_flag(word: &OsStr) -> Option<(IncludeFlag, PathBuf)> {
+    let text = word.to_string_lossy();
+    for &(flag, kind) in &INCLUDE_FLAGS {
+        if text.starts_with(flag) {
+            if text.len() == flag.len() {
+                return None;
             }
+            return Some((kind, PathBuf::from(strip_flag(word, flag.len()))));
         }
     }
+    None
+}
 
-    fn parse_modversion(&mut self, output: &str) {
-        self.version.push_str(output.trim());
+/// The existing files under `root` recording the state of the system's
+/// package managers: their databases, or the pacman log, which unlike its
+/// database is a single file. Homebrew has no database, so the `.pc` files
+/// linked into its prefix, `homebrew_prefix` or else the default one, are
+/// watched instead.
+fn package_manager_state(root: &Path, homebrew_prefix: Option<&Path>) -> Vec<PathBuf> {
+    let mut paths = [
+        "var/lib/dpkg/status",
+        "var/lib/rpm/rpmdb.sqlite",
+        "var/lib/rpm/Packages",
+        "usr/lib/sysimage/rpm/rpmdb.sqlite",
+        "usr/lib/sysimage/rpm/Packages.db",
+        "var/log/pacman.log",
+        "lib/apk/db/installed",
+        "opt/local/var/macports/registry/registry.db",
+        "nix/var/nix/db/db.sqlite",
+    ].iter().map(|path| root.join(path)).collect::<Vec<_>>();
+    let homebrew_prefix = homebrew_prefix.map(Path::to_path_buf).or_else(|| {
+        ["opt/homebrew", "usr/local"].iter()
+                                     .map(|prefix| root.join(prefix))
+                                     .find(|prefix| prefix.join("bin/brew").is_file())
+    });
+    if let Some(prefix) = homebrew_prefix {
+        paths.push(prefix.join("lib/pkgconfig"));
+    }
+    let mut existing: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        if path.exists() && !existing.contains(&path) {
+            existing.push(path);
+        }
     }
+    existing
+}
+
+/// Compare versions the way `pkg-config` does (rpm's `rpmvercmp`): runs of
+/// digits compare numerically, runs of letters lexically, and a numeric run
+/// is newer than an alphabetic one.
+fn compare_versions(a: &str, b: &str) -> cmp::Ordering {
+    fn segments(v: &str) -> Vec<&str> {
+        let mut segments = Vec::new();
+        let mut rest = v.trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+        while !rest.is_empty() {
+            let numeric = rest.starts_with(|c: char| c.is_ascii_digit());
+            let end = rest.find(|c: char| {
+                !c.is_ascii_alphanumeric() || c.is_ascii_digit() != numeric
+            }).unwrap_or(rest.len());
+            segments.push(&rest[..end]);
+            rest = rest[end..].trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+        }
+        segments
+    }
+
+    let (a, b) = (segments(a), segments(b));
+    for (x, y) in a.iter().zip(&b) {
+        let x_numeric = x.starts_with(|c: char| c.is_ascii_digit());
+        let y_numeric = y.starts_with(|c: char| c.is_ascii_digit());
+        let ordering = match (x_numeric, y_numeric) {
+            (true, true) => {
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+            }
+            (true, false) => cmp::Ordering::Greater,
+            (false, true) => cmp::Ordering::Less,
+            (false, false) => x.cmp(y),
+        };
+        if ordering != cmp::Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Split the value of a `-D` flag into the macro name and its optional value.
+fn parse_define(val: &str) -> (String, Option<String>) {
+    let mut iter = val.split('=');
+    (iter.next().unwrap().to_owned(), iter.next().map(|s| s.to_owned()))
 }
 
 fn envify(name: &str) -> String {
@@ -587,40 +4914,260 @@ fn envify(name: &str) -> String {
     }).collect()
 }
 
-/// System libraries should only be linked dynamically
-fn is_static_available(name: &str, dirs: &[PathBuf]) -> bool {
-    let libname = format!("lib{}.a", name);
+/// Append the `extra` directories to `paths`, leaving out those in it already.
+fn extend_paths(paths: &mut Vec<PathBuf>, extra: &[PathBuf]) {
+    for path in extra {
+        if !paths.contains(path) {
+            paths.push(path.clone());
+        }
+    }
+}
+
+/// The queries the `pkg-config` options `args` make, for `Config::arg_for`.
+fn queries(args: &[&str]) -> Vec<Query> {
+    let mut queries = Vec::new();
+    if args.iter().any(|a| a.starts_with("--libs") || a.starts_with("--cflags")) {
+        queries.push(Query::Flags);
+    }
+    if args.contains(&"--modversion") {
+        queries.push(Query::Version);
+    }
+    if args.iter().any(|a| a.starts_with("--variable=") || *a == "--print-variables") {
+        queries.push(Query::Variable);
+    }
+    queries
+}
+
+/// Whether the cargo feature `feature` of the crate being built is enabled.
+fn feature_enabled(feature: &str) -> bool {
+    env::var_os(format!("CARGO_FEATURE_{}", envify(feature))).is_some()
+}
+
+/// The static archive of `name` in `dirs`, if any. System libraries should
+/// only be linked dynamically, so system directories are skipped.
+fn static_archive(name: &str, dirs: &[PathBuf], patterns: &[String]) -> Option<PathBuf> {
     let system_roots = if cfg!(target_os = "macos") {
         vec![Path::new("/Library"), Path::new("/System")]
     } else {
         vec![Path::new("/usr")]
     };
+    let dirs = dirs.iter()
+                   .filter(|dir| !system_roots.iter().any(|sys| dir.starts_with(sys)))
+                   .cloned()
+                   .collect::<Vec<_>>();
+    find_archive(name, &dirs, patterns)
+}
+
+/// The static archive of `name` in `dirs`, system directories included.
+fn find_archive(name: &str, dirs: &[PathBuf], patterns: &[String]) -> Option<PathBuf> {
+    let libnames = patterns.iter()
+                           .map(|pattern| pattern.replace("{}", name))
+                           // MinGW import libraries stand in for a DLL
+                           .filter(|libname| !libname.ends_with(".dll.a"))
+                           .collect::<Vec<_>>();
+    dirs.iter()
+        .flat_map(|dir| libnames.iter().map(move |libname| dir.join(libname)))
+        .find(|path| path.exists())
+}
+
+/// The `Statik` mode named by the value of a `*_STATIC` variable, `Yes` for
+/// values other than `force`, `yes`, `no`, `prefer` and `probe`.
+fn parse_statik(value: &OsStr) -> Statik {
+    value.to_string_lossy().parse().unwrap_or(Statik::Yes)
+}
+
+/// The library directories listed by `cc -print-search-dirs`, as gcc and
+/// clang do.
+fn compiler_link_dirs(cc: &str) -> Option<Vec<PathBuf>> {
+    // `CC` may include arguments, e.g. `ccache gcc`.
+    let mut words = cc.split_whitespace();
+    let output = Command::new(words.next()?).args(words).arg("-print-search-dirs")
+                                            .output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8_lossy(&output.stdout);
+    let dirs = output.lines().find_map(|l| l.strip_prefix("libraries: "))?;
+    Some(env::split_paths(dirs.trim_start_matches('=')).filter(|d| d.is_dir()).collect())
+}
+
+/// The usual default library directories for `target`.
+fn target_link_dirs(target: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("/usr/lib"), PathBuf::from("/lib")];
+    if target.contains("-linux-") {
+        // The Debian multiarch directory, e.g. `x86_64-linux-gnu` for
+        // `x86_64-unknown-linux-gnu`.
+        let parts = target.split('-').collect::<Vec<_>>();
+        if parts.len() == 4 {
+            let multiarch = format!("{}-{}-{}", parts[0], parts[2], parts[3]);
+            dirs.push(Path::new("/usr/lib").join(&multiarch));
+            dirs.push(Path::new("/lib").join(&multiarch));
+        }
+        if target.starts_with("x86_64") || target.starts_with("aarch64") {
+            dirs.push(PathBuf::from("/usr/lib64"));
+            dirs.push(PathBuf::from("/lib64"));
+        }
+    }
+    dirs
+}
 
-    dirs.iter().any(|dir| {
-        !system_roots.iter().any(|sys| dir.starts_with(sys)) &&
-        dir.join(&libname).exists()
+/// The minor version of the `rustc` compiling the crate being built, if it
+/// could be determined. It's asked once per process.
+fn rustc_minor_version() -> Option<u32> {
+    static VERSION: OnceLock<Option<u32>> = OnceLock::new();
+    *VERSION.get_or_init(|| {
+        let rustc = env::var_os("RUSTC").unwrap_or_else(|| OsString::from("rustc"));
+        let output = Command::new(rustc).arg("--version").output().ok()?;
+        let version = str::from_utf8(&output.stdout).ok()?;
+        // e.g. `rustc 1.61.0 (fe5b13d68 2022-05-18)`
+        version.split_whitespace().nth(1)?.split('.').nth(1)?.parse().ok()
     })
 }
 
-fn run(mut cmd: Command) -> Result<Vec<u8>, Error> {
-    match cmd.output() {
-        Ok(output) => {
+fn run(cmd: &mut Command, config: &Config) -> Result<Vec<u8>, Error> {
+    run_output(cmd, config).map(|output| output.stdout)
+}
+
+/// Like `run`, but returning the whole output of a successful command.
+fn run_output(cmd: &mut Command, config: &Config) -> Result<Output, Error> {
+    if let Some(ref on_command) = config.on_command {
+        on_command(cmd).map_err(|reason| Error::Vetoed {
+            command: command_line(cmd),
+            reason,
+        })?;
+    }
+    config.emit_trace(&Trace::Command(cmd));
+    let mut attempts = 1;
+    let result = loop {
+        match cmd.output() {
+            Err(ref cause) if is_transient(cause) && attempts <= config.retries => {
+                thread::sleep(Duration::from_millis(10 << (attempts - 1).min(10)));
+                attempts += 1;
+            }
+            Err(cause) if is_transient(&cause) => {
+                return Err(Error::SpawnFailed {
+                    command: command_line(cmd),
+                    attempts,
+                    cause,
+                });
+            }
+            result => break result,
+        }
+    };
+    match result {
+        Ok(mut output) => {
+            if let Some(ref on_output) = config.on_output {
+                on_output(&mut output);
+            }
+            config.emit_trace(&Trace::Output(&output));
             if output.status.success() {
-                Ok(output.stdout)
+                Ok(output)
             } else {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let suggestions = match distro::missing_package(&stderr) {
+                    Some(package) => config.suggestions(package),
+                    None => Vec::new(),
+                };
                 Err(Error::Failure {
-                    command: format!("{:?}", cmd),
-                    output: output,
+                    command: command_line(cmd),
+                    output,
+                    suggestions,
                 })
             }
         }
         Err(cause) => Err(Error::Command {
-            command: format!("{:?}", cmd),
-            cause: cause,
+            command: command_line(cmd),
+            cause,
         }),
     }
 }
 
+/// Add the non-empty lines of `stderr` to `lines`, skipping repeats.
+fn extend_stderr(lines: &mut Vec<String>, stderr: &[u8]) {
+    for line in String::from_utf8_lossy(stderr).lines().map(str::trim) {
+        if !line.is_empty() && !lines.iter().any(|l| l == line) {
+            lines.push(line.to_string());
+        }
+    }
+}
+
+/// Whether `cause`, an error starting a command, is likely to go away when
+/// retried: an interrupted call, or `ETXTBSY` from executing a file that's
+/// still open for writing, e.g. by a parallel build that just wrote it.
+fn is_transient(cause: &io::Error) -> bool {
+    const ETXTBSY: i32 = 26;
+    cause.kind() == io::ErrorKind::Interrupted ||
+    (cfg!(unix) && cause.raw_os_error() == Some(ETXTBSY))
+}
+
+/// The `Version` field of the `.pc` file at `path` with `contents`, with
+/// the variables defined before it expanded. `None` if it's missing or uses
+/// a variable the file doesn't define.
+fn pc_version(path: &Path, contents: &str) -> Option<String> {
+    fn expand(value: &str, vars: &HashMap<String, String>) -> Option<String> {
+        let mut expanded = String::new();
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            let end = start + rest[start..].find('}')?;
+            expanded.push_str(&rest[..start]);
+            expanded.push_str(vars.get(&rest[start + 2..end])?);
+            rest = &rest[end + 1..];
+        }
+        expanded.push_str(rest);
+        Some(expanded)
+    }
+
+    let mut vars = HashMap::new();
+    vars.insert("pcfiledir".to_string(), path.parent()?.to_string_lossy().into_owned());
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        match line.find([':', '=']) {
+            Some(i) if line[i..].starts_with('=') => {
+                if let Some(value) = expand(line[i + 1..].trim(), &vars) {
+                    vars.insert(line[..i].trim().to_string(), value);
+                }
+            }
+            Some(i) if line[..i].trim() == "Version" => return expand(line[i + 1..].trim(), &vars),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 64-bit FNV-1a of `s`, which unlike `DefaultHasher` is specified to stay
+/// the same.
+fn fnv1a(s: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in s.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Render `cmd` as a shell command line, preceded by the environment
+/// variables it sets.
+fn command_line(cmd: &Command) -> String {
+    fn quote(s: &OsStr) -> String {
+        let s = s.to_string_lossy();
+        let safe = |c: char| c.is_ascii_alphanumeric() || "-_=./:,+@%".contains(c);
+        if !s.is_empty() && s.chars().all(safe) {
+            s.into_owned()
+        } else {
+            format!("'{}'", s.replace('\'', "'\\''"))
+        }
+    }
+    let mut words = Vec::new();
+    for (key, value) in cmd.get_envs() {
+        if let Some(value) = value {
+            words.push(format!("{}={}", key.to_string_lossy(), quote(value)));
+        }
+    }
+    words.push(quote(cmd.get_program()));
+    words.extend(cmd.get_args().map(quote));
+    words.join(" ")
+}
+
 /// Split output produced by pkg-config --cflags and / or --libs into separate flags.
 ///
 /// Backslash in output is used to preserve literal meaning of following byte.  Different words are
@@ -628,7 +5175,7 @@ fn run(mut cmd: Command) -> Result<Vec<u8>, Error> {
 /// at all, apart from the newline at the end of output. For compatibility with what others
 /// consumers of pkg-config output would do in this scenario, they are used here for splitting as
 /// well.
-fn split_flags(output: &[u8]) -> Vec<String> {
+fn split_flags(output: &[u8]) -> Vec<OsString> {
     let mut word = Vec::new();
     let mut words = Vec::new();
     let mut escaped = false;
@@ -644,7 +5191,7 @@ fn split_flags(output: &[u8]) -> Vec<String> {
             }
             b'\t' | b'\n' | b'\r' | b' ' => {
                 if !word.is_empty() {
-                    words.push(String::from_utf8(word).unwrap());
+                    words.push(os_string(word));
                     word = Vec::new();
                 }
             }
@@ -653,28 +5200,210 @@ fn split_flags(output: &[u8]) -> Vec<String> {
     }
 
     if !word.is_empty() {
-        words.push(String::from_utf8(word).unwrap());
+        words.push(os_string(word));
     }
 
     words
 }
 
+/// `bytes` printed by `pkg-config`, losslessly where paths can hold any
+/// bytes, and decoded as UTF-8 elsewhere.
+fn os_string(bytes: Vec<u8>) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(bytes)
+    }
+    #[cfg(not(unix))]
+    {
+        OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+/// `word` without its first `len` bytes, an ASCII flag like `-L`.
+fn strip_flag(word: &OsStr, len: usize) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        OsStr::from_bytes(&word.as_bytes()[len..]).to_os_string()
+    }
+    #[cfg(not(unix))]
+    {
+        OsString::from(&word.to_string_lossy()[len..])
+    }
+}
+
+/// `output` without trailing whitespace, e.g. the newline after a variable.
+fn trim_output(mut output: Vec<u8>) -> Vec<u8> {
+    while output.last().is_some_and(|b| b.is_ascii_whitespace()) {
+        output.pop();
+    }
+    output
+}
+
+/// The output of `cmd`, for what must be text, like versions.
+fn utf8(output: Vec<u8>, cmd: &Command) -> Result<String, Error> {
+    String::from_utf8(output).map_err(|e| Error::InvalidOutput {
+        command: command_line(cmd),
+        output: String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    })
+}
+
+/// A directory of the current process under `temp_dir`, removed on drop, so
+/// that concurrent test runs don't share it.
+#[cfg(test)]
+struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("pkg-config-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
 #[test]
 #[cfg(target_os = "macos")]
 fn system_library_mac_test() {
-    assert!(!is_static_available("PluginManager", &[PathBuf::from("/Library/Frameworks")]));
-    assert!(!is_static_available("python2.7", &[PathBuf::from("/System/Library/Frameworks/Python.framework/Versions/2.7/lib/python2.7/config")]));
-    assert!(!is_static_available("ffi_convenience", &[PathBuf::from("/Library/Ruby/Gems/2.0.0/gems/ffi-1.9.10/ext/ffi_c/libffi-x86_64/.libs")]));
+    let patterns = ["lib{}.a".to_string()];
+    assert!(static_archive("PluginManager", &[PathBuf::from("/Library/Frameworks")], &patterns).is_none());
+    assert!(static_archive("python2.7", &[PathBuf::from("/System/Library/Frameworks/Python.framework/Versions/2.7/lib/python2.7/config")], &patterns).is_none());
+    assert!(static_archive("ffi_convenience", &[PathBuf::from("/Library/Ruby/Gems/2.0.0/gems/ffi-1.9.10/ext/ffi_c/libffi-x86_64/.libs")], &patterns).is_none());
 
     // Homebrew is in /usr/local, and it's not a part of the OS
     if Path::new("/usr/local/lib/libpng16.a").exists() {
-        assert!(is_static_available("png16", &[PathBuf::from("/usr/local/lib")]));
+        assert!(static_archive("png16", &[PathBuf::from("/usr/local/lib")], &patterns).is_some());
     }
 }
 
 #[test]
 #[cfg(target_os = "linux")]
 fn system_library_linux_test() {
-    assert!(!is_static_available("util", &[PathBuf::from("/usr/lib/x86_64-linux-gnu")]));
-    assert!(!is_static_available("dialog", &[PathBuf::from("/usr/lib")]));
+    let patterns = ["lib{}.a".to_string()];
+    assert!(static_archive("util", &[PathBuf::from("/usr/lib/x86_64-linux-gnu")], &patterns).is_none());
+    assert!(static_archive("dialog", &[PathBuf::from("/usr/lib")], &patterns).is_none());
+}
+
+#[test]
+fn static_archive_patterns_test() {
+    let dir = TempDir::new("static-archive-patterns");
+    std::fs::File::create(dir.join("libimport.dll.a")).unwrap();
+    std::fs::File::create(dir.join("mingw.a")).unwrap();
+    let dirs = [dir.to_path_buf()];
+
+    let patterns = ["lib{}.a".to_string(), "{}.a".to_string()];
+    assert!(static_archive("mingw", &dirs, &patterns).is_some());
+    assert!(static_archive("import", &dirs, &patterns).is_none());
+    assert!(static_archive("import.dll", &dirs, &patterns).is_none());
+    assert!(static_archive("mingw", &dirs, &patterns[..1]).is_none());
+}
+
+#[test]
+fn statik_whitelist_test() {
+    let dir = TempDir::new("statik-whitelist");
+    std::fs::File::create(dir.join("libvendored.a")).unwrap();
+    std::fs::File::create(dir.join("libother.a")).unwrap();
+    let dirs = [dir.to_path_buf()];
+    let patterns = ["lib{}.a".to_string()];
+
+    let mut config = Config::new();
+    config.statik_whitelist(vec!["vendored", "missing"]);
+    assert!(config.links_statically(&Statik::No, "vendored", &dirs, &patterns));
+    assert!(!config.links_statically(&Statik::No, "missing", &dirs, &patterns));
+    assert!(config.links_statically(&Statik::Force, "missing", &dirs, &patterns));
+    assert!(!config.links_statically(&Statik::Force, "other", &dirs, &patterns));
+
+    config.statik_blacklist(vec!["vendored"]);
+    assert!(!config.links_statically(&Statik::Force, "vendored", &dirs, &patterns));
+}
+
+#[test]
+fn parse_statik_test() {
+    assert!(parse_statik(OsStr::new("force")) == Statik::Force);
+    assert!(parse_statik(OsStr::new("Yes")) == Statik::Yes);
+    assert!(parse_statik(OsStr::new("no")) == Statik::No);
+    assert!(parse_statik(OsStr::new("prefer")) == Statik::Prefer);
+    assert!(parse_statik(OsStr::new("1")) == Statik::Yes);
+    assert!(parse_statik(OsStr::new("")) == Statik::Yes);
+}
+
+#[test]
+fn defer_metadata_test() {
+    let mut config = Config::new();
+    config.defer_metadata(true);
+    config.print_metadata("rustc-link-search=native=/deferred/a");
+    config.print_metadata("rustc-link-lib=deferred-a");
+    config.print_metadata("rustc-link-search=native=/deferred/a");
+    config.print_metadata("rustc-link-lib=deferred-b");
+    config.print_metadata("rustc-link-lib=deferred-a");
+    let pending = METADATA.lock().unwrap().pending.iter()
+        .map(|p| p.0.clone())
+        .filter(|m| m.contains("deferred"))
+        .collect::<Vec<_>>();
+    assert_eq!(pending, ["rustc-link-search=native=/deferred/a",
+                         "rustc-link-lib=deferred-a",
+                         "rustc-link-lib=deferred-b"]);
+
+    emit_metadata();
+    config.print_metadata("rustc-link-lib=deferred-a");
+    assert!(!METADATA.lock().unwrap().pending.iter().any(|m| m.0.contains("deferred")));
+}
+
+#[test]
+fn filter_fragments_test() {
+    let mut config = Config::new();
+    // Flags resolved in-process are always filtered here.
+    config.fragment_filter("lL").libpkgconf = true;
+    let words = config.filter_fragments(split_flags(b"-L/lib -lfoo -I/include -pthread /lib/libbar.a"));
+    assert_eq!(words, ["-L/lib", "-lfoo"]);
+}
+
+#[test]
+fn package_manager_state_test() {
+    let root = env::temp_dir().join(format!("pkg-config-rs-state-{}", std::process::id()));
+    for dir in ["var/lib/dpkg", "var/lib/rpm", "usr/local/bin", "usr/local/lib/pkgconfig"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    fs::write(root.join("var/lib/dpkg/status"), "").unwrap();
+    fs::write(root.join("usr/local/bin/brew"), "").unwrap();
+    let state = package_manager_state(&root, None);
+    let homebrew = package_manager_state(&root, Some(&root.join("opt/homebrew")));
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(state, [root.join("var/lib/dpkg/status"), root.join("usr/local/lib/pkgconfig")]);
+    assert_eq!(homebrew, [root.join("var/lib/dpkg/status")]);
+}
+
+#[test]
+fn is_transient_test() {
+    assert!(is_transient(&io::Error::from(io::ErrorKind::Interrupted)));
+    assert!(!is_transient(&io::Error::from(io::ErrorKind::NotFound)));
+    assert_eq!(is_transient(&io::Error::from_raw_os_error(26)), cfg!(unix));
+}
+
+#[test]
+fn pc_version_test() {
+    let path = Path::new("/opt/foo/lib/pkgconfig/foo.pc");
+    assert_eq!(pc_version(path, "major=2 # comment\nversion=${major}.1\nName: foo\n\
+                                 Version: ${version}\n").as_deref(), Some("2.1"));
+    assert_eq!(pc_version(path, "Version: ${pc_sysrootdir}1.0\n"), None);
+    assert_eq!(pc_version(path, "Name: foo\n"), None);
 }
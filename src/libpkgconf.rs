@@ -0,0 +1,274 @@
+//! In-process package resolution with libpkgconf, for build environments that
+//! can't spawn the `pkg-config` executable.
+//!
+//! Only the small, long-stable part of the libpkgconf API is used. The flags
+//! are rendered back to a string so that they go through the same parsing as
+//! the output of the executable.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::ptr;
+
+use {Failure, Options};
+
+// pkgconf_client_t flags, see libpkgconf.h.
+const PKGF_SEARCH_PRIVATE: c_uint = 0x0001;
+const PKGF_NO_UNINSTALLED: c_uint = 0x0004;
+const PKGF_MERGE_PRIVATE_FRAGMENTS: c_uint = 0x0010;
+const PKGF_REDEFINE_PREFIX: c_uint = 0x0400;
+
+/// The maximum depth of the dependency graph, as in the pkgconf CLI.
+const MAX_DEPTH: c_int = 2000;
+
+/// The major versions of pkgconf whose structures start like those below.
+const KNOWN_MAJOR_VERSIONS: [&str; 2] = ["1", "2"];
+
+/// `pkgconf_list_t`
+#[repr(C)]
+struct List {
+    head: *mut c_void,
+    tail: *mut c_void,
+    length: usize,
+}
+
+impl List {
+    fn new() -> List {
+        List { head: ptr::null_mut(), tail: ptr::null_mut(), length: 0 }
+    }
+}
+
+/// `pkgconf_node_t`
+#[repr(C)]
+struct Node {
+    prev: *mut Node,
+    next: *mut Node,
+    data: *mut c_void,
+}
+
+/// The leading fields of `pkgconf_pkg_t`, the same in pkgconf 1.x and 2.x.
+#[repr(C)]
+struct Pkg {
+    cache_iter: Node,
+    refcount: c_int,
+    id: *mut c_char,
+    filename: *mut c_char,
+    realname: *mut c_char,
+    version: *mut c_char,
+}
+
+/// The leading fields of `pkgconf_cross_personality_t`.
+#[repr(C)]
+struct Personality {
+    name: *const c_char,
+    dir_list: List,
+}
+
+/// `pkgconf_client_t`, whose first field is its search path.
+#[repr(C)]
+struct Client {
+    dir_list: List,
+}
+
+type ErrorHandler = extern "C" fn(*const c_char, *const Client, *mut c_void) -> bool;
+type QueueApplyFunc = extern "C" fn(*mut Client, *mut Pkg, *mut c_void, c_int) -> bool;
+type FragmentFilterFunc = extern "C" fn(*const Client, *const c_void, *mut c_void) -> bool;
+
+#[link(name = "pkgconf")]
+extern "C" {
+    fn pkgconf_builtin_pkg_get(name: *const c_char) -> *mut Pkg;
+    fn pkgconf_cross_personality_default() -> *mut Personality;
+    fn pkgconf_client_new(handler: ErrorHandler, data: *mut c_void,
+                          personality: *const Personality) -> *mut Client;
+    fn pkgconf_client_free(client: *mut Client);
+    fn pkgconf_client_set_flags(client: *mut Client, flags: c_uint);
+    fn pkgconf_client_set_sysroot_dir(client: *mut Client, dir: *const c_char);
+    fn pkgconf_client_set_buildroot_dir(client: *mut Client, dir: *const c_char);
+    fn pkgconf_path_split(text: *const c_char, dirs: *mut List, filter: bool) -> usize;
+    fn pkgconf_path_copy_list(dst: *mut List, src: *const List);
+    fn pkgconf_queue_push(list: *mut List, package: *const c_char);
+    fn pkgconf_queue_apply(client: *mut Client, list: *mut List, func: QueueApplyFunc,
+                           maxdepth: c_int, data: *mut c_void) -> bool;
+    fn pkgconf_queue_free(list: *mut List);
+    fn pkgconf_pkg_find(client: *mut Client, name: *const c_char) -> *mut Pkg;
+    fn pkgconf_pkg_unref(client: *mut Client, pkg: *mut Pkg);
+    fn pkgconf_pkg_libs(client: *mut Client, root: *mut Pkg, list: *mut List,
+                        maxdepth: c_int) -> c_uint;
+    fn pkgconf_pkg_cflags(client: *mut Client, root: *mut Pkg, list: *mut List,
+                          maxdepth: c_int) -> c_uint;
+    fn pkgconf_fragment_filter(client: *const Client, dst: *mut List, src: *mut List,
+                               func: FragmentFilterFunc, data: *mut c_void);
+    fn pkgconf_fragment_has_system_dir(client: *const Client, frag: *const c_void) -> bool;
+    fn pkgconf_fragment_render(list: *const List, escape: bool,
+                               ops: *const c_void) -> *mut c_char;
+    fn pkgconf_fragment_free(list: *mut List);
+}
+
+extern "C" {
+    fn free(ptr: *mut c_void);
+}
+
+/// Resolve the packages `queue` (`name`, optionally with version
+/// constraints), returning the output of `--cflags --libs` and the version
+/// of `name`.
+pub fn query(name: &str, queue: &[String], options: &Options)
+             -> Result<(Vec<u8>, String), Failure> {
+    let mut messages = String::new();
+    unsafe {
+        if !known_abi() {
+            return Err(Failure::Other("unsupported libpkgconf version, the structures of \
+                                       pkgconf 1.x or 2.x are expected".to_string()));
+        }
+        let client = pkgconf_client_new(error_handler,
+                                        &mut messages as *mut String as *mut c_void,
+                                        pkgconf_cross_personality_default());
+        if client.is_null() {
+            return Err(Failure::Other("failed to create a libpkgconf client".to_string()));
+        }
+        let result = resolve(client, name, queue, options);
+        pkgconf_client_free(client);
+        let constrained = queue.iter().any(|package| package != name);
+        result.map_err(|found| match found {
+            Some(found) if constrained => {
+                Failure::VersionMismatch(found, messages.trim().to_string())
+            }
+            _ => Failure::Other(messages.trim().to_string()),
+        })
+    }
+}
+
+unsafe fn resolve(client: *mut Client, name: &str, queue: &[String], options: &Options)
+                  -> Result<(Vec<u8>, String), Option<String>> {
+    let mut flags = 0;
+    if options.statik {
+        // Like `--static --pure`, search private modules without merging
+        // their fragments back.
+        flags |= PKGF_SEARCH_PRIVATE;
+        if !options.pure {
+            flags |= PKGF_MERGE_PRIVATE_FRAGMENTS;
+        }
+    }
+    if !options.uninstalled {
+        flags |= PKGF_NO_UNINSTALLED;
+    }
+    if options.define_prefix {
+        flags |= PKGF_REDEFINE_PREFIX;
+    }
+    pkgconf_client_set_flags(client, flags);
+
+    // Mirrors pkgconf_client_dir_list_build, which only reads the process
+    // environment.
+    let dirs = &mut (*client).dir_list;
+    if let Some(ref path) = options.path {
+        pkgconf_path_split(cstring(path).as_ptr(), dirs, true);
+    }
+    match options.libdir {
+        Some(ref libdir) => {
+            pkgconf_path_split(cstring(libdir).as_ptr(), dirs, true);
+        }
+        None => pkgconf_path_copy_list(dirs, &(*pkgconf_cross_personality_default()).dir_list),
+    }
+    if let Some(ref sysroot) = options.sysroot {
+        pkgconf_client_set_sysroot_dir(client, cstring(sysroot).as_ptr());
+    }
+    if let Some(ref dir) = options.top_build_dir {
+        pkgconf_client_set_buildroot_dir(client, cstring(dir).as_ptr());
+    }
+
+    // On failure, the version found (if any) tells a version mismatch apart
+    // from a missing package.
+    let cflags = fragments(client, queue, apply_cflags, options.system_cflags)
+        .ok_or_else(|| version(client, name))?;
+    let libs = fragments(client, queue, apply_libs, options.system_libs)
+        .ok_or_else(|| version(client, name))?;
+    let version = version(client, name).unwrap_or_default();
+
+    let mut output = cflags;
+    output.push(b' ');
+    output.extend(libs);
+    Ok((output, version))
+}
+
+/// Whether the structures of the loaded libpkgconf start like those declared
+/// here, judging by its builtin `pkg-config` package: the only way to ask
+/// for its version is to read them.
+unsafe fn known_abi() -> bool {
+    let pkg = pkgconf_builtin_pkg_get(b"pkg-config\0".as_ptr() as *const c_char);
+    if pkg.is_null() || (*pkg).id.is_null() || (*pkg).version.is_null() {
+        return false;
+    }
+    let version = CStr::from_ptr((*pkg).version).to_string_lossy();
+    CStr::from_ptr((*pkg).id).to_bytes() == b"pkg-config" &&
+    KNOWN_MAJOR_VERSIONS.contains(&version.split('.').next().unwrap_or(""))
+}
+
+/// Collect and render the fragments `apply` gathers for `queue`.
+unsafe fn fragments(client: *mut Client, queue: &[String], apply: QueueApplyFunc,
+                    system_dirs: bool) -> Option<Vec<u8>> {
+    let mut packages = List::new();
+    for package in queue {
+        pkgconf_queue_push(&mut packages, cstring(package).as_ptr());
+    }
+    let mut unfiltered = List::new();
+    let ok = pkgconf_queue_apply(client, &mut packages, apply, MAX_DEPTH,
+                                 &mut unfiltered as *mut List as *mut c_void);
+    pkgconf_queue_free(&mut packages);
+
+    let mut output = None;
+    if ok {
+        let mut filtered = List::new();
+        let mut system_dirs = system_dirs;
+        pkgconf_fragment_filter(client, &mut filtered, &mut unfiltered, filter_system_dirs,
+                                &mut system_dirs as *mut bool as *mut c_void);
+        let rendered = pkgconf_fragment_render(&filtered, true, ptr::null());
+        if !rendered.is_null() {
+            output = Some(CStr::from_ptr(rendered).to_bytes().to_vec());
+            free(rendered as *mut c_void);
+        }
+        pkgconf_fragment_free(&mut filtered);
+    }
+    pkgconf_fragment_free(&mut unfiltered);
+    output
+}
+
+/// The version of the package `name`, if it can be found at all.
+unsafe fn version(client: *mut Client, name: &str) -> Option<String> {
+    let pkg = pkgconf_pkg_find(client, cstring(name).as_ptr());
+    if pkg.is_null() {
+        return None;
+    }
+    let version = if (*pkg).version.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr((*pkg).version).to_string_lossy().into_owned()
+    };
+    pkgconf_pkg_unref(client, pkg);
+    Some(version)
+}
+
+fn cstring(s: &str) -> CString {
+    CString::new(s).unwrap()
+}
+
+extern "C" fn error_handler(msg: *const c_char, _client: *const Client, data: *mut c_void)
+                            -> bool {
+    unsafe {
+        let messages = &mut *(data as *mut String);
+        messages.push_str(&CStr::from_ptr(msg).to_string_lossy());
+    }
+    true
+}
+
+extern "C" fn apply_cflags(client: *mut Client, world: *mut Pkg, data: *mut c_void,
+                           maxdepth: c_int) -> bool {
+    unsafe { pkgconf_pkg_cflags(client, world, data as *mut List, maxdepth) == 0 }
+}
+
+extern "C" fn apply_libs(client: *mut Client, world: *mut Pkg, data: *mut c_void,
+                         maxdepth: c_int) -> bool {
+    unsafe { pkgconf_pkg_libs(client, world, data as *mut List, maxdepth) == 0 }
+}
+
+extern "C" fn filter_system_dirs(client: *const Client, frag: *const c_void,
+                                 data: *mut c_void) -> bool {
+    unsafe { *(data as *const bool) || !pkgconf_fragment_has_system_dir(client, frag) }
+}
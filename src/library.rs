@@ -0,0 +1,92 @@
+//! Building `Library` values outside of probes, e.g. for vendored builds or
+//! test fixtures.
+
+use std::path::PathBuf;
+
+use {Library, Linkage};
+
+/// A builder for `Library`.
+///
+/// ```
+/// let lib = pkg_config::library::Builder::new()
+///     .link_path("/opt/foo/lib")
+///     .static_lib("foo")
+///     .include_path("/opt/foo/include")
+///     .define("FOO_STATIC", None)
+///     .version("1.2.3")
+///     .build();
+/// assert_eq!(lib.libs, vec!["foo"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Builder {
+    library: Library,
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    /// Creates a builder for a `Library` with all fields empty.
+    pub fn new() -> Builder {
+        Builder { library: Library::new() }
+    }
+
+    /// Add a library linked dynamically, like `-l<name>`.
+    pub fn lib(&mut self, name: &str) -> &mut Builder {
+        self.library.libs.push(name.to_string());
+        self.library.linkage.insert(name.to_string(), Linkage::Dynamic);
+        self
+    }
+
+    /// Add a library linked statically.
+    pub fn static_lib(&mut self, name: &str) -> &mut Builder {
+        self.library.libs.push(name.to_string());
+        self.library.static_libs.push(name.to_string());
+        self.library.linkage.insert(name.to_string(), Linkage::Static);
+        self
+    }
+
+    /// Add a library search directory, like `-L<path>`.
+    pub fn link_path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Builder {
+        self.library.link_paths.push(path.into());
+        self
+    }
+
+    /// Add a framework, like `-framework <name>`.
+    pub fn framework(&mut self, name: &str) -> &mut Builder {
+        self.library.frameworks.push(name.to_string());
+        self
+    }
+
+    /// Add a framework search directory, like `-F<path>`.
+    pub fn framework_path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Builder {
+        self.library.framework_paths.push(path.into());
+        self
+    }
+
+    /// Add an include directory, like `-I<path>`.
+    pub fn include_path<P: Into<PathBuf>>(&mut self, path: P) -> &mut Builder {
+        self.library.include_paths.push(path.into());
+        self
+    }
+
+    /// Add a preprocessor definition, like `-D<name>` or `-D<name>=<value>`.
+    pub fn define(&mut self, name: &str, value: Option<&str>) -> &mut Builder {
+        self.library.defines.insert(name.to_string(), value.map(|v| v.to_string()));
+        self
+    }
+
+    /// Set the version of the library.
+    pub fn version(&mut self, version: &str) -> &mut Builder {
+        self.library.version = version.to_string();
+        self
+    }
+
+    /// Make a `Library` with the settings so far.
+    pub fn build(&self) -> Library {
+        self.library.clone()
+    }
+}
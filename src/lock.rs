@@ -0,0 +1,81 @@
+//! Lock files recording the package versions of a known-good build, see
+//! `Config::enforce_lock`.
+//!
+//! A lock file has one `name = version` line per package. Blank lines and
+//! lines starting with `#` are ignored.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use Library;
+
+/// Read the `(name, version)` entries of the lock file at `path`.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<(String, String)>> {
+    let contents = fs::read_to_string(path)?;
+    let mut entries = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(version)) if !name.trim().is_empty() => {
+                entries.push((name.trim().to_string(), version.trim().to_string()))
+            }
+            _ => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("line {}: expected `name = version`", i + 1)))
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Record the version of `library`, found as the package `name`, in the
+/// lock file at `path`.
+///
+/// An existing entry for `name` is updated in place and other lines are kept
+/// as is; otherwise the entry is appended, creating the file if needed.
+pub fn record<P: AsRef<Path>>(name: &str, library: &Library, path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let entry = format!("{} = {}", name, library.version);
+    let mut found = false;
+    let mut lines = Vec::new();
+    for line in contents.lines() {
+        let is_entry = !line.trim_start().starts_with('#') &&
+                       line.split('=').next().is_some_and(|n| n.trim() == name);
+        if is_entry && !found {
+            lines.push(entry.clone());
+            found = true;
+        } else if !is_entry {
+            lines.push(line.to_string());
+        }
+    }
+    if !found {
+        lines.push(entry);
+    }
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    fs::write(path, contents)
+}
+
+/// Whether versions `a` and `b` agree on their major and minor components.
+pub(crate) fn same_minor(a: &str, b: &str) -> bool {
+    let minor = |v: &str| v.split('.').take(2).map(|s| s.to_string()).collect::<Vec<_>>();
+    minor(a) == minor(b)
+}
+
+#[test]
+fn same_minor_test() {
+    assert!(same_minor("3.10.0.SVN", "3.10.2"));
+    assert!(same_minor("1", "1"));
+    assert!(!same_minor("3.10.0", "3.9.0"));
+    assert!(!same_minor("1", "1.0"));
+}
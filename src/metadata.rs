@@ -0,0 +1,253 @@
+//! Packages declared in the `[package.metadata.pkg-config]` table of a
+//! crate's `Cargo.toml`, see `probe_from_metadata`.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use distro::unquote;
+use {feature_enabled, print_cargo, probe_many, Config, Error, Library};
+
+const TABLE: &[&str] = &["package", "metadata", "pkg-config"];
+
+/// A package declared in the manifest.
+#[derive(Debug, Default, PartialEq)]
+struct Dependency {
+    /// The key of the declaration, the package name unless `name` is given.
+    key: String,
+    name: Option<String>,
+    version: Option<String>,
+    max_version: Option<String>,
+    /// The cargo feature that enables the dependency, if any.
+    feature: Option<String>,
+    optional: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    String(String),
+    Bool(bool),
+    Table(Vec<(String, Value)>),
+}
+
+/// Probe the packages declared in the manifest of the crate being built.
+pub fn probe() -> Result<HashMap<String, Library>, Error> {
+    let dir = env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from).unwrap_or_default();
+    let path = dir.join("Cargo.toml");
+    print_cargo(&format!("rerun-if-changed={}", path.display()));
+    let error = |message: String| Error::Metadata { path: path.clone(), message };
+    let contents = fs::read_to_string(&path).map_err(|e| error(e.to_string()))?;
+    let dependencies = parse(&contents).map_err(&error)?;
+
+    let mut keys = Vec::new();
+    let mut configs = Vec::new();
+    for dep in dependencies {
+        match dep.feature {
+            Some(ref feature) if !feature_enabled(feature) => continue,
+            _ => {}
+        }
+        let mut config = Config::new();
+        if let Some(ref version) = dep.version {
+            config.atleast_version(version);
+        }
+        if let Some(ref version) = dep.max_version {
+            config.at_most_version(version);
+        }
+        let name = dep.name.clone().unwrap_or_else(|| dep.key.clone());
+        configs.push((name, config));
+        keys.push((dep.key, dep.optional));
+    }
+
+    let mut libraries = HashMap::new();
+    for ((key, optional), (_, result)) in keys.into_iter().zip(probe_many(configs)) {
+        match result {
+            Ok(library) => {
+                libraries.insert(key, library);
+            }
+            Err(_) if optional => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(libraries)
+}
+
+/// Parse the dependencies of the `[package.metadata.pkg-config]` table out of
+/// a manifest, understanding the small subset of TOML they're declared with,
+/// see `probe_from_metadata`:
+///
+/// ```toml
+/// [package.metadata.pkg-config]
+/// zlib = "1.2"
+/// gtk = { name = "gtk+-3.0", version = "3.24", feature = "gtk" }
+///
+/// [package.metadata.pkg-config.libpng]
+/// version = "1.6"
+/// optional = true
+/// ```
+fn parse(contents: &str) -> Result<Vec<Dependency>, String> {
+    let mut dependencies: Vec<Dependency> = Vec::new();
+    // The path of the current table, relative to `TABLE`, if inside it.
+    let mut table: Option<Vec<String>> = None;
+    for (i, line) in contents.lines().enumerate() {
+        let err = |msg: &str| format!("line {}: {}", i + 1, msg);
+        let line = strip_comment(line);
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with("[[") {
+            table = None;
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let keys = split_outside_quotes(header, '.')
+                .iter()
+                .map(|key| unquote(key.trim()))
+                .collect::<Option<Vec<_>>>();
+            table = match keys {
+                Some(ref keys) if keys.len() >= TABLE.len() && keys[..TABLE.len()] == *TABLE => {
+                    Some(keys[TABLE.len()..].to_vec())
+                }
+                _ => None,
+            };
+            continue;
+        }
+        let table = match table {
+            Some(ref table) => table,
+            None => continue,
+        };
+        let (key, value) = match split_key_value(line) {
+            Some((key, value)) => (unquote(key).ok_or_else(|| err("invalid key"))?,
+                                   parse_value(value).ok_or_else(|| err("invalid value"))?),
+            None => return Err(err("expected `key = value`")),
+        };
+        match table.as_slice() {
+            [] => {
+                let mut dep = Dependency { key, ..Dependency::default() };
+                match value {
+                    Value::String(version) => dep.version = Some(version),
+                    Value::Table(fields) => {
+                        for (field, value) in fields {
+                            set_field(&mut dep, &field, value).map_err(|m| err(&m))?;
+                        }
+                    }
+                    Value::Bool(_) => return Err(err("expected a version or a table")),
+                }
+                dependencies.push(dep);
+            }
+            [package] => {
+                let position = dependencies.iter().position(|d| d.key == *package);
+                let dep = match position {
+                    Some(i) => &mut dependencies[i],
+                    None => {
+                        dependencies.push(Dependency { key: package.clone(),
+                                                       ..Dependency::default() });
+                        dependencies.last_mut().unwrap()
+                    }
+                };
+                set_field(dep, &key, value).map_err(|m| err(&m))?;
+            }
+            _ => return Err(err("unexpected nested table")),
+        }
+    }
+    Ok(dependencies)
+}
+
+fn set_field(dep: &mut Dependency, field: &str, value: Value) -> Result<(), String> {
+    match (field, value) {
+        ("name", Value::String(s)) => dep.name = Some(s),
+        ("version", Value::String(s)) => dep.version = Some(s),
+        ("max-version", Value::String(s)) => dep.max_version = Some(s),
+        ("feature", Value::String(s)) => dep.feature = Some(s),
+        ("optional", Value::Bool(b)) => dep.optional = b,
+        (field, _) => return Err(format!("invalid field `{}` of `{}`", field, dep.key)),
+    }
+    Ok(())
+}
+
+/// A string, boolean or inline table of strings and booleans.
+fn parse_value(value: &str) -> Option<Value> {
+    if let Some(s) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return Some(Value::String(s.to_string()));
+    }
+    if let Some(fields) = value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+        let mut table = Vec::new();
+        for field in split_outside_quotes(fields, ',') {
+            if field.trim().is_empty() {
+                continue;
+            }
+            let (key, value) = split_key_value(&field)?;
+            match parse_value(value)? {
+                Value::Table(_) => return None,
+                value => table.push((unquote(key)?, value)),
+            }
+        }
+        return Some(Value::Table(table));
+    }
+    match value {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => None,
+    }
+}
+
+/// Split `key = value` at its first `=` outside of a quoted key.
+fn split_key_value(s: &str) -> Option<(&str, &str)> {
+    let key_len = split_outside_quotes(s, '=').first()?.len();
+    let value = s.get(key_len..)?.strip_prefix('=')?;
+    Some((s[..key_len].trim(), value.trim()))
+}
+
+/// Split `s` at each `sep` that isn't inside a quoted string.
+fn split_outside_quotes(s: &str, sep: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut quoted = false;
+    for c in s.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c == sep && !quoted => {
+                parts.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        parts.last_mut().unwrap().push(c);
+    }
+    parts
+}
+
+/// Remove a `#` comment, unless the `#` is in a quoted string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[test]
+fn parse_test() {
+    let manifest = "[package]\nname = \"foo-sys\" # comment\n\n\
+                    [package.metadata.pkg-config]\n\
+                    zlib = \"1.2\"\n\
+                    gtk = { name = \"gtk+-3.0\", version = \"3.24\", feature = \"gtk\" }\n\n\
+                    [package.metadata.pkg-config.\"libpng\"]\n\
+                    version = \"1.6\" # the first with APNG\noptional = true\n\n\
+                    [[bin]]\nname = \"x\"\n\
+                    [dependencies]\nlibc = { version = \"0.2\", features = [\"std\"] }\n";
+    assert_eq!(parse(manifest).unwrap(), vec![
+        Dependency { key: "zlib".into(), version: Some("1.2".into()), ..Dependency::default() },
+        Dependency { key: "gtk".into(), name: Some("gtk+-3.0".into()),
+                     version: Some("3.24".into()), feature: Some("gtk".into()),
+                     ..Dependency::default() },
+        Dependency { key: "libpng".into(), version: Some("1.6".into()), optional: true,
+                     ..Dependency::default() },
+    ]);
+    assert!(parse("[package.metadata.pkg-config]\nzlib = 1").is_err());
+    assert!(parse("[package.metadata.pkg-config]\nzlib = { verison = \"1\" }").is_err());
+}
@@ -0,0 +1,76 @@
+//! Translation of the POSIX paths printed by an MSYS2 `pkg-config` to the
+//! Windows paths rustc understands, and normalization of Windows paths.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str;
+use std::sync::Mutex;
+
+/// The output of `cygpath -w /`, once it has been run.
+static CYGPATH_ROOT: Mutex<Option<Option<String>>> = Mutex::new(None);
+
+/// The Windows path of the MSYS2 root, e.g. `C:\msys64`, as reported by
+/// `cygpath`.
+pub fn root() -> Option<String> {
+    CYGPATH_ROOT.lock().unwrap().get_or_insert_with(|| {
+        let output = Command::new("cygpath").args(["-w", "/"]).output().ok()?;
+        let root = str::from_utf8(&output.stdout).ok()?.trim();
+        if !output.status.success() || root.is_empty() {
+            return None;
+        }
+        Some(root.to_string())
+    }).clone()
+}
+
+/// Translate the MSYS2 path `path` to a Windows path, given the Windows path
+/// of the MSYS2 root.
+///
+/// `/c/foo` is `C:\foo` and `/mingw64/lib` is `<root>\mingw64\lib`. Paths
+/// that aren't absolute POSIX paths are returned as is.
+pub fn to_windows(path: &str, root: &str) -> PathBuf {
+    if !path.starts_with('/') || path.starts_with("//") {
+        return PathBuf::from(path);
+    }
+    let mut components = path[1..].splitn(2, '/');
+    let first = components.next().unwrap_or("");
+    let rest = components.next().unwrap_or("").replace('/', "\\");
+    if first.len() == 1 && first.chars().all(|c| c.is_ascii_alphabetic()) {
+        return PathBuf::from(format!("{}:\\{}", first.to_ascii_uppercase(), rest));
+    }
+    let root = root.trim_end_matches(['\\', '/']);
+    PathBuf::from(format!("{}{}", root, path.replace('/', "\\").trim_end_matches('\\')))
+}
+
+/// Normalize the Windows path `path`: backslashes only, no `\\?\` prefix
+/// and an uppercase drive letter.
+///
+/// Paths that aren't UTF-8 are returned as is.
+pub fn normalize(path: &Path) -> PathBuf {
+    let path = match path.to_str() {
+        Some(path) => path.replace('/', "\\"),
+        None => return path.to_path_buf(),
+    };
+    let mut path = match path.strip_prefix("\\\\?\\") {
+        Some(rest) => match rest.strip_prefix("UNC\\") {
+            Some(share) => format!("\\\\{}", share),
+            None => rest.to_string(),
+        },
+        None => path,
+    };
+    if path.as_bytes().get(1) == Some(&b':') && path.as_bytes()[0].is_ascii_lowercase() {
+        path[..1].make_ascii_uppercase();
+    }
+    PathBuf::from(path)
+}
+
+#[test]
+fn to_windows_test() {
+    let root = "C:\\msys64\\";
+    assert_eq!(to_windows("/mingw64/lib", root), PathBuf::from("C:\\msys64\\mingw64\\lib"));
+    assert_eq!(to_windows("/mingw64/include/", root),
+               PathBuf::from("C:\\msys64\\mingw64\\include"));
+    assert_eq!(to_windows("/d/deps/lib", root), PathBuf::from("D:\\deps\\lib"));
+    assert_eq!(to_windows("/c", root), PathBuf::from("C:\\"));
+    assert_eq!(to_windows("C:/deps/lib", root), PathBuf::from("C:/deps/lib"));
+    assert_eq!(to_windows("//server/share", root), PathBuf::from("//server/share"));
+}
@@ -0,0 +1,66 @@
+//! Per-package settings on top of a shared `Config`, see `Config::package`.
+
+use std::ffi::OsStr;
+
+use {Config, Error, Library, Statik};
+
+/// The settings of a `Config` with tweaks for a single package, as made by
+/// `Config::package`.
+///
+/// ```no_run
+/// let mut config = pkg_config::Config::new();
+/// config.statik(true);
+/// let ssl = config.package("openssl").atleast_version("1.1").probe().unwrap();
+/// let z = config.package("zlib").statik(false).probe().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct PackageConfig {
+    config: Config,
+    name: String,
+}
+
+impl PackageConfig {
+    pub(crate) fn new(config: &Config, name: &str) -> PackageConfig {
+        PackageConfig { config: config.clone(), name: name.to_string() }
+    }
+
+    /// Indicate that the package must be at least version `vers`.
+    pub fn atleast_version(&mut self, vers: &str) -> &mut PackageConfig {
+        self.config.atleast_version(vers);
+        self
+    }
+
+    /// Indicate that the package must be at most version `vers`.
+    pub fn at_most_version(&mut self, vers: &str) -> &mut PackageConfig {
+        self.config.at_most_version(vers);
+        self
+    }
+
+    /// Override how the package is linked, see `Config::statik`.
+    pub fn statik<S>(&mut self, statik: S) -> &mut PackageConfig
+        where S: Into<Statik>
+    {
+        self.config.statik(statik);
+        self
+    }
+
+    /// Add an argument to pass to pkg-config for this package only.
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut PackageConfig {
+        self.config.arg(arg);
+        self
+    }
+
+    /// Look for the package under the name `name` instead, e.g. where a
+    /// distribution ships it with a different `.pc` file name.
+    ///
+    /// Environment variables such as `FOO_STATIC` follow the new name.
+    pub fn rename(&mut self, name: &str) -> &mut PackageConfig {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Run `pkg-config` to find the package, like `Config::probe`.
+    pub fn probe(&self) -> Result<Library, Error> {
+        self.config.probe(&self.name)
+    }
+}
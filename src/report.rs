@@ -0,0 +1,150 @@
+//! A serializable record of what a probe found, see `Library::report`, and
+//! serde helpers for the types of the `serde` feature.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use {Library, Linkage};
+
+/// What a probe found and how it's linked, e.g. to write into `OUT_DIR` for
+/// auditing which system libraries a build linked against.
+///
+/// Paths are converted to strings lossily, and maps are sorted, so that the
+/// same probe always gives the same report.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Report {
+    /// The name of the package that was found, `None` for libraries not
+    /// found by `pkg-config`, e.g. made with `Library::from_parts`.
+    pub package: Option<String>,
+    /// The `.pc` file of the package, see `Library::pc_path`.
+    pub pc_path: Option<String>,
+    pub version: String,
+    /// The `pkg-config` executable run, `None` if the package was resolved
+    /// in-process or not probed.
+    pub pkg_config: Option<String>,
+    pub libs: Vec<String>,
+    pub link_paths: Vec<String>,
+    pub frameworks: Vec<String>,
+    pub framework_paths: Vec<String>,
+    pub include_paths: Vec<String>,
+    pub defines: BTreeMap<String, Option<String>>,
+    pub compile_flags: Vec<String>,
+    /// How each `-l` flag was handled, see `Library::linkage`.
+    pub linkage: BTreeMap<String, Linkage>,
+    pub static_libs: Vec<String>,
+    /// The libraries passed with `-l:<file>`, see `Library::verbatim_libs`.
+    pub verbatim_libs: Vec<String>,
+    pub probe_commands: Vec<String>,
+}
+
+impl Report {
+    pub(crate) fn new(library: &Library) -> Report {
+        fn strings(paths: &[PathBuf]) -> Vec<String> {
+            paths.iter().map(|p| p.to_string_lossy().into_owned()).collect()
+        }
+        Report {
+            package: library.package.clone(),
+            pc_path: library.pc_path.as_ref().map(|p| p.to_string_lossy().into_owned()),
+            version: library.version.clone(),
+            pkg_config: library.pkg_config.clone(),
+            libs: library.libs.clone(),
+            link_paths: strings(&library.link_paths),
+            frameworks: library.frameworks.clone(),
+            framework_paths: strings(&library.framework_paths),
+            include_paths: strings(&library.include_paths),
+            defines: library.defines.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            compile_flags: library.compile_flags.clone(),
+            linkage: library.linkage.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+            static_libs: library.static_libs.clone(),
+            verbatim_libs: library.verbatim_libs.clone(),
+            probe_commands: library.probe_commands.clone(),
+        }
+    }
+
+    /// The report as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        // Only strings, lists and maps with string keys, which can't fail.
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+/// (De)serialization of `OsString`s as strings, which unlike serde's own
+/// representation doesn't depend on the platform. Arguments that aren't
+/// valid Unicode can't be serialized.
+pub(crate) mod os_strings {
+    use std::ffi::OsString;
+
+    use serde::de::Deserializer;
+    use serde::ser::{Error, Serializer};
+    use serde::Deserialize;
+
+    pub fn serialize<S: Serializer>(args: &[OsString], serializer: S)
+                                    -> Result<S::Ok, S::Error> {
+        let args = args.iter().map(|arg| {
+            arg.to_str().ok_or_else(|| {
+                S::Error::custom(format!("argument {:?} isn't valid Unicode", arg))
+            })
+        }).collect::<Result<Vec<_>, _>>()?;
+        serializer.collect_seq(args)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D)
+                                                  -> Result<Vec<OsString>, D::Error> {
+        let args = Vec::<String>::deserialize(deserializer)?;
+        Ok(args.into_iter().map(OsString::from).collect())
+    }
+}
+
+/// (De)serialization of the arguments of `Config::arg_for` as pairs of a
+/// `Query` and a string, like `os_strings`.
+pub(crate) mod query_args {
+    use std::ffi::OsString;
+
+    use serde::de::Deserializer;
+    use serde::ser::{Error, Serializer};
+    use serde::Deserialize;
+
+    use Query;
+
+    pub fn serialize<S: Serializer>(args: &[(Query, OsString)], serializer: S)
+                                    -> Result<S::Ok, S::Error> {
+        let args = args.iter().map(|&(query, ref arg)| {
+            let arg = arg.to_str().ok_or_else(|| {
+                S::Error::custom(format!("argument {:?} isn't valid Unicode", arg))
+            })?;
+            Ok((query, arg))
+        }).collect::<Result<Vec<_>, S::Error>>()?;
+        serializer.collect_seq(args)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D)
+                                                  -> Result<Vec<(Query, OsString)>, D::Error> {
+        let args = Vec::<(Query, String)>::deserialize(deserializer)?;
+        Ok(args.into_iter().map(|(query, arg)| (query, OsString::from(arg))).collect())
+    }
+}
+
+/// (De)serialization of a `HashMap` with its entries sorted by key, so that
+/// the same map is always written the same.
+pub(crate) mod sorted {
+    use std::collections::{BTreeMap, HashMap};
+    use std::hash::Hash;
+
+    use serde::de::Deserializer;
+    use serde::ser::Serializer;
+    use serde::{Deserialize, Serialize};
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+        where K: Ord + Serialize, V: Serialize, S: Serializer
+    {
+        serializer.collect_map(map.iter().collect::<BTreeMap<_, _>>())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+        where K: Eq + Hash + Deserialize<'de>, V: Deserialize<'de>, D: Deserializer<'de>
+    {
+        HashMap::deserialize(deserializer)
+    }
+}
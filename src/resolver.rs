@@ -0,0 +1,507 @@
+//! A minimal pure-Rust implementation of `pkg-config`, used when no system
+//! tool can be run.
+//!
+//! It covers what simple packages need: variables, `Requires` with version
+//! constraints, `Cflags` and `Libs`, and their `.private` variants for static
+//! linking. The flags are rendered the way `pkg-config` prints them, so that
+//! they go through the same parsing as the output of the executable.
+//!
+//! `<name>-uninstalled.pc` files are preferred over `<name>.pc` unless
+//! disabled, with `pc_top_builddir` set like `pkg-config` does. `prefix` is
+//! redefined from the location of the `.pc` file like `--define-prefix` does,
+//! if enabled.
+//!
+//! Relative `-I`, `-L`, `-F`, `-isystem`, `-iquote` and `-idirafter`
+//! directories are taken relative to the directory of the `.pc` file, so
+//! that relocatable SDKs work without `${pcfiledir}`.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use {compare_versions, dedup_libs, parse_search_path, Failure, Options};
+
+/// The `pkg-config` release the resolver follows, the version of its builtin
+/// `pkg-config` package.
+const VERSION: &str = "0.29.2";
+
+/// Include and link directories `pkg-config` leaves out of its output.
+const SYSTEM_INCLUDE_DIRS: &[&str] = &["/usr/include"];
+const SYSTEM_LIB_DIRS: &[&str] = &["/usr/lib", "/lib", "/usr/lib64", "/lib64"];
+
+struct Package {
+    version: String,
+    cflags: Vec<String>,
+    cflags_private: Vec<String>,
+    libs: Vec<String>,
+    libs_private: Vec<String>,
+    requires: Vec<Dependency>,
+    requires_private: Vec<Dependency>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Dependency {
+    name: String,
+    constraint: Option<(String, String)>,
+}
+
+/// Resolve the packages `queue` (`name`, optionally with version
+/// constraints), returning the output of `--cflags --libs` and the version
+/// of `name`.
+pub fn query(name: &str, queue: &[String], options: &Options)
+             -> Result<(Vec<u8>, String), Failure> {
+    let dirs = search_dirs(options);
+    let mut resolver = Resolver { dirs: &dirs, options, packages: HashMap::new(), order: vec![] };
+    for dep in queue.iter().flat_map(|package| parse_requires(package)) {
+        resolver.visit(&dep, dep.name == name)?;
+    }
+
+    let mut cflags = Vec::new();
+    let mut libs = Vec::new();
+    for package in resolver.order.iter().map(|n| &resolver.packages[n]) {
+        cflags.extend(package.cflags.iter().cloned());
+        libs.extend(package.libs.iter().cloned());
+        if options.statik {
+            cflags.extend(package.cflags_private.iter().cloned());
+            if !options.pure {
+                libs.extend(package.libs_private.iter().cloned());
+            }
+        }
+    }
+    if !options.system_cflags {
+        cflags.retain(|f| !is_system_dir(f, "-I", SYSTEM_INCLUDE_DIRS));
+    }
+    if !options.system_libs {
+        libs.retain(|f| !is_system_dir(f, "-L", SYSTEM_LIB_DIRS));
+    }
+    let cflags = dedup(cflags);
+    let libs = dedup(dedup_libs(libs));
+
+    let mut output: Vec<String> = Vec::new();
+    for flag in cflags.iter().chain(&libs) {
+        let flag = match options.sysroot {
+            // The directory of e.g. `-isystem` may be the next word.
+            Some(ref sysroot) => match output.last() {
+                Some(previous) if DIR_OPTIONS.contains(&&previous[..]) => {
+                    with_sysroot(&format!("{}{}", previous, flag), sysroot)[previous.len()..]
+                        .to_string()
+                }
+                _ => with_sysroot(flag, sysroot),
+            },
+            None => flag.clone(),
+        };
+        output.push(flag);
+    }
+    let version = resolver.packages.get(name).map(|p| p.version.clone()).unwrap_or_default();
+    Ok((output.join(" ").into_bytes(), version))
+}
+
+struct Resolver<'a> {
+    dirs: &'a [PathBuf],
+    options: &'a Options,
+    packages: HashMap<String, Package>,
+    order: Vec<String>,
+}
+
+impl<'a> Resolver<'a> {
+    /// Load `dep` and, recursively, its dependencies. `top` is whether `dep`
+    /// is a constraint on the probed package itself.
+    fn visit(&mut self, dep: &Dependency, top: bool) -> Result<(), Failure> {
+        if !self.packages.contains_key(&dep.name) {
+            let package = self.load(&dep.name)?;
+            let mut requires = package.requires.clone();
+            if self.options.statik {
+                requires.extend(package.requires_private.iter().cloned());
+            }
+            self.packages.insert(dep.name.clone(), package);
+            self.order.push(dep.name.clone());
+            for required in &requires {
+                self.visit(required, false)?;
+            }
+        }
+
+        let version = &self.packages[&dep.name].version;
+        if let Some((ref op, ref required)) = dep.constraint {
+            if !satisfies(version, op, required) {
+                return Err(if top {
+                    Failure::VersionMismatch(version.clone(),
+                                             format!("Requested '{} {} {}' but version of {} \
+                                                      is {}", dep.name, op, required, dep.name,
+                                                     version))
+                } else {
+                    Failure::Other(format!("Package dependency requirement '{} {} {}' could \
+                                            not be satisfied, version {} was found",
+                                           dep.name, op, required, version))
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn load(&self, name: &str) -> Result<Package, Failure> {
+        // Like `pkg-config`, which knows itself before any `.pc` file.
+        if name == "pkg-config" {
+            let contents = format!("Version: {}", VERSION);
+            return Ok(parse(Path::new(""), &contents, &self.globals(), false));
+        }
+        let mut files = vec![format!("{}.pc", name)];
+        if self.options.uninstalled {
+            files.insert(0, format!("{}-uninstalled.pc", name));
+        }
+        for file in &files {
+            for dir in self.dirs {
+                let path = dir.join(file);
+                if let Ok(contents) = fs::read_to_string(&path) {
+                    return Ok(parse(&path, &contents, &self.globals(),
+                                     self.options.define_prefix));
+                }
+            }
+        }
+        Err(Failure::Other(format!("Package {} was not found in the pkg-config search path",
+                                   name)))
+    }
+
+    /// The variables every package has, besides `pcfiledir`.
+    fn globals(&self) -> HashMap<String, String> {
+        let top_build_dir = self.options.top_build_dir.as_deref().unwrap_or("$(top_builddir)");
+        let mut vars = HashMap::new();
+        vars.insert("pc_top_builddir".to_string(), top_build_dir.to_string());
+        vars
+    }
+}
+
+/// `PKG_CONFIG_PATH`, followed by `PKG_CONFIG_LIBDIR` or the usual default
+/// directories.
+fn search_dirs(options: &Options) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(ref path) = options.path {
+        dirs.extend(parse_search_path(OsStr::new(path)));
+    }
+    match options.libdir {
+        Some(ref libdir) => dirs.extend(parse_search_path(OsStr::new(libdir))),
+        None if cfg!(unix) => {
+            let mut defaults = vec![
+                "/usr/local/lib/pkgconfig",
+                "/usr/local/share/pkgconfig",
+                "/usr/lib/pkgconfig",
+                "/usr/share/pkgconfig",
+            ];
+            if cfg!(target_os = "macos") {
+                defaults.insert(0, "/opt/homebrew/lib/pkgconfig");
+            }
+            dirs.extend(defaults.into_iter().map(PathBuf::from));
+        }
+        None => {}
+    }
+    dirs
+}
+
+/// Parse the `.pc` file at `path`, expanding variables as it goes, starting
+/// from `globals`. With `define_prefix`, `prefix` is set to the parent of the
+/// `pkgconfig` directory the file is in, if it is.
+fn parse(path: &Path, contents: &str, globals: &HashMap<String, String>,
+         define_prefix: bool) -> Package {
+    let mut dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    if dir.is_relative() {
+        if let Ok(cwd) = env::current_dir() {
+            dir = cwd.join(dir);
+        }
+    }
+    let mut vars = globals.clone();
+    // Escaped like `pkg-config` does, for directories with spaces.
+    vars.insert("pcfiledir".to_string(), escape(&dir.to_string_lossy()));
+    let prefix = match dir.parent() {
+        Some(parent) if define_prefix && dir.ends_with("pkgconfig") => parent.parent(),
+        _ => None,
+    };
+    let mut fields = HashMap::new();
+
+    let mut logical = String::new();
+    for line in contents.lines() {
+        if let Some(line) = line.strip_suffix('\\') {
+            logical.push_str(line);
+            continue;
+        }
+        logical.push_str(line);
+        let line = strip_comment(&logical);
+        logical = String::new();
+
+        let sep = match line.find([':', '=']) {
+            Some(i) => i,
+            None => continue,
+        };
+        let key = line[..sep].trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+            continue;
+        }
+        let value = expand(line[sep + 1..].trim(), &vars);
+        if line[sep..].starts_with('=') {
+            let value = match prefix {
+                Some(prefix) if key == "prefix" => escape(&prefix.to_string_lossy()),
+                _ => value,
+            };
+            vars.insert(key.to_string(), value);
+        } else {
+            fields.insert(key.to_string(), value);
+        }
+    }
+
+    let field = |key: &str| fields.get(key).map(|s| &s[..]).unwrap_or("");
+    let flags = |key: &str| anchor(split_words(field(key)), &dir);
+    Package {
+        version: field("Version").to_string(),
+        cflags: flags("Cflags"),
+        cflags_private: flags("Cflags.private"),
+        libs: flags("Libs"),
+        libs_private: flags("Libs.private"),
+        requires: parse_requires(field("Requires")),
+        requires_private: parse_requires(field("Requires.private")),
+    }
+}
+
+/// Remove a `#` comment, unless the `#` is escaped.
+fn strip_comment(line: &str) -> String {
+    let mut out = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('#') => out.push('#'),
+                Some(c) => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                None => out.push('\\'),
+            },
+            '#' => break,
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Substitute `${var}` references, with `$$` standing for a literal `$`.
+fn expand(value: &str, vars: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            out.push('$');
+            rest = after;
+        } else if let (true, Some(end)) = (rest.starts_with("${"), rest.find('}')) {
+            if let Some(var) = vars.get(&rest[2..end]) {
+                out.push_str(var);
+            }
+            rest = &rest[end + 1..];
+        } else {
+            out.push('$');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Split a `Cflags` or `Libs` value into words, keeping backslash escapes and
+/// escaping whitespace within quotes, as `pkg-config` prints them.
+fn split_words(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote = None;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                word.push('\\');
+                word.extend(chars.next());
+            }
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            c if Some(c) == quote => quote = None,
+            c if c.is_whitespace() && quote.is_some() => {
+                word.push('\\');
+                word.push(c);
+            }
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Parse a `Requires` list such as `foo >= 1.0, bar`.
+fn parse_requires(value: &str) -> Vec<Dependency> {
+    let mut tokens = String::new();
+    let mut in_op = false;
+    for c in value.chars() {
+        let is_op = "<>=!".contains(c);
+        if is_op != in_op {
+            tokens.push(' ');
+            in_op = is_op;
+        }
+        tokens.push(if c == ',' { ' ' } else { c });
+    }
+    let mut tokens = tokens.split_whitespace().peekable();
+    let mut deps = Vec::new();
+    while let Some(name) = tokens.next() {
+        let mut constraint = None;
+        if let Some(&op) = tokens.peek() {
+            if ["=", "!=", "<", "<=", ">", ">="].contains(&op) {
+                tokens.next();
+                let version = tokens.next().unwrap_or("").to_string();
+                constraint = Some((op.to_string(), version));
+            }
+        }
+        deps.push(Dependency { name: name.to_string(), constraint });
+    }
+    deps
+}
+
+fn satisfies(version: &str, op: &str, required: &str) -> bool {
+    let ordering = compare_versions(version, required);
+    match op {
+        "=" => ordering == Ordering::Equal,
+        "!=" => ordering != Ordering::Equal,
+        "<" => ordering == Ordering::Less,
+        "<=" => ordering != Ordering::Greater,
+        ">" => ordering == Ordering::Greater,
+        ">=" => ordering != Ordering::Less,
+        _ => false,
+    }
+}
+
+fn is_system_dir(flag: &str, prefix: &str, dirs: &[&str]) -> bool {
+    flag.strip_prefix(prefix).is_some_and(|dir| dirs.contains(&dir.trim_end_matches('/')))
+}
+
+/// Flags taking a directory, which the sysroot is prefixed to.
+const DIR_OPTIONS: &[&str] = &["-I", "-L", "-F", "-isystem", "-iquote", "-idirafter"];
+
+/// Prefix the directory of an absolute `-I`, `-L`, `-F`, `-isystem`,
+/// `-iquote` or `-idirafter` flag with the sysroot, unless it's already in
+/// the sysroot, e.g. because it's relative to `${pcfiledir}`.
+fn with_sysroot(flag: &str, sysroot: &str) -> String {
+    let sysroot = escape(sysroot.trim_end_matches('/'));
+    for prefix in DIR_OPTIONS {
+        if let Some(dir) = flag.strip_prefix(prefix) {
+            if dir.starts_with('/') && !dir.starts_with(&format!("{}/", sysroot)) {
+                return format!("{}{}{}", prefix, sysroot, dir);
+            }
+        }
+    }
+    flag.to_string()
+}
+
+/// Make the relative directories of the `DIR_OPTIONS` in `flags` relative
+/// to `dir`, whether joined to the option or the next word.
+fn anchor(flags: Vec<String>, dir: &Path) -> Vec<String> {
+    let join = |prefix: &str, rel: &str| {
+        if rel.is_empty() || Path::new(rel).is_absolute() {
+            return format!("{}{}", prefix, rel);
+        }
+        let dir = escape(&dir.to_string_lossy());
+        format!("{}{}", prefix, Path::new(&dir).join(rel).display())
+    };
+    let mut anchored: Vec<String> = Vec::with_capacity(flags.len());
+    for flag in flags {
+        let flag = match anchored.last() {
+            Some(previous) if DIR_OPTIONS.contains(&&previous[..]) => join("", &flag),
+            _ => match DIR_OPTIONS.iter().find(|prefix| flag.starts_with(*prefix)) {
+                Some(prefix) => join(prefix, &flag[prefix.len()..]),
+                None => flag,
+            },
+        };
+        anchored.push(flag);
+    }
+    anchored
+}
+
+/// Escape backslashes and whitespace in `s` with backslashes.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\\' || c.is_whitespace() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Remove all but the first occurrence of each flag, except for `-l` flags
+/// which are already deduplicated.
+fn dedup(flags: Vec<String>) -> Vec<String> {
+    let mut deduped: Vec<String> = Vec::with_capacity(flags.len());
+    for flag in flags {
+        if flag.starts_with("-l") || !deduped.contains(&flag) {
+            deduped.push(flag);
+        }
+    }
+    deduped
+}
+
+#[test]
+fn resolver_test() {
+    assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+    assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Less);
+    assert_eq!(compare_versions("2.0a", "2.0"), Ordering::Greater);
+    assert_eq!(compare_versions("1.01", "1.1"), Ordering::Equal);
+    assert!(satisfies("3.10.0.SVN", ">=", "3.9"));
+    assert!(!satisfies("3.10.0.SVN", "<", "3.10"));
+
+    assert_eq!(parse_requires("foo >= 1.0, bar baz<2"),
+               vec![Dependency { name: "foo".into(), constraint: Some((">=".into(), "1.0".into())) },
+                    Dependency { name: "bar".into(), constraint: None },
+                    Dependency { name: "baz".into(), constraint: Some(("<".into(), "2".into())) }]);
+
+    let mut vars = HashMap::new();
+    vars.insert("prefix".to_string(), "/usr".to_string());
+    assert_eq!(expand("${prefix}/lib $$ORIGIN ${unknown}", &vars), "/usr/lib $ORIGIN ");
+    assert_eq!(split_words(r#"-DA="b c" -Ia\ b  -lx"#), vec![r"-DA=b\ c", r"-Ia\ b", "-lx"]);
+    assert_eq!(strip_comment(r"Libs: -lfoo \# not a comment # comment"),
+               "Libs: -lfoo # not a comment ");
+
+    let sdk = parse(Path::new("/opt/sdk/lib/pkgconfig/sdk.pc"),
+                    "Cflags: -I../../include -I/usr/include/sdk -DX\nLibs: -L.. -lsdk",
+                    &HashMap::new(), false);
+    assert_eq!(sdk.cflags, vec!["-I/opt/sdk/lib/pkgconfig/../../include",
+                                "-I/usr/include/sdk", "-DX"]);
+    assert_eq!(sdk.libs, vec!["-L/opt/sdk/lib/pkgconfig/..", "-lsdk"]);
+
+    let system = parse(Path::new("/opt/sdk/lib/pkgconfig/sdk.pc"),
+                       "Cflags: -isystem../../include -isystem ../../sys -idirafter ../../after \
+                        -iquote/usr/include/q",
+                       &HashMap::new(), false);
+    assert_eq!(system.cflags, vec!["-isystem/opt/sdk/lib/pkgconfig/../../include", "-isystem",
+                                   "/opt/sdk/lib/pkgconfig/../../sys", "-idirafter",
+                                   "/opt/sdk/lib/pkgconfig/../../after", "-iquote/usr/include/q"]);
+
+    let spaced = parse(Path::new("/opt/my sdk/lib/pkgconfig/sdk.pc"),
+                       "prefix=${pcfiledir}/../..\nCflags: -I${prefix}/include\nLibs: -L..",
+                       &HashMap::new(), false);
+    assert_eq!(spaced.cflags, vec![r"-I/opt/my\ sdk/lib/pkgconfig/../../include"]);
+    assert_eq!(spaced.libs, vec![r"-L/opt/my\ sdk/lib/pkgconfig/.."]);
+
+    let moved = "prefix=/usr\nlibdir=${prefix}/lib\nLibs: -L${libdir}";
+    let relocated = parse(Path::new("/opt/my sdk/lib/pkgconfig/sdk.pc"), moved,
+                          &HashMap::new(), true);
+    assert_eq!(relocated.libs, vec![r"-L/opt/my\ sdk/lib"]);
+    let unmoved = parse(Path::new("/opt/sdk/sdk.pc"), moved, &HashMap::new(), true);
+    assert_eq!(unmoved.libs, vec!["-L/usr/lib"]);
+
+    assert_eq!(with_sysroot("-I/usr/include/x", "/sysroot/"), "-I/sysroot/usr/include/x");
+    assert_eq!(with_sysroot("-isystem/usr/include", "/sysroot"), "-isystem/sysroot/usr/include");
+    assert_eq!(with_sysroot("-L/sysroot/usr/lib", "/sysroot"), "-L/sysroot/usr/lib");
+    assert_eq!(with_sysroot("-lfoo", "/sysroot"), "-lfoo");
+}
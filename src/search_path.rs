@@ -0,0 +1,84 @@
+//! Parsing and validation of `PKG_CONFIG_PATH`-style lists of directories.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Split a `PKG_CONFIG_PATH`-style list of directories, separated by `:` (or
+/// `;` on Windows), skipping empty entries.
+pub fn parse_search_path(value: &OsStr) -> Vec<PathBuf> {
+    env::split_paths(value).filter(|p| !p.as_os_str().is_empty()).collect()
+}
+
+/// Parse a list of directories with `parse_search_path` and check each of
+/// them, e.g. to verify the setup of a machine before building.
+pub fn validate_search_path(value: &OsStr) -> Vec<SearchPathEntry> {
+    parse_search_path(value).into_iter().map(SearchPathEntry::check).collect()
+}
+
+/// A problem with a directory of a search path, see `validate_search_path`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SearchPathProblem {
+    /// The directory doesn't exist.
+    Missing,
+    /// The path exists, but isn't a directory.
+    NotADirectory,
+    /// The directory can't be read, with the reason.
+    Unreadable(String),
+    /// The directory contains no `.pc` files.
+    NoPcFiles,
+}
+
+/// A directory of a search path, along with the packages it provides.
+#[derive(Clone, Debug)]
+#[allow(clippy::manual_non_exhaustive)]
+pub struct SearchPathEntry {
+    pub path: PathBuf,
+    /// The names of the packages found, i.e. of the `.pc` files in the
+    /// directory, sorted.
+    pub packages: Vec<String>,
+    /// What's wrong with the directory, if anything.
+    pub problem: Option<SearchPathProblem>,
+    _priv: (),
+}
+
+impl SearchPathEntry {
+    fn check(path: PathBuf) -> SearchPathEntry {
+        let mut packages = Vec::new();
+        let problem = if !path.exists() {
+            Some(SearchPathProblem::Missing)
+        } else if !path.is_dir() {
+            Some(SearchPathProblem::NotADirectory)
+        } else {
+            match fs::read_dir(&path) {
+                Ok(entries) => {
+                    for entry in entries.flatten() {
+                        let name = entry.file_name();
+                        if let Some(package) = name.to_str().and_then(|n| n.strip_suffix(".pc")) {
+                            packages.push(package.to_string());
+                        }
+                    }
+                    packages.sort();
+                    if packages.is_empty() { Some(SearchPathProblem::NoPcFiles) } else { None }
+                }
+                Err(e) => Some(SearchPathProblem::Unreadable(e.to_string())),
+            }
+        };
+        SearchPathEntry { path, packages, problem, _priv: () }
+    }
+}
+
+impl fmt::Display for SearchPathEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}: ", self.path.display())?;
+        match self.problem {
+            None => write!(f, "{} packages", self.packages.len()),
+            Some(SearchPathProblem::Missing) => write!(f, "missing"),
+            Some(SearchPathProblem::NotADirectory) => write!(f, "not a directory"),
+            Some(SearchPathProblem::Unreadable(ref e)) => write!(f, "unreadable: {}", e),
+            Some(SearchPathProblem::NoPcFiles) => write!(f, "no .pc files"),
+        }
+    }
+}
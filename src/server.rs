@@ -0,0 +1,244 @@
+//! A cache of probe results shared by the build scripts of a workspace, see
+//! `PKG_CONFIG_RS_SERVER` in the crate documentation and `probe_server`.
+//!
+//! The first build script to miss the cache spawns a copy of itself with
+//! `PKG_CONFIG_RS_SERVE` set, whose call to `probe_server` serves the cache
+//! on a Unix socket in the profile directory of the target directory, and
+//! exits once idle. Requests are single lines, `get <key>` or
+//! `put <key> <version> <flags> <commands> <warnings>`, with every field
+//! hex-encoded, answered by `hit <version> <flags> <commands> <warnings>`,
+//! `miss` or `ok`.
+//!
+//! The socket is only trusted if it's owned by the owner of `OUT_DIR` and
+//! can't be written to by anybody else.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the server waits for requests before exiting.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the server waits for a client to send its request or read the
+/// response, so that a stalled one doesn't hold up the others.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The longest socket path `sockaddr_un` holds, with its terminating nul.
+const MAX_SOCKET_PATH: usize = 103;
+
+/// Whether the build script called `probe_server`, without which a spawned
+/// copy of it would run the build script again instead of serving.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether this process spawned the server, so that `put` waits for it.
+static SPAWNED: AtomicBool = AtomicBool::new(false);
+
+/// Serve the cache if this process was spawned as the server, exiting
+/// afterwards, and otherwise let probes use the server.
+pub fn init() {
+    if let Some(path) = env::var_os("PKG_CONFIG_RS_SERVE") {
+        serve(Path::new(&path));
+        process::exit(0);
+    }
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// The socket of the server for the build scripts sharing the target
+/// directory and profile of this one, if this is a build script.
+fn socket_path() -> Option<PathBuf> {
+    if !ENABLED.load(Ordering::SeqCst) {
+        return None;
+    }
+    // `<target dir>/[<triple>/]<profile>/build/<package>-<hash>/out`
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR")?);
+    let profile_dir = out_dir.ancestors().find(|p| p.ends_with("build"))?.parent()?;
+    let path = profile_dir.join("pkg-config-rs.sock");
+    if path.as_os_str().len() > MAX_SOCKET_PATH {
+        return None;
+    }
+    Some(path)
+}
+
+/// Whether the socket at `path` belongs to the owner of `OUT_DIR`, and only
+/// they can connect to it.
+fn trusted(path: &Path) -> bool {
+    let owner = match env::var_os("OUT_DIR").and_then(|dir| fs::metadata(dir).ok()) {
+        Some(metadata) => metadata.uid(),
+        None => return false,
+    };
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => {
+            metadata.file_type().is_socket() && metadata.uid() == owner &&
+            metadata.mode() & 0o077 == 0
+        }
+        Err(_) => false,
+    }
+}
+
+/// A cached probe.
+pub struct Answer {
+    /// The output of `--libs --cflags`.
+    pub flags: Vec<u8>,
+    pub version: String,
+    /// The commands run, as recorded in `Library::probe_commands`.
+    pub commands: Vec<String>,
+    /// What they printed on stderr, as recorded in `Library::warnings`.
+    pub warnings: Vec<String>,
+}
+
+/// The cached answer for `key`, starting the server if there's none.
+pub fn get(key: &str) -> Option<Answer> {
+    let path = socket_path()?;
+    let response = match request(&path, &format!("get {}", hex(key.as_bytes()))) {
+        Some(response) => response,
+        None => {
+            spawn(&path);
+            return None;
+        }
+    };
+    let mut fields = response.split(' ');
+    if fields.next() != Some("hit") {
+        return None;
+    }
+    let mut field = || fields.next().and_then(unhex);
+    let version = String::from_utf8(field()?).ok()?;
+    let flags = field()?;
+    let mut lines = || -> Option<Vec<String>> {
+        let lines = String::from_utf8(field()?).ok()?;
+        Some(lines.lines().map(|l| l.to_string()).collect())
+    };
+    let commands = lines()?;
+    let warnings = lines()?;
+    Some(Answer { flags, version, commands, warnings })
+}
+
+/// Cache the results of a probe for `key`, waiting a little for the server
+/// if this process just spawned it.
+pub fn put(key: &str, answer: &Answer) {
+    let path = match socket_path() {
+        Some(path) => path,
+        None => return,
+    };
+    let line = format!("put {} {} {} {} {}", hex(key.as_bytes()), hex(answer.version.as_bytes()),
+                       hex(&answer.flags), hex(answer.commands.join("\n").as_bytes()),
+                       hex(answer.warnings.join("\n").as_bytes()));
+    let attempts = if SPAWNED.load(Ordering::SeqCst) { 20 } else { 1 };
+    for _ in 0..attempts {
+        if request(&path, &line).is_some() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn request(path: &Path, line: &str) -> Option<String> {
+    if !trusted(path) {
+        return None;
+    }
+    let mut stream = UnixStream::connect(path).ok()?;
+    stream.set_read_timeout(Some(Duration::from_secs(5))).ok()?;
+    stream.write_all(format!("{}\n", line).as_bytes()).ok()?;
+    stream.shutdown(Shutdown::Write).ok()?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+    // Only the newline is trimmed, as the last field may be empty.
+    Some(response.trim_end_matches('\n').to_string())
+}
+
+/// Start the server in the background, as a copy of this build script.
+fn spawn(path: &Path) {
+    if let Ok(exe) = env::current_exe() {
+        let spawned = Command::new(exe).env("PKG_CONFIG_RS_SERVE", path)
+                                       .stdin(Stdio::null())
+                                       .stdout(Stdio::null())
+                                       .stderr(Stdio::null())
+                                       .spawn();
+        if spawned.is_ok() {
+            SPAWNED.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+fn serve(path: &Path) {
+    // Another build script's server may have won the race.
+    if UnixStream::connect(path).is_ok() {
+        return;
+    }
+    // Only a stale socket of a server that exited is replaced.
+    match fs::symlink_metadata(path) {
+        Ok(ref metadata) if metadata.file_type().is_socket() => {
+            let _ = fs::remove_file(path);
+        }
+        Ok(_) => return,
+        Err(_) => {}
+    }
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(_) => return,
+    };
+    if fs::set_permissions(path, fs::Permissions::from_mode(0o600)).is_err() {
+        let _ = fs::remove_file(path);
+        return;
+    }
+    let last_request = Arc::new(Mutex::new(Instant::now()));
+    let idle = last_request.clone();
+    let socket = path.to_path_buf();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        if idle.lock().unwrap().elapsed() > IDLE_TIMEOUT {
+            let _ = fs::remove_file(&socket);
+            process::exit(0);
+        }
+    });
+
+    let mut cache = HashMap::new();
+    for stream in listener.incoming().flatten() {
+        handle(stream, &mut cache);
+        *last_request.lock().unwrap() = Instant::now();
+    }
+}
+
+fn handle(mut stream: UnixStream, cache: &mut HashMap<String, String>) {
+    if stream.set_read_timeout(Some(CLIENT_TIMEOUT)).is_err() ||
+       stream.set_write_timeout(Some(CLIENT_TIMEOUT)).is_err() {
+        return;
+    }
+    let mut line = String::new();
+    if BufReader::new(&stream).read_line(&mut line).is_err() {
+        return;
+    }
+    let mut fields = line.trim_end_matches('\n').splitn(3, ' ');
+    let response = match (fields.next(), fields.next(), fields.next()) {
+        (Some("get"), Some(key), None) => match cache.get(key) {
+            Some(value) => format!("hit {}", value),
+            None => "miss".to_string(),
+        },
+        (Some("put"), Some(key), Some(value)) => {
+            cache.insert(key.to_string(), value.to_string());
+            "ok".to_string()
+        }
+        _ => return,
+    };
+    let _ = stream.write_all(format!("{}\n", response).as_bytes());
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes().chunks(2).map(|pair| match *pair {
+        [hi, lo] => Some((char::from(hi).to_digit(16)? * 16 + char::from(lo).to_digit(16)?) as u8),
+        _ => None,
+    }).collect()
+}
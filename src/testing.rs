@@ -0,0 +1,66 @@
+//! Helpers for testing build scripts that use this crate.
+//!
+//! ```
+//! use pkg_config::testing::{capture_metadata, with_env};
+//!
+//! let (result, metadata) = with_env(&[("PKG_CONFIG_PATH", Some("/nonexistent"))], || {
+//!     capture_metadata(|| pkg_config::probe_library("zzz-nonexistent"))
+//! });
+//! assert!(result.is_err());
+//! assert!(metadata.iter().all(|m| m.starts_with("cargo:")));
+//! ```
+
+use std::env;
+use std::ffi::OsString;
+use std::sync::{Mutex, MutexGuard};
+
+use capture;
+
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Run `f` with the environment variables `vars` set, or removed for `None`,
+/// and restore their previous values afterwards, even if `f` panics.
+///
+/// Calls are serialized with a global lock so that tests running in parallel
+/// don't see each other's changes, which means that calls can't be nested.
+pub fn with_env<F, R>(vars: &[(&str, Option<&str>)], f: F) -> R
+    where F: FnOnce() -> R
+{
+    // Taken first, so that the values saved aren't those of another call.
+    let lock = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let _guard = Restore {
+        saved: vars.iter().map(|&(k, _)| (k.to_string(), env::var_os(k))).collect(),
+        _lock: lock,
+    };
+    for &(k, v) in vars {
+        match v {
+            Some(v) => env::set_var(k, v),
+            None => env::remove_var(k),
+        }
+    }
+    f()
+}
+
+struct Restore {
+    saved: Vec<(String, Option<OsString>)>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        for (k, v) in self.saved.drain(..) {
+            match v {
+                Some(v) => env::set_var(k, v),
+                None => env::remove_var(k),
+            }
+        }
+    }
+}
+
+/// Run `f`, returning its result and the `cargo:` lines printed by this crate
+/// on the current thread meanwhile, instead of printing them.
+pub fn capture_metadata<F, R>(f: F) -> (R, Vec<String>)
+    where F: FnOnce() -> R
+{
+    capture::capture(f)
+}
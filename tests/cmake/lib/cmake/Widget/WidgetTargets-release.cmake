@@ -0,0 +1,13 @@
+# Generated by CMake for configuration "Release".
+
+set_property(TARGET Widget::core APPEND PROPERTY IMPORTED_CONFIGURATIONS RELEASE)
+set_target_properties(Widget::core PROPERTIES
+  IMPORTED_LOCATION_RELEASE "${_IMPORT_PREFIX}/lib/libwidget.so.2.4.1"
+  IMPORTED_SONAME_RELEASE "libwidget.so.2"
+  )
+
+set_property(TARGET Widget::extra APPEND PROPERTY IMPORTED_CONFIGURATIONS RELEASE)
+set_target_properties(Widget::extra PROPERTIES
+  IMPORTED_LINK_INTERFACE_LANGUAGES_RELEASE "CXX"
+  IMPORTED_LOCATION_RELEASE "${_IMPORT_PREFIX}/lib/libwidget_extra.a"
+  )
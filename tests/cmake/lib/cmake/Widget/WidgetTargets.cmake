@@ -0,0 +1,29 @@
+# Generated by CMake
+
+# Compute the installation prefix relative to this file.
+get_filename_component(_IMPORT_PREFIX "${CMAKE_CURRENT_LIST_FILE}" PATH)
+get_filename_component(_IMPORT_PREFIX "${_IMPORT_PREFIX}" PATH)
+get_filename_component(_IMPORT_PREFIX "${_IMPORT_PREFIX}" PATH)
+get_filename_component(_IMPORT_PREFIX "${_IMPORT_PREFIX}" PATH)
+
+# Create imported target Widget::core
+add_library(Widget::core SHARED IMPORTED)
+
+set_target_properties(Widget::core PROPERTIES
+  INTERFACE_COMPILE_DEFINITIONS "WIDGET_SHARED;WIDGET_LEVEL=2"
+  INTERFACE_INCLUDE_DIRECTORIES "${_IMPORT_PREFIX}/include;${_IMPORT_PREFIX}/include/widget"
+  INTERFACE_LINK_LIBRARIES "Threads::Threads;m;$<LINK_ONLY:dl>"
+)
+
+# Create imported target Widget::extra
+add_library(Widget::extra STATIC IMPORTED)
+
+set_target_properties(Widget::extra PROPERTIES
+  INTERFACE_LINK_LIBRARIES "Widget::core"
+)
+
+# Load information for each installed configuration.
+file(GLOB _cmake_config_files "${CMAKE_CURRENT_LIST_DIR}/WidgetTargets-*.cmake")
+foreach(_cmake_config_file IN LISTS _cmake_config_files)
+  include("${_cmake_config_file}")
+endforeach()
@@ -0,0 +1 @@
+int prefixed(void);
@@ -0,0 +1 @@
+void widget(void);
@@ -1,10 +1,14 @@
 extern crate pkg_config;
+#[cfg(feature = "cc")]
+extern crate cc;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 #[macro_use]
 extern crate lazy_static;
 
 use pkg_config::Error;
 use std::env;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
 
 lazy_static! {
@@ -16,19 +20,66 @@ fn reset() {
         if k.contains("DYNAMIC") ||
            k.contains("STATIC") ||
            k.contains("PKG_CONFIG_ALLOW_CROSS") ||
+           k.contains("PKG_CONFIG_SYSROOT_DIR") ||
+           k.contains("PKG_CONFIG_PATH_") ||
+           k.contains("PKG_CONFIG_LIBDIR") ||
            k.contains("FOO_NO_PKG_CONFIG") {
             env::remove_var(&k);
         }
     }
     env::remove_var("TARGET");
     env::remove_var("HOST");
-    env::set_var("PKG_CONFIG_PATH", &env::current_dir().unwrap().join("tests"));
+    env::set_var("OUT_DIR", env::temp_dir());
+    env::set_var("PKG_CONFIG_PATH", env::current_dir().unwrap().join("tests"));
 }
 
 fn find(name: &str) -> Result<pkg_config::Library, Error> {
     pkg_config::probe_library(name)
 }
 
+/// A directory of its own under the temporary directory, removed with
+/// everything in it when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("pkg-config-rs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl std::ops::Deref for TempDir {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Record the cargo metadata that probes with `config` print and that
+/// starts with `prefix`, without it, e.g. `"warning="` for their warnings.
+fn record_metadata(config: &mut pkg_config::Config, prefix: &'static str)
+                   -> Arc<Mutex<Vec<String>>> {
+    let metadata = Arc::new(Mutex::new(Vec::new()));
+    let sink = metadata.clone();
+    config.trace(move |event| {
+        if let pkg_config::Trace::Metadata(line) = *event {
+            if let Some(line) = line.strip_prefix(prefix) {
+                sink.lock().unwrap().push(line.to_owned());
+            }
+        }
+    });
+    metadata
+}
+
 #[test]
 fn cross_disabled() {
     let _g = LOCK.lock();
@@ -36,7 +87,7 @@ fn cross_disabled() {
     env::set_var("TARGET", "foo");
     env::set_var("HOST", "bar");
     match find("foo") {
-        Err(Error::CrossCompilation) => {},
+        Err(Error::CrossCompilation { .. }) => {},
         x => panic!("Error::CrossCompilation expected, found `{:?}`", x),
     }
 }
@@ -51,6 +102,38 @@ fn cross_enabled() {
     find("foo").unwrap();
 }
 
+#[test]
+fn cross_forbidden() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("TARGET", "foo");
+    env::set_var("HOST", "bar");
+    env::set_var("PKG_CONFIG_SYSROOT_DIR", "/");
+    assert_eq!(find("foo").unwrap().version, "3.10.0.SVN");
+    env::set_var("PKG_CONFIG_ALLOW_CROSS", "0");
+    match find("foo") {
+        Err(Error::CrossCompilation { ref reason }) if reason.contains("ALLOW_CROSS=0") => {},
+        x => panic!("Error::CrossCompilation expected, found `{:?}`", x),
+    }
+    env::set_var("PKG_CONFIG_ALLOW_CROSS", "false");
+    assert!(!pkg_config::target_supported());
+}
+
+#[test]
+fn cross_target_scoped() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("TARGET", "foo-bar");
+    env::set_var("HOST", "bar");
+    env::set_var("PKG_CONFIG_PATH_foo_bar", env::var_os("PKG_CONFIG_PATH").unwrap());
+    assert_eq!(find("foo").unwrap().version, "3.10.0.SVN");
+    env::set_var("PKG_CONFIG_ALLOW_CROSS", "0");
+    match find("foo") {
+        Err(Error::CrossCompilation { .. }) => {},
+        x => panic!("Error::CrossCompilation expected, found `{:?}`", x),
+    }
+}
+
 #[test]
 fn package_disabled() {
     let _g = LOCK.lock();
@@ -116,3 +199,2193 @@ fn version() {
     reset();
     assert_eq!(&find("foo").unwrap().version[..], "3.10.0.SVN");
 }
+
+#[test]
+fn probe_headers() {
+    let _g = LOCK.lock();
+    reset();
+    let includes = pkg_config::Config::new().probe_headers("headers").unwrap();
+    assert!(includes.include_paths.contains(&PathBuf::from("/usr/include/headers")));
+    assert_eq!(includes.defines.get("HEADERS_NO_DEBUG"), Some(&None));
+    assert_eq!(includes.defines.get("HEADERS_MAX"), Some(&Some("4".to_owned())));
+    assert_eq!(includes.version, "1.2.0");
+}
+
+#[test]
+fn compile_args() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("escape").unwrap();
+    assert_eq!(lib.compile_args(pkg_config::Language::Cxx),
+               vec!["-x".to_owned(),
+                    "c++".to_owned(),
+                    "-Iinclude path with spaces".to_owned(),
+                    "-DA=\"escaped string' literal\"".to_owned(),
+                    "-DB=ESCAPED IDENTIFIER".to_owned(),
+                    "-DFOX=🦊".to_owned()]);
+}
+
+#[test]
+fn version_mismatch() {
+    let _g = LOCK.lock();
+    reset();
+    match pkg_config::Config::new().at_most_version("3.0").probe("foo") {
+        Err(Error::VersionMismatch { package, found, required }) => {
+            assert_eq!(package, "foo");
+            assert_eq!(found, "3.10.0.SVN");
+            assert_eq!(required, "<= 3.0");
+        }
+        x => panic!("Error::VersionMismatch expected, found `{:?}`", x),
+    }
+    pkg_config::Config::new().atleast_version("3.0").at_most_version("4.0")
+        .probe("foo").unwrap();
+}
+
+#[test]
+fn probe_any() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new().probe_any(&["nonexistent", "foo"]).unwrap();
+    assert_eq!(lib.version, "3.10.0.SVN");
+
+    match pkg_config::Config::new().probe_any(&["nonexistent", "missing"]) {
+        Err(Error::AlternativesFailed { tried }) => {
+            let names = tried.iter().map(|t| &t.0[..]).collect::<Vec<_>>();
+            assert_eq!(names, ["nonexistent", "missing"]);
+        }
+        x => panic!("Error::AlternativesFailed expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn artifacts() {
+    use pkg_config::ArtifactKind;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    let _g = LOCK.lock();
+    reset();
+    let dir = TempDir::new("artifacts");
+    fs::File::create(dir.join("libshared.so.1.2.3")).unwrap();
+    symlink("libshared.so.1.2.3", dir.join("libshared.so.1")).unwrap();
+    symlink("libshared.so.1", dir.join("libshared.so")).unwrap();
+    fs::File::create(dir.join("libimport.dll.a")).unwrap();
+    fs::File::create(dir.join("libarchive.a")).unwrap();
+    fs::write(dir.join("artifacts.pc"), format!(
+        "Name: Artifacts\nDescription: Artifacts\nVersion: 1.0\n\
+         Libs: -L{} -lshared -limport -larchive -lmissing\n",
+        dir.display())).unwrap();
+    env::set_var("PKG_CONFIG_PATH", &*dir);
+
+    let artifacts = find("artifacts").unwrap().artifacts();
+    assert_eq!(artifacts.len(), 3);
+    assert_eq!(artifacts[0].name, "shared");
+    assert_eq!(artifacts[0].kind, ArtifactKind::Shared);
+    assert_eq!(artifacts[0].path, dir.join("libshared.so"));
+    assert_eq!(artifacts[0].real_path,
+               fs::canonicalize(dir.join("libshared.so.1.2.3")).unwrap());
+    assert_eq!(artifacts[0].soname, Some("libshared.so.1".to_owned()));
+    assert_eq!(artifacts[1].kind, ArtifactKind::ImportLibrary);
+    assert_eq!(artifacts[2].kind, ArtifactKind::Static);
+    assert_eq!(artifacts[2].soname, None);
+}
+
+#[test]
+fn whole_archive() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new()
+        .statik(pkg_config::Statik::Force)
+        .whole_archive(vec!["gcc"])
+        .probe("foo")
+        .unwrap();
+    assert_eq!(lib.whole_archive, vec!["gcc".to_owned()]);
+
+    let lib = pkg_config::Config::new().whole_archive(vec!["gcc"]).probe("foo").unwrap();
+    assert!(lib.whole_archive.is_empty());
+}
+
+#[test]
+fn self_check() {
+    let _g = LOCK.lock();
+    reset();
+    let report = pkg_config::self_check();
+    assert!(report.is_ok(), "{}", report);
+    assert_eq!(report.executable, "pkg-config");
+    assert!(!report.version.unwrap().is_empty());
+    assert!(report.search_path[0].packages.contains(&"foo".to_owned()));
+
+    env::set_var("PKG_CONFIG", "pkg-config-that-does-not-exist");
+    let report = pkg_config::self_check();
+    env::remove_var("PKG_CONFIG");
+    assert!(!report.is_ok());
+    match report.version {
+        Err(Error::Command { .. }) => {}
+        x => panic!("Error::Command expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+fn fragment_filter() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new().fragment_filter("l").probe("foo").unwrap();
+    assert!(lib.libs.contains(&"gcc".to_string()));
+    assert!(lib.link_paths.is_empty());
+}
+
+#[test]
+fn isystem() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("isystem").unwrap();
+    assert_eq!(lib.include_paths, vec![PathBuf::from("/inc")]);
+    assert_eq!(lib.system_include_paths, vec![PathBuf::from("/sys1"), PathBuf::from("/sys2")]);
+    assert_eq!(lib.quote_include_paths, vec![PathBuf::from("/quote")]);
+    assert_eq!(lib.after_include_paths, vec![PathBuf::from("/after")]);
+    assert_eq!(lib.include_paths_with_system(),
+               vec![PathBuf::from("/inc"), PathBuf::from("/sys1"), PathBuf::from("/sys2")]);
+
+    let includes = pkg_config::Config::new().probe_headers("isystem").unwrap();
+    assert_eq!(includes.system_include_paths, lib.system_include_paths);
+}
+
+#[test]
+fn weak_framework() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("weak-framework").unwrap();
+    assert_eq!(lib.frameworks, vec!["Foundation".to_owned()]);
+    assert_eq!(lib.weak_frameworks, vec!["Metal".to_owned(), "MetalKit".to_owned()]);
+    assert_eq!(lib.needed_frameworks, vec!["CoreVideo".to_owned()]);
+}
+
+#[test]
+fn pure() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new()
+        .statik(pkg_config::Statik::Force)
+        .pure(true)
+        .probe("pure")
+        .unwrap();
+    assert_eq!(lib.libs, vec!["b".to_owned(), "a".to_owned()]);
+}
+
+#[test]
+fn explicit_target() {
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    config.target("aarch64-unknown-linux-gnu").host("x86_64-unknown-linux-gnu");
+    match config.probe("foo") {
+        Err(Error::CrossCompilation { .. }) => {},
+        x => panic!("Error::CrossCompilation expected, found `{:?}`", x),
+    }
+
+    env::set_var("PKG_CONFIG_ALLOW_CROSS", "1");
+    env::set_var("PKG_CONFIG_PATH_aarch64-unknown-linux-gnu", env::var_os("PKG_CONFIG_PATH").unwrap());
+    env::set_var("PKG_CONFIG_PATH", "/nonexistent");
+    let lib = config.probe("foo");
+    env::remove_var("PKG_CONFIG_PATH_aarch64-unknown-linux-gnu");
+    assert_eq!(lib.unwrap().version, "3.10.0.SVN");
+}
+
+#[test]
+#[cfg(feature = "libpkgconf")]
+fn libpkgconf() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("PKG_CONFIG", "/nonexistent/pkg-config");
+    let lib = pkg_config::Config::new().probe("foo");
+    let mismatch = pkg_config::Config::new().atleast_version("4").probe("foo");
+    let missing = pkg_config::Config::new().probe("nonexistent");
+    env::remove_var("PKG_CONFIG");
+    assert_eq!(lib.unwrap().version, "3.10.0.SVN");
+    match mismatch {
+        Err(Error::VersionMismatch { ref found, .. }) if found == "3.10.0.SVN" => {}
+        x => panic!("Error::VersionMismatch expected, found `{:?}`", x),
+    }
+    match missing {
+        Err(Error::InProcess { .. }) => {}
+        x => panic!("Error::InProcess expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+fn standalone() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("TARGET", "foo");
+    env::set_var("HOST", "bar");
+    let lib = pkg_config::Config::new().standalone(true).probe("foo");
+    assert_eq!(lib.unwrap().version, "3.10.0.SVN");
+}
+
+#[test]
+#[cfg(feature = "vendored-resolver")]
+fn vendored_resolver() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("PKG_CONFIG", "/nonexistent/pkg-config");
+    let lib = pkg_config::Config::new().probe("foo");
+    let mismatch = pkg_config::Config::new().atleast_version("4").probe("foo");
+    env::set_var("PKG_CONFIG_NO_VENDORED", "1");
+    let disabled = pkg_config::Config::new().probe("foo");
+    env::remove_var("PKG_CONFIG_NO_VENDORED");
+    env::remove_var("PKG_CONFIG");
+
+    let lib = lib.unwrap();
+    assert_eq!(lib.version, "3.10.0.SVN");
+    assert_eq!(lib.libs, vec!["coregrind-amd64-linux", "vex-amd64-linux", "gcc"]);
+    assert_eq!(lib.include_paths, vec![PathBuf::from("/usr/include/valgrind")]);
+    // The failed run of the missing `pkg-config` isn't what found it.
+    assert!(lib.probe_commands.is_empty(), "{:?}", lib.probe_commands);
+    match mismatch {
+        Err(Error::VersionMismatch { ref found, .. }) if found == "3.10.0.SVN" => {}
+        x => panic!("Error::VersionMismatch expected, found `{:?}`", x),
+    }
+    match disabled {
+        Err(Error::Command { .. }) => {}
+        x => panic!("Error::Command expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn trace() {
+    let _g = LOCK.lock();
+    reset();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    pkg_config::Config::new()
+        .cargo_metadata(true)
+        .trace(move |event| sink.lock().unwrap().push(event.to_string()))
+        .probe("foo")
+        .unwrap();
+    let events = events.lock().unwrap();
+    assert!(events[0].starts_with("running ") && events[0].contains("PKG_CONFIG_PATH="));
+    assert!(events.iter().any(|e| e.starts_with("flags of foo: ") && e.contains("-lgcc")));
+    assert!(events.iter().any(|e| e == "cargo:rustc-link-lib=gcc"));
+}
+
+#[test]
+fn error_predicates() {
+    use std::error::Error as StdError;
+
+    let _g = LOCK.lock();
+    reset();
+    let missing = find("nonexistent").unwrap_err();
+    assert!(missing.is_not_found());
+    assert!(!missing.is_tool_missing());
+    let mismatch = pkg_config::Config::new().atleast_version("4").probe("foo").unwrap_err();
+    assert!(!mismatch.is_not_found());
+    let requires = find("missing-requires").unwrap_err();
+    assert!(!requires.is_not_found(), "{}", requires);
+
+    env::set_var("PKG_CONFIG", "/nonexistent/pkg-config");
+    let tool = pkg_config::get_variable("foo", "prefix");
+    env::remove_var("PKG_CONFIG");
+    let tool = tool.unwrap_err();
+    assert!(tool.is_tool_missing());
+    assert!(tool.source().is_some());
+}
+
+fn policy(lib: &mut pkg_config::Library) {
+    lib.libs.retain(|l| l != "forbidden");
+    // Moves the archives of `postprocess` to those of `linkage`.
+    for path in &mut lib.link_paths {
+        if path.as_path() == std::path::Path::new("/opt/postprocess/lib") {
+            *path = env::current_dir().unwrap().join("tests/linkage");
+        }
+    }
+}
+
+#[test]
+fn postprocessor() {
+    let _g = LOCK.lock();
+    reset();
+    pkg_config::register_postprocessor(policy);
+    let mut config = pkg_config::Config::new();
+    let metadata = record_metadata(&mut config, "");
+    let lib = config.probe("postprocess").unwrap();
+    let dir = env::current_dir().unwrap().join("tests/linkage");
+    assert_eq!(lib.libs, vec!["keep", "staticpart"]);
+    assert_eq!(*metadata.lock().unwrap(),
+               vec![format!("rustc-link-search=native={}", dir.display()),
+                    "rustc-link-lib=keep".to_owned(),
+                    "rustc-link-lib=staticpart".to_owned()]);
+
+    let lib = pkg_config::Config::new().statik(pkg_config::Statik::Yes)
+                                       .cargo_metadata(false)
+                                       .probe("postprocess")
+                                       .unwrap();
+    assert_eq!(lib.static_libs, vec!["staticpart"]);
+    assert_eq!(lib.linkage["keep"], pkg_config::Linkage::Dynamic);
+
+    let lib = pkg_config::Config::new().cargo_metadata(false)
+                                       .probe_any(&["nonexistent", "postprocess"])
+                                       .unwrap();
+    assert_eq!(lib.libs, vec!["keep", "staticpart"]);
+}
+
+#[test]
+fn search_path() {
+    use pkg_config::SearchPathProblem;
+    use std::ffi::OsString;
+
+    let paths = env::join_paths(["tests", "", "nonexistent", "Cargo.toml", "src"]).unwrap();
+    assert_eq!(pkg_config::parse_search_path(&paths),
+               vec![PathBuf::from("tests"), PathBuf::from("nonexistent"),
+                    PathBuf::from("Cargo.toml"), PathBuf::from("src")]);
+    assert!(pkg_config::parse_search_path(&OsString::new()).is_empty());
+
+    let report = pkg_config::validate_search_path(&paths);
+    assert_eq!(report[0].problem, None);
+    assert!(report[0].packages.contains(&"foo".to_owned()));
+    assert_eq!(report[1].problem, Some(SearchPathProblem::Missing));
+    assert_eq!(report[2].problem, Some(SearchPathProblem::NotADirectory));
+    assert_eq!(report[3].problem, Some(SearchPathProblem::NoPcFiles));
+    assert_eq!(report[1].to_string(), "nonexistent: missing");
+}
+
+#[test]
+fn or_else() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new()
+        .or_else(|err| {
+            assert!(err.is_not_found());
+            let mut lib = pkg_config::Library::from_parts(vec!["vendored".to_owned()],
+                                                          vec![PathBuf::from("/opt/vendored/lib")],
+                                                          vec![PathBuf::from("/opt/vendored/include")],
+                                                          "1.0");
+            lib.static_libs = lib.libs.clone();
+            Ok(lib)
+        })
+        .probe_any(&["nonexistent", "also-nonexistent"])
+        .unwrap();
+    assert_eq!(lib.libs, vec!["vendored"]);
+    assert_eq!(lib.version, "1.0");
+
+    let lib = pkg_config::Config::new().or_else(|_| panic!("unexpected fallback")).probe("foo");
+    assert_eq!(lib.unwrap().version, "3.10.0.SVN");
+}
+
+#[test]
+fn enforce_lock() {
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    config.enforce_lock("tests/pkg-config.lock");
+    assert_eq!(config.probe("foo").unwrap().version, "3.10.0.SVN");
+    assert_eq!(config.probe("framework").unwrap().version, "3.10.0.SVN");
+    match config.probe("escape") {
+        Err(Error::LockMismatch { ref locked, ref found, .. })
+            if locked == "4.1.0" && found == "4.2.0" => {}
+        x => panic!("Error::LockMismatch expected, found `{:?}`", x),
+    }
+    match pkg_config::Config::new().enforce_lock("tests/nonexistent.lock").probe("foo") {
+        Err(Error::LockFile { .. }) => {}
+        x => panic!("Error::LockFile expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+fn library_builder() {
+    let lib = pkg_config::library::Builder::new()
+        .link_path("/opt/vendored/lib")
+        .static_lib("vendored")
+        .lib("z")
+        .define("VENDORED", Some("1"))
+        .version("1.0")
+        .build();
+    assert_eq!(lib.static_libs, vec!["vendored"]);
+    assert_eq!(lib.defines["VENDORED"], Some("1".to_owned()));
+
+    let mut config = pkg_config::Config::new();
+    let metadata = record_metadata(&mut config, "");
+    lib.emit_cargo_metadata(&config);
+    assert_eq!(*metadata.lock().unwrap(),
+               vec!["rustc-link-search=native=/opt/vendored/lib",
+                    "rustc-link-lib=static=vendored",
+                    "rustc-link-lib=z"]);
+}
+
+#[test]
+fn lock_record() {
+    let _g = LOCK.lock();
+    reset();
+    let dir = TempDir::new("lock");
+    let path = dir.join("pkg-config.lock");
+    std::fs::write(&path, "# reviewed\nfoo = 3.9\nbar = 1.0\n").unwrap();
+    let foo = find("foo").unwrap();
+    let escape = find("escape").unwrap();
+    pkg_config::lock::record("foo", &foo, &path).unwrap();
+    pkg_config::lock::record("escape", &escape, &path).unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let entries = pkg_config::lock::read(&path).unwrap();
+
+    assert_eq!(contents, "# reviewed\nfoo = 3.10.0.SVN\nbar = 1.0\nescape = 4.2.0\n");
+    assert_eq!(entries.len(), 3);
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn testing_helpers() {
+    use pkg_config::testing::{capture_metadata, with_env};
+
+    let _g = LOCK.lock();
+    reset();
+    let path = env::var("PKG_CONFIG_PATH").unwrap();
+    env::set_var("FOO_STATIC", "1");
+    let (lib, metadata) = with_env(&[("PKG_CONFIG_PATH", Some(&path)), ("FOO_STATIC", None)], || {
+        capture_metadata(|| find("foo"))
+    });
+    assert_eq!(env::var("FOO_STATIC").unwrap(), "1");
+    assert!(lib.unwrap().libs.contains(&"gcc".to_string()));
+    assert_eq!(metadata[0], "cargo:rustc-link-search=native=/usr/lib/valgrind");
+    assert!(metadata.contains(&"cargo:rustc-link-lib=gcc".to_string()));
+}
+
+#[test]
+fn tool_package() {
+    let _g = LOCK.lock();
+    reset();
+    let tools = pkg_config::Config::new().probe_tool_package("codegen").unwrap();
+    assert_eq!(tools.version, "1.2.0");
+    assert_eq!(tools.tools.len(), 1);
+    assert!(tools.tools["codegen"].ends_with("tests/bin/codegen"));
+
+    match pkg_config::Config::new().probe_tool_package("codegen-broken") {
+        Err(Error::ToolNotExecutable { ref variable, .. }) if variable == "codegen" => {}
+        x => panic!("Error::ToolNotExecutable expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn probe_commands() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new().atleast_version("3").probe("foo").unwrap();
+    let path = env::var("PKG_CONFIG_PATH").unwrap();
+    assert_eq!(lib.probe_commands.len(), 1);
+    assert!(lib.probe_commands[0].contains(&format!("PKG_CONFIG_PATH={} ", path)));
+    assert!(lib.probe_commands[0].ends_with(" --libs --cflags 'foo >= 3'"));
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn arg_for() {
+    use pkg_config::Query;
+
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new().arg_for(Query::Flags, "--define-variable=prefix=/custom")
+                                       .probe("foo")
+                                       .unwrap();
+    assert_eq!(lib.link_paths, vec![PathBuf::from("/custom/lib/valgrind")]);
+    assert_eq!(lib.version, "3.10.0.SVN");
+    assert_eq!(lib.probe_commands.len(), 2);
+    assert!(lib.probe_commands[0].ends_with(" --modversion foo"), "{:?}", lib.probe_commands);
+    assert!(lib.probe_commands[1].ends_with(" --libs --cflags --define-variable=prefix=/custom foo"),
+            "{:?}", lib.probe_commands);
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn single_invocation() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("PKG_CONFIG", env::current_dir().unwrap().join("tests/bin/pkg-config-reference"));
+    let lib = pkg_config::Config::new().cargo_metadata(false).probe("foo");
+    env::remove_var("PKG_CONFIG");
+    let lib = lib.unwrap();
+    assert_eq!(lib.version, "1.0");
+    assert_eq!(lib.libs, vec!["fake"]);
+    assert_eq!(lib.probe_commands.len(), 1);
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn single_invocation_pkgconf() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("PKG_CONFIG", "pkgconf");
+    let spawns = Arc::new(AtomicUsize::new(0));
+    let counter = spawns.clone();
+    let mut config = pkg_config::Config::new();
+    config.cargo_metadata(false).on_command(move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    });
+    // The first probe also asks pkgconf what it is and where it searches.
+    config.probe("foo").unwrap();
+    spawns.store(0, Ordering::SeqCst);
+    let lib = config.probe("foo");
+    env::remove_var("PKG_CONFIG");
+    let lib = lib.unwrap();
+    assert_eq!(lib.version, "3.10.0.SVN");
+    assert!(lib.libs.contains(&"gcc".to_string()));
+    assert_eq!(spawns.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn stderr_warnings() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("PKG_CONFIG", env::current_dir().unwrap().join("tests/bin/pkg-config-warnings"));
+    let lib = pkg_config::Config::new().cargo_metadata(false).probe("foo");
+    env::remove_var("PKG_CONFIG");
+    let lib = lib.unwrap();
+    assert_eq!(lib.libs, vec!["fake"]);
+    assert_eq!(lib.warnings, vec!["Warning: duplicate definition of Libs in warn.pc"]);
+    assert!(find("foo").unwrap().warnings.is_empty());
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn emit_dep_paths() {
+    use pkg_config::testing::capture_metadata;
+
+    let lib = pkg_config::library::Builder::new()
+        .link_path("/opt/foo/lib")
+        .link_path("/opt/bar/lib")
+        .lib("foo")
+        .lib("bar")
+        .build();
+    let mut config = pkg_config::Config::new();
+    let (result, metadata) = capture_metadata(|| lib.emit_dep_paths(&config, "native"));
+    result.unwrap();
+    assert_eq!(metadata, vec!["cargo:native_lib_paths=/opt/foo/lib:/opt/bar/lib",
+                              "cargo:native_libs=foo,bar"]);
+
+    config.cargo_metadata(false);
+    let (result, metadata) = capture_metadata(|| lib.emit_dep_paths(&config, "native"));
+    result.unwrap();
+    assert!(metadata.is_empty());
+
+    // A path containing the separator can't be joined.
+    let separator = if cfg!(windows) { ';' } else { ':' };
+    let lib = pkg_config::library::Builder::new().link_path(format!("/opt/a{}b/lib", separator))
+                                                 .lib("foo")
+                                                 .build();
+    let (result, metadata) = capture_metadata(|| {
+        lib.emit_dep_paths(&pkg_config::Config::new(), "native")
+    });
+    assert!(result.is_err());
+    assert!(metadata.is_empty());
+}
+
+#[test]
+fn fingerprint() {
+    let _g = LOCK.lock();
+    reset();
+    let base = pkg_config::Config::new().fingerprint();
+    assert_eq!(base.len(), 16);
+    assert_eq!(pkg_config::Config::new().fingerprint(), base);
+    assert!(pkg_config::Config::new().atleast_version("1").fingerprint() != base);
+    assert!(pkg_config::Config::new().statik(true).fingerprint() != base);
+
+    env::set_var("FOO_STATIC", "1");
+    assert!(pkg_config::Config::new().fingerprint() != base);
+    env::remove_var("FOO_STATIC");
+    assert_eq!(pkg_config::Config::new().fingerprint(), base);
+
+    // Only variables that can change a result count.
+    env::set_var("PKG_CONFIG_TRACE", "1");
+    env::set_var("PKG_CONFIG_RS_METADATA_FILE", "/tmp/metadata");
+    let traced = pkg_config::Config::new().fingerprint();
+    env::remove_var("PKG_CONFIG_TRACE");
+    env::remove_var("PKG_CONFIG_RS_METADATA_FILE");
+    assert_eq!(traced, base);
+    env::set_var("TARGET_PKG_CONFIG_SYSROOT_DIR", "/sysroot");
+    let sysroot = pkg_config::Config::new().fingerprint();
+    env::remove_var("TARGET_PKG_CONFIG_SYSROOT_DIR");
+    assert!(sysroot != base);
+}
+
+#[test]
+fn probe_many() {
+    let _g = LOCK.lock();
+    reset();
+    let mut mismatch = pkg_config::Config::new();
+    mismatch.atleast_version("4");
+    let results = pkg_config::probe_many(vec![
+        ("foo".to_string(), pkg_config::Config::new()),
+        ("escape".to_string(), pkg_config::Config::new()),
+        ("foo".to_string(), mismatch),
+        ("nonexistent".to_string(), pkg_config::Config::new()),
+    ]);
+    let names = results.iter().map(|r| &r.0[..]).collect::<Vec<_>>();
+    assert_eq!(names, vec!["foo", "escape", "foo", "nonexistent"]);
+    assert_eq!(results[0].1.as_ref().unwrap().version, "3.10.0.SVN");
+    assert_eq!(results[1].1.as_ref().unwrap().version, "4.2.0");
+    match results[2].1 {
+        Err(Error::VersionMismatch { .. }) => {}
+        ref x => panic!("Error::VersionMismatch expected, found `{:?}`", x),
+    }
+    assert!(results[3].1.as_ref().unwrap_err().is_not_found());
+}
+
+#[test]
+fn version_mismatch_of_requires() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("PKG_CONFIG_PATH", env::current_dir().unwrap().join("tests/requires"));
+    // `newer-base` itself satisfies the constraint, its private requires
+    // don't.
+    let result = pkg_config::Config::new().cargo_metadata(false)
+                                          .statik(true)
+                                          .atleast_version("1")
+                                          .probe("newer-base");
+    match result {
+        Err(Error::VersionMismatch { .. }) | Ok(_) => {
+            panic!("a failure other than Error::VersionMismatch expected, found `{:?}`", result)
+        }
+        Err(_) => {}
+    }
+}
+
+#[test]
+fn probe_all() {
+    let _g = LOCK.lock();
+    reset();
+    let libs = pkg_config::probe_all(vec![
+        ("foo".to_string(), pkg_config::Config::new()),
+        ("escape".to_string(), pkg_config::Config::new()),
+    ]).unwrap();
+    assert_eq!(libs.len(), 2);
+
+    let errors = pkg_config::probe_all(vec![
+        ("nonexistent".to_string(), pkg_config::Config::new()),
+        ("foo".to_string(), pkg_config::Config::new()),
+        ("also-nonexistent".to_string(), pkg_config::Config::new()),
+    ]).unwrap_err();
+    let names = errors.iter().map(|e| &e.0[..]).collect::<Vec<_>>();
+    assert_eq!(names, vec!["nonexistent", "also-nonexistent"]);
+    assert!(errors.iter().all(|e| e.1.is_not_found()));
+    let message = errors.to_string();
+    assert!(message.starts_with("--- nonexistent\n"));
+    assert!(message.contains("\n--- also-nonexistent\n"));
+}
+
+#[test]
+fn compile_flags() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("compile-flags").unwrap();
+    assert_eq!(lib.compile_flags, vec!["-pthread", "-std=gnu11", "-mfpmath=sse",
+                                       "-arch", "x86_64", "-include", "cf.h"]);
+    assert!(lib.compile_args(pkg_config::Language::C).ends_with(&lib.compile_flags));
+}
+
+#[test]
+#[cfg(feature = "build-script-check")]
+fn build_script_check() {
+    let _g = LOCK.lock();
+    reset();
+    env::remove_var("OUT_DIR");
+    assert!(pkg_config::Config::new().standalone(true).probe("foo").is_ok());
+    let result = std::panic::catch_unwind(|| find("foo"));
+    env::set_var("OUT_DIR", env::temp_dir());
+    assert!(result.is_err());
+    assert!(find("foo").is_ok());
+}
+
+#[test]
+#[cfg(feature = "cc")]
+fn apply_to_cc() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("escape").unwrap();
+    let mut build = cc::Build::new();
+    build.target("x86_64-unknown-linux-gnu").host("x86_64-unknown-linux-gnu")
+         .opt_level(0).debug(false).cargo_metadata(false);
+    lib.apply_to(&mut build);
+    let args = build.get_compiler().args().iter()
+                    .map(|a| a.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>();
+    assert!(args.windows(2).any(|w| w == ["-I", "include path with spaces"]));
+    assert!(args.contains(&"-DA=\"escaped string' literal\"".to_string()));
+    assert!(args.contains(&"-DFOX=🦊".to_string()));
+}
+
+#[test]
+fn union_roots() {
+    let _g = LOCK.lock();
+    reset();
+    let base = TempDir::new("roots");
+    for &(root, dir, version) in &[("a", "lib/pkgconfig", "1.0"), ("c", "share/pkgconfig", "2.0")] {
+        let dir = base.join(root).join(dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("rooted.pc"),
+                       format!("Name: rooted\nDescription: d\nVersion: {}\nLibs: -lrooted\n",
+                               version)).unwrap();
+    }
+    let roots = vec![base.join("a"), base.join("b"), base.join("c")];
+    let found = pkg_config::Config::new().union_roots("rooted", &roots);
+
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].0, roots[0]);
+    assert_eq!(found[0].1.version, "1.0");
+    assert_eq!(found[1].0, roots[2]);
+    assert_eq!(found[1].1.version, "2.0");
+}
+
+#[test]
+#[cfg(feature = "bindgen")]
+fn clang_args() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("isystem").unwrap();
+    assert_eq!(lib.clang_args(), vec!["-iquote/quote", "-I/inc", "-isystem/sys1", "-isystem/sys2",
+                                      "-idirafter/after"]);
+    let lib = find("compile-flags").unwrap();
+    assert_eq!(lib.clang_args(), vec!["-I/opt/cf/include", "-DCF"]);
+
+    env::set_var("TARGET", "foo");
+    env::set_var("HOST", "bar");
+    env::set_var("PKG_CONFIG_SYSROOT_DIR", "/sysroot");
+    let lib = find("headers").unwrap();
+    assert_eq!(lib.clang_args(), vec!["--sysroot=/sysroot", "-I/sysroot/usr/include/headers",
+                                      "-DHEADERS_MAX=4", "-DHEADERS_NO_DEBUG"]);
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn negative_cache() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    let _g = LOCK.lock();
+    reset();
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = runs.clone();
+    let mut config = pkg_config::Config::new();
+    config.trace(move |event| if let pkg_config::Trace::Command(_) = *event {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+    assert!(config.probe("cached-missing").unwrap_err().is_not_found());
+    let first = runs.load(Ordering::SeqCst);
+    assert!(first > 0);
+    // Missing packages are remembered for the process without a file.
+    assert!(config.probe("cached-missing").unwrap_err().is_not_found());
+    assert_eq!(runs.load(Ordering::SeqCst), first);
+
+    let dir = TempDir::new("missing");
+    let path = dir.join("missing");
+    config.negative_cache(&path, Duration::from_secs(3600));
+    assert!(config.probe("cached-missing").unwrap_err().is_not_found());
+    assert!(config.probe("other-missing").unwrap_err().is_not_found());
+    assert!(config.probe("other-missing").unwrap_err().is_not_found());
+    assert_eq!(runs.load(Ordering::SeqCst), 2 * first);
+
+    // Entries of the cache file expire after the TTL.
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    std::fs::write(&path, format!("{0} fresh {1}\n{0} stale 0\n",
+                                  config.fingerprint(), now)).unwrap();
+    let fresh = config.probe("fresh");
+    let stale = config.probe("stale");
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    match fresh {
+        Err(Error::NotFoundCached { ref package, .. }) if package == "fresh" => {}
+        x => panic!("Error::NotFoundCached expected, found `{:?}`", x),
+    }
+    assert!(stale.unwrap_err().is_not_found());
+    assert_eq!(recorded.lines().filter(|l| l.contains(" stale ")).count(), 2);
+}
+
+#[test]
+#[cfg(feature = "vcpkg")]
+fn vcpkg() {
+    use std::fs;
+
+    let _g = LOCK.lock();
+    reset();
+    let root = TempDir::new("vcpkg");
+    let triplet = "x64-windows-static-md";
+    fs::create_dir_all(root.join("installed/vcpkg/info")).unwrap();
+    fs::create_dir_all(root.join("installed/vcpkg/updates")).unwrap();
+    fs::create_dir_all(root.join("installed").join(triplet).join("lib")).unwrap();
+    fs::write(root.join(".vcpkg-root"), "").unwrap();
+    fs::write(root.join("installed/vcpkg/status"),
+              format!("Package: vcfoo\nVersion: 1.0\nArchitecture: {}\n\
+                       Status: install ok installed\n", triplet)).unwrap();
+    fs::write(root.join(format!("installed/vcpkg/info/vcfoo_1.0_{}.list", triplet)),
+              format!("{}/lib/vcfoo.lib\n", triplet)).unwrap();
+    fs::write(root.join("installed").join(triplet).join("lib/vcfoo.lib"), "").unwrap();
+
+    env::set_var("VCPKG_ROOT", &*root);
+    let unix = find("vcfoo");
+    env::set_var("TARGET", "x86_64-pc-windows-msvc");
+    env::set_var("HOST", "x86_64-pc-windows-msvc");
+    let msvc = find("vcfoo");
+    // vcpkg doesn't tell the version, which thus meets no constraint.
+    let versioned = pkg_config::Config::new().atleast_version("1").probe("vcfoo");
+    env::remove_var("VCPKG_ROOT");
+
+    assert!(unix.unwrap_err().is_not_found());
+    match versioned {
+        Err(Error::VersionMismatch { ref found, .. }) => assert_eq!(found, "unknown"),
+        other => panic!("unexpected {:?}", other.map(|l| l.libs)),
+    }
+    let msvc = msvc.unwrap();
+    assert_eq!(msvc.libs, vec!["vcfoo"]);
+    assert_eq!(msvc.static_libs, vec!["vcfoo"]);
+    assert_eq!(msvc.link_paths, vec![root.join("installed").join(triplet).join("lib")]);
+}
+
+#[test]
+#[cfg(feature = "cmake-fallback")]
+fn cmake_fallback() {
+    let _g = LOCK.lock();
+    reset();
+    let prefix = env::current_dir().unwrap().join("tests/cmake");
+    env::set_var("CMAKE_PREFIX_PATH", &prefix);
+    let lib = find("widget");
+    let too_old = pkg_config::Config::new().atleast_version("3").probe("widget");
+    env::remove_var("CMAKE_PREFIX_PATH");
+    match too_old {
+        Err(Error::VersionMismatch { ref found, .. }) => assert_eq!(found, "2.4.1"),
+        other => panic!("unexpected {:?}", other.map(|l| l.version)),
+    }
+    let lib = lib.unwrap();
+    assert_eq!(lib.version, "2.4.1");
+    assert_eq!(lib.include_paths, vec![prefix.join("include"), prefix.join("include/widget")]);
+    assert_eq!(lib.link_paths, vec![prefix.join("lib")]);
+    assert_eq!(lib.libs, vec!["widget", "widget_extra", "m"]);
+    assert_eq!(lib.static_libs, vec!["widget_extra"]);
+    assert_eq!(lib.defines["WIDGET_LEVEL"], Some("2".to_string()));
+}
+
+#[test]
+fn distro_packages() {
+    let _g = LOCK.lock();
+    reset();
+    let gtk = pkg_config::distro_packages("gtk4");
+    assert_eq!(gtk.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+               vec!["apt install libgtk-4-dev", "dnf install gtk4-devel", "pacman -S gtk4",
+                    "apk add gtk4.0-dev", "brew install gtk4", "vcpkg install gtk"]);
+    assert!(pkg_config::distro_packages("distro-test").is_empty());
+
+    env::set_var("PKG_CONFIG_DISTRO_PACKAGES",
+                 env::current_dir().unwrap().join("tests/distro.toml"));
+    assert!(!pkg_config::distro_packages("gtk4").iter()
+                                                  .any(|s| s.distro == pkg_config::Distro::Vcpkg));
+    let suggestions = pkg_config::distro_packages("distro-test");
+    let err = find("distro-test").unwrap_err();
+    env::remove_var("PKG_CONFIG_DISTRO_PACKAGES");
+    // The suggestions are resolved when the error is, not when it's shown.
+    let err = err.to_string();
+    assert_eq!(suggestions.len(), 2);
+    assert_eq!(suggestions[1].distro, pkg_config::Distro::Alpine);
+    assert_eq!(suggestions[1].package, "distro-test-dev");
+    assert!(err.ends_with("--- hint\n`distro-test` may be provided by a system \
+                                       package:\n    apt install libdistro-test-dev\n    \
+                                       apk add distro-test-dev"), "{}", err);
+}
+
+#[test]
+fn probe_framework() {
+    let _g = LOCK.lock();
+    reset();
+    let sdk = env::current_dir().unwrap().join("tests/sdk");
+    let frameworks = sdk.join("System/Library/Frameworks");
+    env::set_var("SDKROOT", &sdk);
+    let lib = pkg_config::Config::new().atleast_version("2.3").probe_framework("Widget");
+    let mismatch = pkg_config::Config::new().atleast_version("2.10").probe_framework("Widget");
+    let missing = pkg_config::Config::new().probe_framework("NoSuchFramework");
+    env::remove_var("SDKROOT");
+
+    let lib = lib.unwrap();
+    assert_eq!(lib.version, "2.3.1");
+    assert_eq!(lib.frameworks, vec!["Widget"]);
+    assert_eq!(lib.framework_paths, vec![frameworks.clone()]);
+    assert_eq!(lib.include_paths, vec![frameworks.join("Widget.framework/Headers")]);
+    match mismatch {
+        Err(Error::VersionMismatch { ref found, ref required, .. }) => {
+            assert_eq!(found, "2.3.1");
+            assert_eq!(required, ">= 2.10");
+        }
+        x => panic!("Error::VersionMismatch expected, found `{:?}`", x),
+    }
+    match missing {
+        Err(ref err @ Error::FrameworkNotFound { ref searched, .. }) => {
+            assert!(err.is_not_found());
+            assert_eq!(searched[0], frameworks);
+        }
+        x => panic!("Error::FrameworkNotFound expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+fn search_mode() {
+    use pkg_config::SearchMode;
+
+    let _g = LOCK.lock();
+    reset();
+    let probe = |mode: SearchMode, name: &str| {
+        let mut config = pkg_config::Config::new();
+        let warnings = record_metadata(config.search_mode(mode), "warning=");
+        let result = config.probe(name);
+        let warnings = warnings.lock().unwrap().clone();
+        (result, warnings)
+    };
+
+    env::set_var("PKG_CONFIG_LIBDIR", env::current_dir().unwrap().join("tests/libdir"));
+    assert!(probe(SearchMode::Default, "foo").0.is_ok());
+    assert!(probe(SearchMode::Default, "libdir-only").0.is_ok());
+    let (result, warnings) = probe(SearchMode::LibdirOnly, "foo");
+    assert!(result.unwrap_err().is_not_found());
+    assert_eq!(warnings, vec!["pkg-config: PKG_CONFIG_PATH is set but ignored, as the search \
+                               mode is LibdirOnly"]);
+    assert!(probe(SearchMode::LibdirOnly, "libdir-only").0.is_ok());
+
+    env::remove_var("PKG_CONFIG_LIBDIR");
+    let (result, warnings) = probe(SearchMode::PathThenLibdir, "foo");
+    assert!(warnings.is_empty());
+    let _lib = result.unwrap();
+    #[cfg(not(feature = "libpkgconf"))]
+    assert!(_lib.probe_commands[0].contains("PKG_CONFIG_LIBDIR=''"));
+
+    let tests = env::current_dir().unwrap().join("tests");
+    env::set_var("PKG_CONFIG_LIBDIR", &tests);
+    let (_, warnings) = probe(SearchMode::Default, "foo");
+    env::remove_var("PKG_CONFIG_LIBDIR");
+    assert_eq!(warnings, vec![format!("pkg-config: {} is in both PKG_CONFIG_PATH and \
+                                       PKG_CONFIG_LIBDIR", tests.display())]);
+}
+
+#[test]
+fn system_paths() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("foo").unwrap();
+    assert_eq!(lib.link_paths, vec![PathBuf::from("/usr/lib/valgrind")]);
+    assert_eq!(lib.include_paths, vec![PathBuf::from("/usr/include/valgrind")]);
+
+    let lib = pkg_config::Config::new()
+        .system_library_path(vec!["/usr/lib/valgrind/"])
+        .probe("foo")
+        .unwrap();
+    assert!(lib.link_paths.is_empty());
+    assert_eq!(lib.include_paths, vec![PathBuf::from("/usr/include/valgrind")]);
+
+    env::set_var("PKG_CONFIG_SYSTEM_INCLUDE_PATH", "/usr/include/valgrind");
+    let lib = find("foo");
+    let headers = pkg_config::Config::new().probe_headers("foo");
+    let overridden = pkg_config::Config::new().system_include_path(Vec::<PathBuf>::new())
+                                              .probe("foo");
+    env::remove_var("PKG_CONFIG_SYSTEM_INCLUDE_PATH");
+    let lib = lib.unwrap();
+    assert_eq!(lib.link_paths, vec![PathBuf::from("/usr/lib/valgrind")]);
+    assert!(lib.include_paths.is_empty());
+    assert!(headers.unwrap().include_paths.is_empty());
+    assert_eq!(overridden.unwrap().libs, lib.libs);
+}
+
+#[test]
+fn drop_system_link_paths() {
+    let _g = LOCK.lock();
+    reset();
+    let probe = |drop: bool| {
+        let mut config = pkg_config::Config::new();
+        let metadata = record_metadata(config.drop_system_link_paths(drop), "");
+        let lib = config.probe("system-link-path").unwrap();
+        let metadata = metadata.lock().unwrap().clone();
+        (lib, metadata)
+    };
+
+    let (lib, metadata) = probe(true);
+    assert_eq!(lib.link_paths, vec![PathBuf::from("/usr/lib"),
+                                    PathBuf::from("/opt/system-link-path/lib")]);
+    assert!(!metadata.iter().any(|m| m == "rustc-link-search=native=/usr/lib"));
+    assert!(metadata.iter().any(|m| m == "rustc-link-search=native=/opt/system-link-path/lib"));
+
+    let (_, metadata) = probe(false);
+    assert!(metadata.iter().any(|m| m == "rustc-link-search=native=/usr/lib"));
+}
+
+#[test]
+fn system_libs() {
+    use pkg_config::SystemLibs;
+
+    let _g = LOCK.lock();
+    reset();
+    let link_paths = |system_libs: SystemLibs| {
+        pkg_config::Config::new().system_libs(system_libs)
+                                 .probe("system-link-path")
+                                 .unwrap()
+                                 .link_paths
+    };
+    let with_system = vec![PathBuf::from("/usr/lib"), PathBuf::from("/opt/system-link-path/lib")];
+    let without_system = vec![PathBuf::from("/opt/system-link-path/lib")];
+
+    env::remove_var("PKG_CONFIG_ALLOW_SYSTEM_LIBS");
+    assert_eq!(find("system-link-path").unwrap().link_paths, with_system);
+    assert_eq!(link_paths(SystemLibs::ForceOn), with_system);
+    assert_eq!(link_paths(SystemLibs::ForceOff), without_system);
+    assert_eq!(link_paths(SystemLibs::Inherit), without_system);
+    let lib = pkg_config::Config::new().print_system_libs(false).probe("system-link-path");
+    assert_eq!(lib.unwrap().link_paths, without_system);
+
+    env::set_var("PKG_CONFIG_ALLOW_SYSTEM_LIBS", "1");
+    let force_off = link_paths(SystemLibs::ForceOff);
+    let inherit = link_paths(SystemLibs::Inherit);
+    env::set_var("PKG_CONFIG_ALLOW_SYSTEM_LIBS", "0");
+    let force_on = link_paths(SystemLibs::ForceOn);
+    let default = find("system-link-path").unwrap().link_paths;
+    env::remove_var("PKG_CONFIG_ALLOW_SYSTEM_LIBS");
+    assert_eq!(force_off, without_system);
+    assert_eq!(inherit, with_system);
+    assert_eq!(force_on, with_system);
+    assert_eq!(default, with_system);
+
+    // Suppressed for the host, allowed for the target.
+    env::set_var("PKG_CONFIG_ALLOW_SYSTEM_LIBS", "0");
+    env::set_var("PKG_CONFIG_ALLOW_SYSTEM_LIBS_aarch64_unknown_linux_gnu", "yes");
+    env::set_var("PKG_CONFIG_ALLOW_CROSS", "1");
+    let mut config = pkg_config::Config::new();
+    config.system_libs(SystemLibs::Inherit);
+    let host = config.effective_system_libs();
+    let host_paths = config.probe("system-link-path").map(|lib| lib.link_paths);
+    config.target("aarch64-unknown-linux-gnu").host("x86_64-unknown-linux-gnu");
+    let target = config.effective_system_libs();
+    let target_paths = config.probe("system-link-path").map(|lib| lib.link_paths);
+    env::remove_var("PKG_CONFIG_ALLOW_SYSTEM_LIBS");
+    env::remove_var("PKG_CONFIG_ALLOW_SYSTEM_LIBS_aarch64_unknown_linux_gnu");
+    env::remove_var("PKG_CONFIG_ALLOW_CROSS");
+    assert_eq!(host, None);
+    assert_eq!(host_paths.unwrap(), without_system);
+    assert_eq!(target.as_deref(), Some("yes"));
+    assert_eq!(target_paths.unwrap(), with_system);
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn static_env_values() {
+    let _g = LOCK.lock();
+    reset();
+    let passes_static = || find("foo").unwrap().probe_commands[0].contains("--static");
+    env::set_var("PKG_CONFIG_ALL_STATIC", "1");
+    assert!(passes_static());
+    env::set_var("FOO_STATIC", "no");
+    assert!(!passes_static());
+    env::set_var("FOO_STATIC", "prefer");
+    assert!(passes_static());
+    env::set_var("FOO_STATIC", "force");
+    assert!(passes_static());
+    env::remove_var("FOO_STATIC");
+    env::set_var("PKG_CONFIG_ALL_STATIC", "no");
+    assert!(!passes_static());
+    env::remove_var("PKG_CONFIG_ALL_STATIC");
+}
+
+#[test]
+fn linkage() {
+    use pkg_config::{Linkage, Statik};
+
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("linkage").unwrap();
+    assert_eq!(lib.linkage["staticpart"], Linkage::Dynamic);
+    assert_eq!(lib.linkage["dynamicpart"], Linkage::Dynamic);
+
+    let lib = pkg_config::Config::new().statik(Statik::Probe).probe("linkage").unwrap();
+    assert_eq!(lib.linkage["staticpart"], Linkage::Static);
+    assert_eq!(lib.linkage["dynamicpart"], Linkage::Dynamic);
+    assert_eq!(lib.static_libs[0], "staticpart");
+    assert!(lib.libs.contains(&"dynamicpart".to_string()));
+
+    env::set_var("TARGET", "x86_64-pc-windows-msvc");
+    env::set_var("HOST", "x86_64-pc-windows-msvc");
+    let lib = find("linkage").unwrap();
+    assert_eq!(lib.linkage["m"], Linkage::Skipped);
+    assert!(!lib.libs.contains(&"m".to_string()));
+}
+
+#[test]
+fn probe_default_link_dirs() {
+    use pkg_config::{Linkage, Statik};
+
+    let _g = LOCK.lock();
+    reset();
+    let cc = env::current_dir().unwrap().join("tests/bin/cc-search-dirs");
+    env::set_var("CC", &cc);
+    let lib = pkg_config::Config::new().statik(Statik::Probe).probe("default-archive").unwrap();
+    env::remove_var("CC");
+    let dir = env::current_dir().unwrap().join("tests/default-archive");
+    assert_eq!(lib.linkage["defaultpart"], Linkage::Static);
+    assert_eq!(lib.link_paths, vec![dir.canonicalize().unwrap()]);
+}
+
+#[test]
+fn verbatim_libs() {
+    use pkg_config::Linkage;
+
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    let metadata = record_metadata(&mut config, "");
+    let lib = config.probe("verbatim").unwrap();
+    assert_eq!(lib.libs, vec!["m"]);
+    assert_eq!(lib.verbatim_libs, vec!["libverbatim.so.1", "libverbatim_static.a"]);
+    assert_eq!(lib.linkage["libverbatim.so.1"], Linkage::Dynamic);
+    assert_eq!(lib.linkage["libverbatim_static.a"], Linkage::Static);
+    let metadata = metadata.lock().unwrap();
+    assert!(metadata.contains(&"rustc-link-lib=dylib:+verbatim=libverbatim.so.1".to_string()));
+    assert!(metadata.contains(&"rustc-link-lib=static:+verbatim=libverbatim_static.a"
+                                  .to_string()));
+    assert!(!metadata.iter().any(|m| m.contains("=:")));
+}
+
+#[test]
+fn pthread() {
+    use pkg_config::Linkage;
+
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("TARGET", "x86_64-unknown-linux-musl");
+    env::set_var("HOST", "x86_64-unknown-linux-musl");
+    let lib = find("pthread").unwrap();
+    assert_eq!(lib.libs, vec!["threaded", "pthread"]);
+    assert_eq!(lib.pthread, Some(Linkage::Dynamic));
+    assert!(lib.compile_flags.contains(&"-pthread".to_string()));
+
+    let lib = pkg_config::Config::new().statik(pkg_config::Statik::Force)
+                                       .probe("pthread")
+                                       .unwrap();
+    assert_eq!(lib.pthread, Some(Linkage::Static));
+    assert!(lib.static_libs.contains(&"pthread".to_string()));
+
+    env::set_var("TARGET", "aarch64-apple-darwin");
+    env::set_var("HOST", "aarch64-apple-darwin");
+    let lib = find("pthread").unwrap();
+    assert_eq!(lib.libs, vec!["threaded"]);
+    assert_eq!(lib.pthread, Some(Linkage::Skipped));
+    assert_eq!(lib.linkage["pthread"], Linkage::Skipped);
+
+    assert_eq!(find("foo").unwrap().pthread, None);
+}
+
+#[test]
+fn statik_from_str() {
+    use pkg_config::Statik;
+
+    let _g = LOCK.lock();
+    reset();
+    for name in &["no", "yes", "force", "prefer", "probe"] {
+        assert_eq!(name.parse::<Statik>().unwrap().to_string(), *name);
+    }
+    assert_eq!("Force".parse::<Statik>().unwrap(), Statik::Force);
+    let err = "sometimes".parse::<Statik>().unwrap_err();
+    assert!(err.to_string().contains("`sometimes`"));
+
+    let mut config = pkg_config::Config::new();
+    assert_eq!(Statik::resolve(&config, "foo"), Statik::No);
+    env::set_var("FOO_STATIC", "prefer");
+    assert_eq!(Statik::resolve(&config, "foo"), Statik::Prefer);
+    env::remove_var("FOO_STATIC");
+    config.statik(Statik::Force).statik_blacklist(vec!["bar"]);
+    assert_eq!(Statik::resolve(&config, "foo"), Statik::Force);
+    assert_eq!(Statik::resolve(&config, "bar"), Statik::No);
+    let dirs = [env::current_dir().unwrap().join("tests/linkage")];
+    assert!(Statik::Yes.links_statically(&config, "staticpart", &dirs));
+    assert!(!Statik::Yes.links_statically(&config, "dynamicpart", &dirs));
+    assert!(Statik::Force.links_statically(&config, "dynamicpart", &dirs));
+    assert!(!Statik::Force.links_statically(&config, "bar", &dirs));
+}
+
+#[test]
+fn verify_archives() {
+    use pkg_config::{Linkage, Statik};
+
+    let _g = LOCK.lock();
+    reset();
+    // `libstaticpart.a` is empty, so it can't be linked.
+    let lib = pkg_config::Config::new().statik(Statik::Prefer)
+                                       .verify_archives(true)
+                                       .probe("linkage")
+                                       .unwrap();
+    assert_eq!(lib.linkage["staticpart"], Linkage::Dynamic);
+    assert!(lib.static_libs.is_empty());
+
+    let lib = pkg_config::Config::new().statik(Statik::Force)
+                                       .verify_archives(true)
+                                       .probe("linkage")
+                                       .unwrap();
+    assert_eq!(lib.linkage["staticpart"], Linkage::Static);
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn statik_env_override() {
+    use pkg_config::Statik;
+
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    config.statik_env_override(false);
+    assert_eq!(config.statik_from_env("foo"), None);
+
+    env::set_var("PKG_CONFIG_ALL_STATIC", "1");
+    let lib = config.probe("foo").unwrap();
+    assert!(!lib.probe_commands[0].contains("--static"));
+    assert_eq!(config.statik_from_env("foo"), Some(Statik::Yes));
+    env::set_var("FOO_DYNAMIC", "1");
+    assert_eq!(config.statik_from_env("foo"), Some(Statik::No));
+    env::remove_var("PKG_CONFIG_ALL_STATIC");
+    env::remove_var("FOO_DYNAMIC");
+
+    env::set_var("FOO_STATIC", "force");
+    let lib = config.statik_env_override(true).probe("foo");
+    env::remove_var("FOO_STATIC");
+    assert!(lib.unwrap().probe_commands[0].contains("--static"));
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn command_hooks() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new()
+        .on_command(|cmd| {
+            cmd.arg("--static");
+            Ok(())
+        })
+        .on_output(|output| {
+            let stdout = String::from_utf8_lossy(&output.stdout).replace("-lgcc", "");
+            output.stdout = stdout.into_bytes();
+        })
+        .probe("foo")
+        .unwrap();
+    assert!(lib.probe_commands[0].ends_with("--static"));
+    assert!(!lib.libs.contains(&"gcc".to_owned()));
+
+    let err = pkg_config::Config::new()
+        .on_command(|_| Err("not today".to_owned()))
+        .probe("foo")
+        .unwrap_err();
+    match err {
+        pkg_config::Error::Vetoed { ref reason, .. } => assert_eq!(reason, "not today"),
+        x => panic!("Error::Vetoed expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+fn warnings() {
+    let _g = LOCK.lock();
+    reset();
+    let dir = TempDir::new("warnings");
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("suspicious.pc"),
+                   "libdir=${pcfiledir}/lib\nName: s\nDescription: d\nVersion: 1\n\
+                    Libs: -L${libdir} -L/nonexistent/suspicious -lsuspicious\n").unwrap();
+    // The start of the ELF header of a 32-bit x86 shared library.
+    let mut header = b"\x7fELF\x01\x01\x01".to_vec();
+    header.resize(16, 0);
+    header.extend_from_slice(&[3, 0, 3, 0]);
+    std::fs::write(dir.join("lib/libsuspicious.so"), header).unwrap();
+    env::set_var("PKG_CONFIG_PATH", &*dir);
+
+    let mut config = pkg_config::Config::new();
+    config.target("x86_64-unknown-linux-gnu")
+          .host("x86_64-unknown-linux-gnu")
+          .warnings(true);
+    let warnings = record_metadata(&mut config, "warning=");
+    let result = config.probe("suspicious");
+    result.unwrap();
+
+    let warnings = warnings.lock().unwrap();
+    assert_eq!(warnings.len(), 2, "{:?}", warnings);
+    assert!(warnings[0].contains("/nonexistent/suspicious` of `suspicious` doesn't exist"));
+    assert!(warnings[1].ends_with("is built for x86, but the target is x86_64"));
+}
+
+#[test]
+fn cross_host_libraries() {
+    let _g = LOCK.lock();
+    reset();
+    let dir = TempDir::new("cross");
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("hostonly.pc"),
+                   "libdir=${pcfiledir}/lib\nName: h\nDescription: d\nVersion: 1\n\
+                    Libs: -L${libdir} -lhostonly\n").unwrap();
+    // The start of the ELF header of an x86_64 shared library.
+    let mut header = b"\x7fELF\x02\x01\x01".to_vec();
+    header.resize(16, 0);
+    header.extend_from_slice(&[3, 0, 62, 0]);
+    std::fs::write(dir.join("lib/libhostonly.so"), header).unwrap();
+    env::set_var("PKG_CONFIG_PATH", &*dir);
+    env::set_var("PKG_CONFIG_ALLOW_CROSS", "1");
+
+    let mut config = pkg_config::Config::new();
+    config.target("aarch64-unknown-linux-gnu").host("x86_64-unknown-linux-gnu");
+    let warnings = record_metadata(&mut config, "warning=");
+    let result = config.probe("hostonly");
+    env::remove_var("PKG_CONFIG_ALLOW_CROSS");
+    result.unwrap();
+
+    let warnings = warnings.lock().unwrap();
+    assert_eq!(warnings.len(), 3, "{:?}", warnings);
+    assert!(warnings[0].ends_with("is built for x86_64, but the target is aarch64"));
+    assert!(warnings[1].contains("while PKG_CONFIG_LIBDIR replaces it"));
+    assert!(warnings[2].contains("PKG_CONFIG_LIBDIR_aarch64_unknown_linux_gnu=\
+                                  /usr/lib/aarch64-linux-gnu/pkgconfig"));
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn standard_variables() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("foo").unwrap();
+    assert_eq!(lib.prefix, None);
+
+    let lib = pkg_config::Config::new().standard_variables(true).probe("foo").unwrap();
+    assert_eq!(lib.prefix, Some(PathBuf::from("/usr")));
+    assert_eq!(lib.exec_prefix, Some(PathBuf::from("/usr")));
+    assert_eq!(lib.libdir, Some(PathBuf::from("/usr/lib")));
+    assert_eq!(lib.includedir, Some(PathBuf::from("/usr/include/valgrind")));
+
+    let lib = pkg_config::Config::new().standard_variables(true).probe("headers").unwrap();
+    assert_eq!(lib.exec_prefix, None);
+    assert_eq!(lib.includedir, Some(PathBuf::from("/usr/include")));
+}
+
+#[test]
+fn package_config() {
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    config.atleast_version("1.0");
+    let lib = config.package("valgrind").rename("foo").atleast_version("3.10").probe().unwrap();
+    assert_eq!(lib.version, "3.10.0.SVN");
+    let err = config.package("foo").at_most_version("3.9").probe().unwrap_err();
+    assert!(matches!(err, Error::VersionMismatch { .. }), "{:?}", err);
+    // The tweaks don't leak into the shared settings.
+    let lib = config.probe("headers").unwrap();
+    assert_eq!(lib.version, "1.2.0");
+    assert!(find("valgrind").is_err());
+}
+
+#[test]
+fn validate_paths() {
+    use pkg_config::PathPolicy;
+
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    let warnings = record_metadata(config.validate_paths(PathPolicy::Warn), "warning=");
+    let lib = config.probe("missing-paths");
+    assert!(lib.is_ok());
+    assert_eq!(*warnings.lock().unwrap(),
+               vec!["search path `/nonexistent/missing-paths/lib` of `missing-paths` doesn't \
+                     exist",
+                    "search path `/nonexistent/missing-paths/Frameworks` of `missing-paths` \
+                     doesn't exist"]);
+
+    let mut config = pkg_config::Config::new();
+    config.validate_paths(PathPolicy::Error);
+    let err = config.probe("missing-paths").unwrap_err();
+    match err {
+        Error::InvalidPaths { ref package, ref missing } => {
+            assert_eq!(package, "missing-paths");
+            assert_eq!(*missing, vec![PathBuf::from("/nonexistent/missing-paths/lib"),
+                                      PathBuf::from("/nonexistent/missing-paths/Frameworks")]);
+        }
+        x => panic!("Error::InvalidPaths expected, found `{:?}`", x),
+    }
+    assert!(config.probe("headers").is_ok());
+}
+
+#[test]
+fn macos_package_managers() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("HOMEBREW_PREFIX", env::current_dir().unwrap().join("tests/homebrew"));
+    let mut config = pkg_config::Config::new();
+    config.target("aarch64-apple-darwin").host("aarch64-apple-darwin");
+    let missing = config.probe("kegonly");
+    let found = config.macos_package_managers(true).probe("kegonly");
+    let linux = config.target("x86_64-unknown-linux-gnu")
+                      .host("x86_64-unknown-linux-gnu")
+                      .probe("kegonly");
+    env::remove_var("HOMEBREW_PREFIX");
+
+    assert!(missing.unwrap_err().is_not_found());
+    assert_eq!(found.unwrap().version, "3.1.0");
+    assert!(linux.is_err());
+}
+
+#[test]
+fn to_cmake_args() {
+    let _g = LOCK.lock();
+    reset();
+    let prefix = env::current_dir().unwrap().join("tests/prefixed");
+    let lib = find("prefixed").unwrap();
+    assert_eq!(lib.to_cmake_args("PREFIXED"),
+               vec![format!("-DCMAKE_PREFIX_PATH={}", prefix.display()),
+                    format!("-DPREFIXED_INCLUDE_DIR={}", prefix.join("include").display()),
+                    format!("-DPREFIXED_LIBRARY={}", prefix.join("lib/libprefixed.a").display())]);
+    assert!(find("headers").unwrap().to_cmake_args("HEADERS")
+                           .contains(&"-DCMAKE_PREFIX_PATH=/usr".to_string()));
+}
+
+#[test]
+fn msys_paths() {
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    config.target("x86_64-pc-windows-gnu").host("x86_64-pc-windows-gnu");
+    let posix = config.probe("msys").unwrap();
+    env::set_var("MSYSTEM", "MINGW64");
+    env::set_var("PKG_CONFIG_MSYS_ROOT", "C:\\msys64");
+    let windows = config.probe("msys");
+    env::remove_var("MSYSTEM");
+    env::remove_var("PKG_CONFIG_MSYS_ROOT");
+
+    assert_eq!(posix.link_paths, vec![PathBuf::from("/mingw64/lib")]);
+    let windows = windows.unwrap();
+    assert_eq!(windows.link_paths, vec![PathBuf::from("C:\\msys64\\mingw64\\lib")]);
+    assert_eq!(windows.include_paths, vec![PathBuf::from("C:\\msys64\\mingw64\\include")]);
+    assert_eq!(windows.system_include_paths, vec![PathBuf::from("C:\\deps\\include")]);
+}
+
+#[test]
+fn normalize_windows_paths() {
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    config.target("x86_64-pc-windows-gnu").host("x86_64-pc-windows-gnu");
+    let metadata = record_metadata(&mut config, "");
+    let lib = config.probe("mixed-separators").unwrap();
+    assert_eq!(lib.link_paths, vec![PathBuf::from("c:/deps/mixed/lib")]);
+
+    let lib = config.normalize_windows_paths(true).probe("mixed-separators").unwrap();
+    assert_eq!(lib.link_paths, vec![PathBuf::from("C:\\deps\\mixed\\lib")]);
+    assert_eq!(lib.include_paths, vec![PathBuf::from("C:\\deps\\mixed\\include")]);
+    assert!(metadata.lock().unwrap().contains(&"rustc-link-search=native=C:\\deps\\mixed\\lib"
+                                                  .to_string()));
+
+    let lib = pkg_config::library::Builder::new().link_path("\\\\?\\d:\\deps\\lib").build();
+    metadata.lock().unwrap().clear();
+    lib.emit_cargo_metadata(&config);
+    assert_eq!(*metadata.lock().unwrap(), vec!["rustc-link-search=native=D:\\deps\\lib"]);
+}
+
+#[test]
+fn metadata_file() {
+    let _g = LOCK.lock();
+    reset();
+    let tmp = TempDir::new("metadata");
+    let path = tmp.join("metadata");
+    env::set_var("PKG_CONFIG_RS_METADATA_FILE", &path);
+    pkg_config::Config::new().env_metadata(false).probe("foo").unwrap();
+    #[cfg(feature = "vendored-resolver")]
+    {
+        let dir = env::current_dir().unwrap().join("tests");
+        pkg_config::Config::new().hermetic().fallback_paths([&dir]).probe("foo").unwrap();
+    }
+    env::remove_var("PKG_CONFIG_RS_METADATA_FILE");
+    let contents = std::fs::read_to_string(&path).unwrap();
+
+    let package = env::var("CARGO_PKG_NAME").unwrap();
+    assert!(contents.lines().all(|l| l.starts_with(&format!("{}: cargo:", package))),
+            "{}", contents);
+    assert!(contents.contains(": cargo:rustc-link-lib=gcc\n"));
+    assert_eq!(contents.matches(": cargo:rustc-link-lib=gcc\n").count(), 1);
+}
+
+#[test]
+fn spaces_in_paths() {
+    let _g = LOCK.lock();
+    reset();
+    let prefix = env::current_dir().unwrap().join("tests/path with spaces");
+    env::set_var("PKG_CONFIG_PATH", env::join_paths(&[prefix.join("lib/pkgconfig"),
+                                                      env::current_dir().unwrap().join("tests")])
+                                       .unwrap());
+    let mut config = pkg_config::Config::new();
+    let metadata = record_metadata(&mut config, "");
+
+    // The static archive is found in the directory with spaces.
+    let lib = config.statik(true).probe("spaced").unwrap();
+    let libdir = prefix.join("lib/pkgconfig/../../lib");
+    assert_eq!(lib.link_paths, vec![libdir.clone()]);
+    assert_eq!(lib.include_paths, vec![prefix.join("lib/pkgconfig/../../include")]);
+    assert_eq!(lib.static_libs, vec!["spaced"]);
+    assert_eq!(*metadata.lock().unwrap(),
+               vec![format!("rustc-link-search=native={}", libdir.display()),
+                    "rustc-link-lib=static=spaced".to_string()]);
+
+    metadata.lock().unwrap().clear();
+    let lib = config.statik(false)
+                    .target("x86_64-pc-windows-gnu")
+                    .host("x86_64-pc-windows-gnu")
+                    .normalize_windows_paths(true)
+                    .probe("program-files")
+                    .unwrap();
+    assert_eq!(lib.link_paths, vec![PathBuf::from("C:\\Program Files\\spaced\\lib")]);
+    assert_eq!(lib.include_paths, vec![PathBuf::from("C:\\Program Files\\spaced\\include")]);
+    assert_eq!(*metadata.lock().unwrap(),
+               vec!["rustc-link-search=native=C:\\Program Files\\spaced\\lib",
+                    "rustc-link-lib=spaced"]);
+}
+
+#[test]
+fn fallback_paths() {
+    let _g = LOCK.lock();
+    reset();
+    let dir = env::current_dir().unwrap().join("tests/fallback");
+    assert!(find("fallback").is_err());
+
+    let mut config = pkg_config::Config::new();
+    let metadata = record_metadata(config.fallback_paths([&dir]), "");
+    assert_eq!(config.probe("fallback").unwrap().version, "0.2.0");
+    assert!(metadata.lock().unwrap().contains(&format!("rerun-if-changed={}", dir.display())));
+    // `PKG_CONFIG_PATH` comes first.
+    assert_eq!(config.probe("foo").unwrap().version, "3.10.0.SVN");
+
+    env::remove_var("PKG_CONFIG_PATH");
+    assert_eq!(config.probe("foo").unwrap().version, "0.1.0");
+}
+
+#[test]
+fn for_each_target() {
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("TARGET", "x86_64-unknown-linux-gnu");
+    env::set_var("HOST", "x86_64-unknown-linux-gnu");
+    let dir = env::current_dir().unwrap().join("tests/fallback");
+    env::set_var("PKG_CONFIG_PATH_aarch64-unknown-linux-gnu", &dir);
+
+    let mut config = pkg_config::Config::new();
+    let metadata = record_metadata(&mut config, "");
+    let targets = ["x86_64-unknown-linux-gnu", "aarch64-unknown-linux-gnu"];
+    let versions = config.for_each_target(&targets, |config| {
+        config.probe("foo").map(|lib| lib.version)
+    });
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].0, "x86_64-unknown-linux-gnu");
+    assert_eq!(versions[0].1.as_ref().unwrap(), "3.10.0.SVN");
+    assert_eq!(versions[1].0, "aarch64-unknown-linux-gnu");
+    assert_eq!(versions[1].1.as_ref().unwrap(), "0.1.0");
+    // Only the target being built prints metadata.
+    let metadata = metadata.lock().unwrap();
+    assert!(metadata.iter().any(|m| m.ends_with("=coregrind-amd64-linux")));
+    assert!(!metadata.iter().any(|m| m.ends_with("=foo")));
+    env::remove_var("PKG_CONFIG_PATH_aarch64-unknown-linux-gnu");
+}
+
+#[test]
+#[cfg(feature = "vendored-resolver")]
+fn hermetic() {
+    let _g = LOCK.lock();
+    reset();
+    let dir = env::current_dir().unwrap().join("tests/fallback");
+    let mut config = pkg_config::Config::new();
+    config.cargo_metadata(false).hermetic();
+    assert!(config.probe("foo").is_err());
+
+    config.fallback_paths([&dir]);
+    let fingerprint = config.fingerprint();
+    // `PKG_CONFIG_PATH` and the like are ignored, unlike without `hermetic`.
+    env::set_var("FOO_STATIC", "1");
+    assert_eq!(config.probe("foo").unwrap().version, "0.1.0");
+    assert_eq!(config.fingerprint(), fingerprint);
+    env::set_var("PKG_CONFIG_LIBDIR", "/usr/lib/pkgconfig");
+    assert!(config.probe("fallback").is_ok());
+    assert_eq!(config.fingerprint(), fingerprint);
+    env::remove_var("FOO_STATIC");
+    env::remove_var("PKG_CONFIG_LIBDIR");
+
+    // Only the vendored resolver is hermetic.
+    assert!(matches!(config.probe_headers("foo"), Err(pkg_config::Error::NotHermetic { .. })));
+    config.vendored_resolver(false);
+    assert!(matches!(config.probe("foo"), Err(pkg_config::Error::NotHermetic { .. })));
+}
+
+#[test]
+#[cfg(not(feature = "vendored-resolver"))]
+fn hermetic_without_resolver() {
+    let _g = LOCK.lock();
+    reset();
+    let dir = env::current_dir().unwrap().join("tests/fallback");
+    let err = pkg_config::Config::new().hermetic().fallback_paths([&dir]).probe("foo").unwrap_err();
+    assert!(matches!(err, pkg_config::Error::NotHermetic { ref package } if package == "foo"),
+            "{:?}", err);
+}
+
+#[test]
+#[cfg(all(feature = "probe-server", any(target_os = "linux", target_os = "macos")))]
+fn probe_server() {
+    let _g = LOCK.lock();
+    reset();
+    let target_dir = TempDir::new("server");
+    let out_dir = target_dir.join("debug/build/foo-sys-0123/out");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    env::set_var("OUT_DIR", &out_dir);
+    env::set_var("PKG_CONFIG_RS_SERVER", "1");
+
+    // A build script would spawn itself as the server; this test binary
+    // runs `serve_probe_cache` instead.
+    let socket = target_dir.join("debug/pkg-config-rs.sock");
+    let mut server = std::process::Command::new(env::current_exe().unwrap())
+        .args(["serve_probe_cache", "--exact"])
+        .env("PKG_CONFIG_RS_SERVE", &socket)
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    for _ in 0..500 {
+        if socket.exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    pkg_config::probe_server();
+    let runs = Arc::new(Mutex::new(0));
+    let counter = runs.clone();
+    let mut config = pkg_config::Config::new();
+    config.trace(move |event| {
+        if let pkg_config::Trace::Command(_) = *event {
+            *counter.lock().unwrap() += 1;
+        }
+    });
+
+    // The first probe starts the server and fills it, the second is
+    // answered by it.
+    let first = config.probe("foo").unwrap();
+    assert!(*runs.lock().unwrap() > 0);
+    *runs.lock().unwrap() = 0;
+    // A client that connects and stalls doesn't keep the others waiting.
+    let stalled = std::os::unix::net::UnixStream::connect(&socket).unwrap();
+    let second = config.probe("foo").unwrap();
+    drop(stalled);
+    assert_eq!(*runs.lock().unwrap(), 0);
+    assert_eq!(second.version, first.version);
+    assert_eq!(second.libs, first.libs);
+    assert_eq!(second.probe_commands, first.probe_commands);
+
+    // Warnings are cached along with the flags.
+    env::set_var("PKG_CONFIG", env::current_dir().unwrap().join("tests/bin/pkg-config-warnings"));
+    let first = config.probe("foo").unwrap();
+    *runs.lock().unwrap() = 0;
+    let second = config.probe("foo").unwrap();
+    env::remove_var("PKG_CONFIG");
+    env::remove_var("PKG_CONFIG_RS_SERVER");
+    assert_eq!(*runs.lock().unwrap(), 0);
+    assert!(!first.warnings.is_empty());
+    assert_eq!(second.warnings, first.warnings);
+
+    // Changing a `.pc` file misses the cache.
+    let pc_file = env::current_dir().unwrap().join("tests/foo.pc");
+    let contents = std::fs::read(&pc_file).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    std::fs::write(&pc_file, &contents).unwrap();
+    env::set_var("PKG_CONFIG_RS_SERVER", "1");
+    config.probe("foo").unwrap();
+    env::remove_var("PKG_CONFIG_RS_SERVER");
+    assert!(*runs.lock().unwrap() > 0);
+
+    server.kill().unwrap();
+    server.wait().unwrap();
+}
+
+/// The server of `probe_server`, when run by it.
+#[test]
+#[cfg(all(feature = "probe-server", any(target_os = "linux", target_os = "macos")))]
+fn serve_probe_cache() {
+    if env::var_os("PKG_CONFIG_RS_SERVE").is_some() {
+        pkg_config::probe_server();
+    }
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn report() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("foo").unwrap();
+    let report = lib.report();
+    assert_eq!(report.package.as_deref(), Some("foo"));
+    assert_eq!(report.version, "3.10.0.SVN");
+    assert_eq!(report.libs, lib.libs);
+    assert_eq!(report.linkage.get("gcc"), Some(&pkg_config::Linkage::Dynamic));
+
+    let json = lib.to_json();
+    assert!(json.contains("\"package\": \"foo\""), "{}", json);
+    assert!(json.contains("\"gcc\": \"dynamic\""), "{}", json);
+    #[cfg(not(feature = "libpkgconf"))]
+    assert!(json.contains("\"pkg_config\": \"pkg-config\""), "{}", json);
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn rerun_directives_first() {
+    use pkg_config::testing::capture_metadata;
+
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    config.env_metadata(true);
+    let (lib, metadata) = capture_metadata(|| config.probe("foo"));
+    lib.unwrap();
+    let rerun = metadata.iter().take_while(|l| l.starts_with("cargo:rerun-if-")).collect::<Vec<_>>();
+    assert!(rerun.contains(&&"cargo:rerun-if-env-changed=PKG_CONFIG_PATH".to_string()));
+    assert!(metadata[rerun.len()..].iter().all(|l| !l.starts_with("cargo:rerun-if-")),
+            "{:?}", metadata);
+    let mut sorted = rerun.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(rerun, sorted);
+
+    // A cached failure returns before running `pkg-config`, but still tracks
+    // the variables it would have been run with.
+    assert!(config.probe("rerun-missing").is_err());
+    let (lib, metadata) = capture_metadata(|| config.probe("rerun-missing"));
+    assert!(lib.is_err());
+    assert!(metadata.contains(&"cargo:rerun-if-env-changed=PKG_CONFIG_PATH".to_string()),
+            "{:?}", metadata);
+    assert!(metadata.contains(&"cargo:rerun-if-env-changed=RERUN_MISSING_STATIC".to_string()));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serde_round_trip() {
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    config.atleast_version("3").statik(true).arg("--define-variable=prefix=/usr")
+          .trace(|_| {});
+    let json = serde_json::to_string(&config).unwrap();
+    let copy: pkg_config::Config = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+    assert!(json.contains("\"extra_args\":[\"--define-variable=prefix=/usr\"]"), "{}", json);
+
+    let lib = copy.probe("foo").unwrap();
+    let json = serde_json::to_string(&lib).unwrap();
+    let copy: pkg_config::Library = serde_json::from_str(&json).unwrap();
+    assert_eq!(copy.libs, lib.libs);
+    assert_eq!(copy.link_paths, lib.link_paths);
+    assert_eq!(copy.linkage, lib.linkage);
+    assert_eq!(copy.report(), lib.report());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn json_file() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("foo").unwrap();
+    let dir = TempDir::new("json");
+    let path = dir.join("foo-pkgconfig.json");
+    lib.write_json(&path).unwrap();
+    let json = std::fs::read_to_string(&path).unwrap();
+    let linkage = &json[json.find("\"linkage\"").unwrap()..];
+    let linkage = &linkage[..linkage.find('}').unwrap()];
+    let position = |lib: &str| linkage.find(lib).unwrap();
+    assert!(position("coregrind") < position("gcc") && position("gcc") < position("vex"));
+    let copy = pkg_config::Library::read_json(&path).unwrap();
+    assert_eq!(copy.version, lib.version);
+    assert_eq!(copy.include_paths, lib.include_paths);
+    assert_eq!(copy.defines, lib.defines);
+    assert_eq!(copy.pc_path, lib.pc_path);
+    assert_eq!(copy.report(), lib.report());
+
+    std::fs::write(&path, "{}").unwrap();
+    let err = pkg_config::Library::read_json(&path).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn sysroot() {
+    let _g = LOCK.lock();
+    reset();
+    let sysroot = env::current_dir().unwrap().join("tests/sysroot");
+    let lib = pkg_config::Config::new().target("aarch64-unknown-linux-gnu")
+                                       .host("x86_64-unknown-linux-gnu")
+                                       .sysroot(&sysroot)
+                                       .cargo_metadata(false)
+                                       .probe("sysrooted")
+                                       .unwrap();
+    assert_eq!(lib.version, "2.0.0");
+    assert_eq!(lib.libs, vec!["sysrooted"]);
+    assert_eq!(lib.link_paths, vec![sysroot.join("usr/lib/aarch64-linux-gnu")]);
+    assert_eq!(lib.include_paths, vec![sysroot.join("usr/include/sysrooted")]);
+    #[cfg(all(feature = "vendored-resolver", not(feature = "libpkgconf")))]
+    assert!(lib.probe_commands.is_empty());
+
+    // Only the multiarch directory of the target is searched.
+    let result = pkg_config::Config::new().target("x86_64-unknown-linux-gnu")
+                                          .host("aarch64-unknown-linux-gnu")
+                                          .sysroot(&sysroot)
+                                          .cargo_metadata(false)
+                                          .probe("sysrooted");
+    assert!(result.unwrap_err().is_not_found());
+}
+
+#[test]
+fn virtual_package() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new().standard_variables(true)
+                                       .warnings(true)
+                                       .statik(true)
+                                       .probe("pkg-config")
+                                       .unwrap();
+    assert!(lib.is_virtual());
+    assert!(!lib.version.is_empty());
+    assert!(lib.libs.is_empty());
+    assert!(lib.artifacts().is_empty());
+    assert_eq!(lib.prefix, None);
+
+    assert!(!find("foo").unwrap().is_virtual());
+}
+
+#[test]
+fn name_and_pc_path() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new().probe_any(&["nonexistent", "foo"]).unwrap();
+    assert_eq!(lib.name(), Some("foo"));
+    assert_eq!(lib.pc_path, Some(env::current_dir().unwrap().join("tests/foo.pc")));
+    assert!(!lib.probe_commands.iter().any(|c| c.contains("pcfiledir")));
+
+    let lib = pkg_config::Config::new().probe("pkg-config").unwrap();
+    assert_eq!(lib.name(), Some("pkg-config"));
+    assert_eq!(lib.pc_path, None);
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn min_pkg_config_version() {
+    let _g = LOCK.lock();
+    reset();
+    let err = pkg_config::Config::new().min_pkg_config_version("999").probe("foo").unwrap_err();
+    match err {
+        pkg_config::Error::ToolTooOld { ref required, .. } => assert_eq!(required, "999"),
+        _ => panic!("expected ToolTooOld, got {:?}", err),
+    }
+    assert!(err.to_string().contains("or newer is required"), "{}", err);
+
+    pkg_config::Config::new().min_pkg_config_version("0.9").probe("foo").unwrap();
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn requires_policy() {
+    use pkg_config::RequiresPolicy;
+    use std::collections::BTreeMap;
+
+    let _g = LOCK.lock();
+    reset();
+    env::set_var("PKG_CONFIG_PATH", env::current_dir().unwrap().join("tests/requires"));
+    let probe = |policy: RequiresPolicy| {
+        pkg_config::Config::new().cargo_metadata(false)
+                                 .requires_policy(policy)
+                                 .probe("app")
+                                 .unwrap()
+    };
+    assert_eq!(probe(RequiresPolicy::EmitAll).libs, ["app", "middle", "base"]);
+    let lib = probe(RequiresPolicy::DirectOnly);
+    assert_eq!(lib.libs, ["app"]);
+    assert_eq!(lib.linkage.get("base"), Some(&pkg_config::Linkage::Skipped));
+
+    let links = vec![("base".to_string(), "base-sys".to_string())].into_iter()
+                                                                  .collect::<BTreeMap<_, _>>();
+    // Not a dependency, as no `DEP_BASE_SYS_*` variable is set.
+    assert_eq!(probe(RequiresPolicy::DeferToDeps(links.clone())).libs,
+               ["app", "middle", "base"]);
+    env::set_var("DEP_BASE_SYS_INCLUDE", "/usr/include/base");
+    let lib = probe(RequiresPolicy::DeferToDeps(links));
+    env::remove_var("DEP_BASE_SYS_INCLUDE");
+    assert_eq!(lib.libs, ["app", "middle"]);
+
+    // Libraries of packages still linked stay, even if a deferred package
+    // requires them too.
+    let links = vec![("middle".to_string(), "middle-sys".to_string())].into_iter()
+                                                                      .collect();
+    env::set_var("DEP_MIDDLE_SYS_INCLUDE", "/usr/include/middle");
+    let lib = pkg_config::Config::new().cargo_metadata(false)
+                                       .requires_policy(RequiresPolicy::DeferToDeps(links))
+                                       .probe("plugin")
+                                       .unwrap();
+    env::remove_var("DEP_MIDDLE_SYS_INCLUDE");
+    assert_eq!(lib.libs, ["plugin", "base"]);
+}
+
+#[test]
+fn uninstalled() {
+    let _g = LOCK.lock();
+    reset();
+    let dir = env::current_dir().unwrap().join("tests/uninstalled");
+    env::set_var("PKG_CONFIG_PATH", &dir);
+    let lib = pkg_config::Config::new().top_build_dir("/build").probe("inplace").unwrap();
+    assert_eq!(lib.version, "1.1");
+    assert_eq!(lib.link_paths, vec![PathBuf::from("/build/inplace/.libs")]);
+    assert_eq!(lib.pc_path, Some(dir.join("inplace-uninstalled.pc")));
+
+    env::set_var("PKG_CONFIG_DISABLE_UNINSTALLED", "1");
+    let disabled = pkg_config::Config::new().probe("inplace");
+    let enabled = pkg_config::Config::new().disable_uninstalled(false).probe("inplace");
+    env::remove_var("PKG_CONFIG_DISABLE_UNINSTALLED");
+    assert_eq!(disabled.unwrap().version, "1.0");
+    assert_eq!(enabled.unwrap().version, "1.1");
+    let lib = pkg_config::Config::new().disable_uninstalled(true).probe("inplace").unwrap();
+    assert_eq!(lib.link_paths, vec![PathBuf::from("/opt/inplace/lib")]);
+}
+
+#[test]
+fn define_prefix() {
+    let _g = LOCK.lock();
+    reset();
+    let dir = env::current_dir().unwrap().join("tests/relocatable");
+    env::set_var("PKG_CONFIG_PATH", dir.join("lib/pkgconfig"));
+    let lib = pkg_config::Config::new().define_prefix(true).probe("relocatable").unwrap();
+    assert_eq!(lib.include_paths, vec![dir.join("include")]);
+    assert_eq!(lib.link_paths, vec![dir.join("lib")]);
+
+    let lib = pkg_config::Config::new().define_prefix(false).probe("relocatable").unwrap();
+    assert_eq!(lib.include_paths, vec![PathBuf::from("/original/include")]);
+    assert_eq!(lib.link_paths, vec![PathBuf::from("/original/lib")]);
+}
+
+#[test]
+#[cfg(unix)]
+fn try_link() {
+    let _g = LOCK.lock();
+    reset();
+    let probe = |name: &str| {
+        pkg_config::Config::new().cargo_metadata(false).try_link(true).probe(name)
+    };
+    probe("headers").unwrap();
+    match probe("foo") {
+        Err(pkg_config::Error::LinkFailed { ref package, ref command, ref stderr }) => {
+            assert_eq!(package, "foo");
+            assert!(command.ends_with("-L/usr/lib/valgrind -lcoregrind-amd64-linux \
+                                       -lvex-amd64-linux -lgcc"), "{}", command);
+            assert!(stderr.contains("coregrind"), "{}", stderr);
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+}
+
+#[test]
+#[cfg(all(target_os = "linux", not(feature = "libpkgconf")))]
+fn retries() {
+    use std::fs::{self, File};
+    use std::os::unix::fs::PermissionsExt;
+
+    let _g = LOCK.lock();
+    reset();
+    // Executing a file that's open for writing fails with `ETXTBSY`.
+    let dir = TempDir::new("busy");
+    let exe = dir.join("pkg-config");
+    let writer = File::create(&exe).unwrap();
+    fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+    env::set_var("PKG_CONFIG", &exe);
+    let result = pkg_config::Config::new().retries(2).probe("foo");
+    env::remove_var("PKG_CONFIG");
+    drop(writer);
+    match result {
+        Err(Error::SpawnFailed { attempts, .. }) => assert_eq!(attempts, 3),
+        result => panic!("unexpected {:?}", result),
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn has_symbol() {
+    use std::fs;
+    use std::process::Command;
+
+    let dir = TempDir::new("symbols");
+    fs::write(dir.join("sym.c"), "int sym_tls13(void) { return 13; }\n").unwrap();
+    let run = |args: &[&str]| {
+        assert!(Command::new(args[0]).args(&args[1..]).current_dir(&*dir).status().unwrap()
+                                     .success());
+    };
+    run(&["cc", "-c", "-fPIC", "sym.c", "-o", "sym.o"]);
+    run(&["cc", "-shared", "sym.o", "-o", "libshared.so"]);
+    run(&["ar", "rcs", "libstatic.a", "sym.o"]);
+
+    let names: &[&str] = if cfg!(feature = "elf") { &["shared", "static"] } else { &["static"] };
+    for name in names {
+        let lib = pkg_config::Library::from_parts(vec![name.to_string()], vec![dir.to_path_buf()],
+                                                  vec![], "1.0");
+        assert_eq!(lib.has_symbol("sym_tls13"), Some(true), "{}", name);
+        assert_eq!(lib.has_symbol("sym_tls12"), Some(false), "{}", name);
+    }
+    let missing = pkg_config::Library::from_parts(vec!["missing".into()], vec![dir.to_path_buf()],
+                                                  vec![], "1.0");
+    assert_eq!(missing.has_symbol("sym_tls13"), None);
+}
+
+#[test]
+#[cfg(unix)]
+fn non_utf8_paths() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    let _g = LOCK.lock();
+    reset();
+    let lib = find("latin1").unwrap();
+    let prefix = Path::new(OsStr::from_bytes(b"/opt/caf\xe9"));
+    assert_eq!(lib.link_paths, vec![prefix.join("lib")]);
+    assert_eq!(lib.include_paths, vec![prefix.join("include")]);
+    assert_eq!(lib.libs, vec!["latin1"]);
+    assert_eq!(lib.version, "1.0");
+
+    // Defines are text, and aren't mangled.
+    match find("latin1-define") {
+        Err(Error::InvalidOutput { ref output, .. }) if output == "-DCAFE=caf\u{fffd}" => {}
+        x => panic!("Error::InvalidOutput expected, found `{:?}`", x),
+    }
+}
+
+#[test]
+#[cfg(not(feature = "libpkgconf"))]
+fn assert_variable() {
+    let _g = LOCK.lock();
+    reset();
+    let lib = pkg_config::Config::new().cargo_metadata(false)
+                                       .assert_variable("arch", |arch| arch == "amd64")
+                                       .assert_variable("os", |os| !os.is_empty())
+                                       .probe("foo")
+                                       .unwrap();
+    assert_eq!(lib.variables["arch"], "amd64");
+    assert_eq!(lib.variables["os"], "linux");
+
+    let err = pkg_config::Config::new().cargo_metadata(false)
+                                       .assert_variable("arch", |arch| arch == "arm64")
+                                       .probe("foo")
+                                       .unwrap_err();
+    assert_eq!(err.to_string(), "Variable `arch` of package `foo` has the unexpected value \
+                                 `amd64`");
+    let err = pkg_config::Config::new().cargo_metadata(false)
+                                       .assert_variable("plugindir", |_| true)
+                                       .probe("foo")
+                                       .unwrap_err();
+    assert_eq!(err.to_string(), "Package `foo` doesn't define the variable `plugindir`");
+}
+
+#[test]
+fn library_kind() {
+    use pkg_config::LibraryKind;
+
+    let _g = LOCK.lock();
+    reset();
+    assert_eq!(find("foo").unwrap().kind(), LibraryKind::Libs);
+    assert_eq!(find("framework").unwrap().kind(), LibraryKind::FrameworksOnly);
+    assert_eq!(find("headers").unwrap().kind(), LibraryKind::HeaderOnly);
+
+    let probe = |kind: LibraryKind, name: &str| {
+        pkg_config::Config::new().cargo_metadata(false).expect_kind(kind).probe(name)
+    };
+    let err = probe(LibraryKind::Libs, "framework").unwrap_err();
+    assert!(err.to_string().ends_with("expected to have libraries to link, but it has only \
+                                       frameworks"), "{}", err);
+    let err = probe(LibraryKind::FrameworksOnly, "framework").unwrap_err();
+    assert!(err.to_string().ends_with("framework `foo` wasn't found"), "{}", err);
+    let err = probe(LibraryKind::HeaderOnly, "headers").unwrap_err();
+    assert!(err.to_string().contains("include path `/usr/include/headers` doesn't exist"));
+
+    let sdk = TempDir::new("kind");
+    for name in ["foo", "bar", "baz", "foobar", "foobaz"] {
+        let dir = sdk.join(format!("System/Library/Frameworks/{}.framework", name));
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    env::set_var("SDKROOT", &*sdk);
+    let lib = probe(LibraryKind::FrameworksOnly, "framework");
+    env::remove_var("SDKROOT");
+    lib.unwrap();
+    probe(LibraryKind::Libs, "foo").unwrap();
+}
+
+#[test]
+#[cfg(feature = "testing")]
+fn extra_paths() {
+    use pkg_config::testing::capture_metadata;
+
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    config.extra_include_path("/out/include").extra_link_path("/out/lib");
+    let (lib, metadata) = capture_metadata(|| config.probe("foo"));
+    let lib = lib.unwrap();
+    assert_eq!(lib.include_paths,
+               vec![PathBuf::from("/usr/include/valgrind"), PathBuf::from("/out/include")]);
+    assert_eq!(lib.link_paths,
+               vec![PathBuf::from("/usr/lib/valgrind"), PathBuf::from("/out/lib")]);
+    assert!(metadata.contains(&"cargo:rustc-link-search=native=/out/lib".to_string()));
+
+    let includes = config.probe_headers("headers").unwrap();
+    assert_eq!(includes.include_paths.last(), Some(&PathBuf::from("/out/include")));
+}
+
+#[test]
+fn probe_from_metadata() {
+    let _g = LOCK.lock();
+    reset();
+    let manifest_dir = env::var_os("CARGO_MANIFEST_DIR").unwrap();
+    env::set_var("CARGO_MANIFEST_DIR", env::current_dir().unwrap().join("tests/metadata"));
+    let libraries = pkg_config::probe_from_metadata();
+    env::set_var("CARGO_FEATURE_GATED", "1");
+    let gated = pkg_config::probe_from_metadata();
+    env::remove_var("CARGO_FEATURE_GATED");
+    env::set_var("CARGO_MANIFEST_DIR", manifest_dir);
+
+    let libraries = libraries.unwrap();
+    let mut keys = libraries.keys().collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, ["escape", "valgrind"]);
+    assert!(libraries["valgrind"].libs.contains(&"gcc".to_string()));
+    assert!(gated.unwrap_err().is_not_found());
+}
+
+#[test]
+fn cfg_from_defines() {
+    let _g = LOCK.lock();
+    reset();
+    let mut config = pkg_config::Config::new();
+    let metadata = record_metadata(config.cfg_from_defines(&["CF", "HAVE_MISSING"]), "");
+    config.probe("compile-flags").unwrap();
+    let metadata = metadata.lock().unwrap();
+    assert!(metadata.contains(&"rustc-cfg=CF".to_string()));
+    assert!(!metadata.contains(&"rustc-cfg=HAVE_MISSING".to_string()));
+    assert!(metadata.contains(&"rustc-check-cfg=cfg(CF)".to_string()));
+    assert!(metadata.contains(&"rustc-check-cfg=cfg(HAVE_MISSING)".to_string()));
+}
+
+#[test]
+fn version_from_features() {
+    let _g = LOCK.lock();
+    reset();
+    let features = [("v3_8", "3.8"), ("v3_10", "3.10"), ("v3_12", "3.12")];
+    let mut config = pkg_config::Config::new();
+    assert_eq!(config.version_from_features(&features), None);
+
+    env::set_var("CARGO_FEATURE_V3_8", "1");
+    env::set_var("CARGO_FEATURE_V3_10", "1");
+    assert_eq!(config.version_from_features(&features).as_deref(), Some("v3_10"));
+    assert!(config.probe("foo").is_ok());
+
+    env::set_var("CARGO_FEATURE_V3_12", "1");
+    let feature = config.version_from_features(&features);
+    env::remove_var("CARGO_FEATURE_V3_8");
+    env::remove_var("CARGO_FEATURE_V3_10");
+    env::remove_var("CARGO_FEATURE_V3_12");
+    assert_eq!(feature.as_deref(), Some("v3_12"));
+    match config.probe("foo") {
+        Err(Error::VersionMismatch { ref found, .. }) if found == "3.10.0.SVN" => {}
+        x => panic!("Error::VersionMismatch expected, found `{:?}`", x),
+    }
+}